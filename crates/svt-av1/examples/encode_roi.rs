@@ -1,6 +1,7 @@
 use svt_av1::config::{BitDepth, ColorFormat, ConfigExt, IntraRefreshType, Profile, RcMode, Tier};
 use svt_av1::encoder::{
-    BufferHeader, Configuration, Encoder, PrivDataNode, RoiMap, RoiMapEvent, ROI_MAP_EVENT,
+    BufferHeader, Configuration, Encoder, EncoderBuilder, PrivDataNode, RoiMap, RoiMapEvent,
+    ROI_MAP_EVENT,
 };
 use svt_av1_sys as sys;
 
@@ -17,7 +18,8 @@ use svt_av1_sys as sys;
 // data node that would be attached to an `EbBufferHeaderType`.
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let (_enc, mut cfg): (Encoder, Configuration) = Encoder::init_default()?;
+    let mut builder: EncoderBuilder = Encoder::builder()?;
+    let cfg: &mut Configuration = builder.config_mut();
 
     // Configure encoder and enable ROI maps.
     cfg.set_resolution(320, 240)
@@ -71,11 +73,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     pic.pic_type = sys::enc_bindings::EbAv1PictureType_EB_AV1_INVALID_PICTURE;
     pic.p_app_private = &mut node as *mut _ as *mut std::ffi::c_void;
 
-    // In a full encode flow you would then set parameters and send the picture:
-    // enc.set_parameter(&cfg)?;
-    // enc.init()?;
+    // In a full encode flow you would then build the encoder and send the picture:
+    // let mut enc = builder.build()?;
     // enc.send_picture(&mut pic)?;
-    // enc.drain_packets(true, |_| {})?;
+    // let packets = enc.finish()?;
     // The SVT-AV1 library deep copies `p_app_private`, so dropping `node` after
     // send_picture is fine.
 
@@ -0,0 +1,121 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::encoder::{Encoder, Packet};
+use crate::frame::Frame;
+use crate::{Error, Result};
+
+/// Frames queued ahead of the input thread. The input thread blocks inside
+/// `send_picture` while the library's input buffer pool is exhausted, so the
+/// pool rather than this queue decides how far callers can run ahead.
+const FRAME_QUEUE: usize = 1;
+/// Packets buffered for a consumer that is not polling the stream.
+const PACKET_QUEUE: usize = 8;
+
+fn input_closed() -> Error {
+    Error::InvalidArgument("encoder input is closed".into())
+}
+
+/// Runs an [`Encoder`] behind async channels.
+///
+/// The encoder is [`split`](Encoder::split) across two dedicated threads: one
+/// feeds frames into `send_frame`, the other forwards packets from
+/// `PacketReceiver::recv`. Neither blocks the async runtime, and any executor
+/// can drive the futures; the channels are tokio's runtime-agnostic `mpsc`.
+///
+/// Output is a `Stream<Item = Result<Packet>>`. It ends after the
+/// end-of-stream packet, which follows [`AsyncEncoder::finish`] once every
+/// [`AsyncFrameSender`] has been dropped.
+pub struct AsyncEncoder {
+    frames: Option<mpsc::Sender<Frame>>,
+    packets: mpsc::Receiver<Result<Packet>>,
+}
+
+impl AsyncEncoder {
+    /// Moves `encoder` onto its worker threads.
+    pub fn new(encoder: Encoder) -> Result<Self> {
+        let (frames_tx, mut frames_rx) = mpsc::channel::<Frame>(FRAME_QUEUE);
+        let (packets_tx, packets_rx) = mpsc::channel(PACKET_QUEUE);
+        let (mut sender, receiver) = encoder.split();
+
+        let errors = packets_tx.clone();
+        thread::Builder::new()
+            .name("svt-av1-input".into())
+            .spawn(move || {
+                while let Some(frame) = frames_rx.blocking_recv() {
+                    if let Err(e) = sender.send_frame(&frame) {
+                        let _ = errors.blocking_send(Err(e));
+                        break;
+                    }
+                }
+                if let Err(e) = sender.finish() {
+                    let _ = errors.blocking_send(Err(e));
+                }
+            })?;
+        thread::Builder::new()
+            .name("svt-av1-output".into())
+            .spawn(move || {
+                for packet in receiver {
+                    let failed = packet.is_err();
+                    if packets_tx.blocking_send(packet).is_err() || failed {
+                        break;
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            frames: Some(frames_tx),
+            packets: packets_rx,
+        })
+    }
+
+    /// Queues a frame, waiting while the encoder's input pool is full.
+    pub async fn send(&self, frame: Frame) -> Result<()> {
+        let frames = self.frames.as_ref().ok_or_else(input_closed)?;
+        frames.send(frame).await.map_err(|_| input_closed())
+    }
+
+    /// Returns a cloneable sender for feeding frames from other tasks, or
+    /// `None` after [`AsyncEncoder::finish`].
+    pub fn sender(&self) -> Option<AsyncFrameSender> {
+        self.frames
+            .clone()
+            .map(|frames| AsyncFrameSender { frames })
+    }
+
+    /// Closes this encoder's input. End of stream is signalled once queued
+    /// frames are sent and every [`AsyncFrameSender`] is dropped.
+    pub fn finish(&mut self) {
+        self.frames = None;
+    }
+
+    /// Waits for the next packet; `None` after end of stream.
+    pub async fn next_packet(&mut self) -> Option<Result<Packet>> {
+        self.packets.recv().await
+    }
+}
+
+impl Stream for AsyncEncoder {
+    type Item = Result<Packet>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().packets.poll_recv(cx)
+    }
+}
+
+/// Feeds frames to an [`AsyncEncoder`] from another task.
+#[derive(Clone)]
+pub struct AsyncFrameSender {
+    frames: mpsc::Sender<Frame>,
+}
+
+impl AsyncFrameSender {
+    /// See [`AsyncEncoder::send`].
+    pub async fn send(&self, frame: Frame) -> Result<()> {
+        self.frames.send(frame).await.map_err(|_| input_closed())
+    }
+}
@@ -0,0 +1,323 @@
+use crate::container::av1c::Av1CodecConfig;
+use crate::container::bmff::{make_box, make_full_box};
+use crate::container::strip_temporal_delimiters;
+use crate::frame::Frame;
+use crate::obu::{parse_obus, ObuType};
+use crate::{Error, Result};
+
+const COLOR_ITEM_ID: u16 = 1;
+const ALPHA_ITEM_ID: u16 = 2;
+const ALPHA_URN: &[u8] = b"urn:mpeg:mpegB:cicp:systems:auxiliary:alpha\0";
+
+/// CICP colour description stored in the `colr` (`nclx`) property and passed to
+/// the encoder's colour description fields.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Nclx {
+    pub color_primaries: u16,
+    pub transfer_characteristics: u16,
+    pub matrix_coefficients: u16,
+    pub full_range: bool,
+}
+
+impl Default for Nclx {
+    /// sRGB content converted with BT.601 coefficients, the common case for images.
+    fn default() -> Self {
+        Self {
+            color_primaries: 1,
+            transfer_characteristics: 13,
+            matrix_coefficients: 6,
+            full_range: true,
+        }
+    }
+}
+
+/// Options for [`encode_image`].
+#[derive(Clone, Copy, Debug)]
+pub struct AvifOptions<'a> {
+    /// Quantizer (0-63) used for the color image.
+    pub qp: u32,
+    /// Quantizer used for the alpha image; `None` reuses `qp`.
+    pub alpha_qp: Option<u32>,
+    /// Encoder preset (`enc_mode`); lower is slower and better.
+    pub preset: i8,
+    pub color: Nclx,
+    /// Alpha plane stored as an auxiliary image. Only the luma plane of this
+    /// frame is used; it must match the color image's size and bit depth.
+    pub alpha: Option<&'a Frame>,
+}
+
+impl Default for AvifOptions<'_> {
+    fn default() -> Self {
+        Self {
+            qp: 25,
+            alpha_qp: None,
+            preset: 6,
+            color: Nclx::default(),
+            alpha: None,
+        }
+    }
+}
+
+/// One coded AV1 image item.
+#[derive(Clone, Debug)]
+pub struct AvifItem {
+    pub width: u32,
+    pub height: u32,
+    pub config: Av1CodecConfig,
+    /// Sequence header and frame OBUs, without temporal delimiters.
+    pub data: Vec<u8>,
+}
+
+impl AvifItem {
+    /// Builds an item from a stream header and the coded frame. The sequence
+    /// header is prepended to `frame` if it does not already carry one.
+    pub fn new(width: u32, height: u32, stream_header: &[u8], frame: &[u8]) -> Result<Self> {
+        let config = Av1CodecConfig::from_sequence_header(stream_header)?;
+        let frame = strip_temporal_delimiters(frame)?;
+        let has_sequence_header = parse_obus(&frame)?
+            .iter()
+            .any(|obu| obu.obu_type() == ObuType::SequenceHeader);
+        let data = if has_sequence_header {
+            frame
+        } else {
+            let mut data = config.config_obus.clone();
+            data.extend_from_slice(&frame);
+            data
+        };
+        Ok(Self {
+            width,
+            height,
+            config,
+            data,
+        })
+    }
+}
+
+/// Encodes `frame` as a single intra picture and returns a complete `.avif` file.
+#[cfg(feature = "encoder")]
+pub fn encode_image(frame: &Frame, options: AvifOptions) -> Result<Vec<u8>> {
+    use crate::config::ColorFormat;
+
+    let color = encode_item(frame, options.qp, &options)?;
+    let alpha = match options.alpha {
+        Some(alpha) => {
+            if alpha.width() != frame.width()
+                || alpha.height() != frame.height()
+                || alpha.bit_depth() != frame.bit_depth()
+            {
+                return Err(Error::InvalidArgument(
+                    "alpha plane must match the color image size and bit depth".into(),
+                ));
+            }
+            // The encoder has no monochrome input path: code the alpha plane as
+            // luma with neutral 4:2:0 chroma. `write_avif` still declares one
+            // channel for the item; see the note on `pixi` there.
+            let mut input = Frame::new(
+                alpha.width(),
+                alpha.height(),
+                alpha.bit_depth(),
+                ColorFormat::Yuv420,
+            );
+            for y in 0..alpha.height() as usize {
+                input.row_mut(0, y).copy_from_slice(alpha.row(0, y));
+            }
+            let mid = 1u16 << (alpha.bit_depth().bits() - 1);
+            for p in 1..3 {
+                if input.bytes_per_sample() == 1 {
+                    input.plane_mut(p).fill(mid as u8);
+                } else {
+                    for sample in input.plane_mut(p).chunks_exact_mut(2) {
+                        sample.copy_from_slice(&mid.to_le_bytes());
+                    }
+                }
+            }
+            let qp = options.alpha_qp.unwrap_or(options.qp);
+            Some(encode_item(&input, qp, &options)?)
+        }
+        None => None,
+    };
+    write_avif(&color, alpha.as_ref(), options.color)
+}
+
+#[cfg(feature = "encoder")]
+fn encode_item(frame: &Frame, qp: u32, options: &AvifOptions) -> Result<AvifItem> {
+    use crate::config::{ColorRange, ConfigExt, RcMode};
+    use crate::encoder::Encoder;
+
+    let mut builder = Encoder::builder()?;
+    let cfg = builder.config_mut();
+    cfg.set_resolution(frame.width(), frame.height())
+        .set_frame_rate(1, 1)
+        .set_bit_depth(frame.bit_depth())
+        .set_color_format(frame.color_format())
+        .set_rc_mode(RcMode::CqpOrCrf)
+        .set_qp(qp)
+        .set_color_range(if options.color.full_range {
+            ColorRange::Full
+        } else {
+            ColorRange::Studio
+        });
+    cfg.enc_mode = options.preset as _;
+    cfg.color_primaries = options.color.color_primaries as _;
+    cfg.transfer_characteristics = options.color.transfer_characteristics as _;
+    cfg.matrix_coefficients = options.color.matrix_coefficients as _;
+    // Still-picture mode: reduced still picture header, single intra frame.
+    let mut enc = builder.parameter("avif", "1")?.build()?;
+
+    let sequence_header = enc.sequence_header()?;
+    enc.send_frame(frame)?;
+
+    let mut coded = Vec::new();
+    for packet in enc.finish()? {
+        coded.extend_from_slice(packet.data());
+    }
+    if coded.is_empty() {
+        return Err(Error::InvalidData("encoder produced no picture".into()));
+    }
+    AvifItem::new(
+        frame.width(),
+        frame.height(),
+        &sequence_header.bytes,
+        &coded,
+    )
+}
+
+/// Writes an AVIF file holding `color` as the primary item and, optionally,
+/// `alpha` as its auxiliary alpha image.
+///
+/// The file is `ftyp`, a `meta` box (`hdlr`, `pitm`, `iloc`, `iinf`, `iref` and
+/// `iprp` with `ispe`/`pixi`/`av1C`/`colr`/`auxC` properties) and one `mdat`.
+pub fn write_avif(color: &AvifItem, alpha: Option<&AvifItem>, nclx: Nclx) -> Result<Vec<u8>> {
+    if let Some(alpha) = alpha {
+        if (alpha.width, alpha.height) != (color.width, color.height) {
+            return Err(Error::InvalidArgument(
+                "alpha item must match the color item size".into(),
+            ));
+        }
+    }
+    let items: Vec<(u16, &AvifItem)> = std::iter::once((COLOR_ITEM_ID, color))
+        .chain(alpha.map(|a| (ALPHA_ITEM_ID, a)))
+        .collect();
+
+    let mut ftyp = b"avif".to_vec();
+    ftyp.extend_from_slice(&0u32.to_be_bytes());
+    for brand in [b"avif", b"mif1", b"miaf"] {
+        ftyp.extend_from_slice(brand);
+    }
+    let ftyp = make_box(b"ftyp", &ftyp);
+
+    // `iloc` offsets are absolute, so build `meta` once to learn its size.
+    let meta_len = meta(&items, nclx, 0).len();
+    let mdat_start = (ftyp.len() + meta_len + 8) as u32;
+    let meta = meta(&items, nclx, mdat_start);
+    debug_assert_eq!(meta.len(), meta_len);
+
+    let mut mdat = Vec::new();
+    for (_, item) in &items {
+        mdat.extend_from_slice(&item.data);
+    }
+    let mut out = ftyp;
+    out.extend(meta);
+    out.extend(make_box(b"mdat", &mdat));
+    Ok(out)
+}
+
+fn meta(items: &[(u16, &AvifItem)], nclx: Nclx, mdat_start: u32) -> Vec<u8> {
+    let mut hdlr = vec![0; 4];
+    hdlr.extend_from_slice(b"pict");
+    hdlr.extend_from_slice(&[0; 12]);
+    hdlr.push(0);
+    let mut meta = make_full_box(b"hdlr", 0, 0, &hdlr);
+
+    meta.extend(make_full_box(b"pitm", 0, 0, &COLOR_ITEM_ID.to_be_bytes()));
+
+    // offset_size = 4, length_size = 4, base_offset_size = 0, reserved
+    let mut iloc = vec![0x44, 0x00];
+    iloc.extend_from_slice(&(items.len() as u16).to_be_bytes());
+    let mut offset = mdat_start;
+    for (id, item) in items {
+        iloc.extend_from_slice(&id.to_be_bytes());
+        iloc.extend_from_slice(&0u16.to_be_bytes()); // data_reference_index
+        iloc.extend_from_slice(&1u16.to_be_bytes()); // extent_count
+        iloc.extend_from_slice(&offset.to_be_bytes());
+        iloc.extend_from_slice(&(item.data.len() as u32).to_be_bytes());
+        offset += item.data.len() as u32;
+    }
+    meta.extend(make_full_box(b"iloc", 0, 0, &iloc));
+
+    let mut iinf = (items.len() as u16).to_be_bytes().to_vec();
+    for (id, _) in items {
+        let mut infe = id.to_be_bytes().to_vec();
+        infe.extend_from_slice(&0u16.to_be_bytes()); // item_protection_index
+        infe.extend_from_slice(b"av01");
+        infe.extend_from_slice(if *id == COLOR_ITEM_ID {
+            b"Color\0"
+        } else {
+            b"Alpha\0"
+        });
+        iinf.extend(make_full_box(b"infe", 2, 0, &infe));
+    }
+    meta.extend(make_full_box(b"iinf", 0, 0, &iinf));
+
+    if items.len() > 1 {
+        let mut auxl = ALPHA_ITEM_ID.to_be_bytes().to_vec();
+        auxl.extend_from_slice(&1u16.to_be_bytes());
+        auxl.extend_from_slice(&COLOR_ITEM_ID.to_be_bytes());
+        meta.extend(make_full_box(b"iref", 0, 0, &make_box(b"auxl", &auxl)));
+    }
+
+    // Property indices in `ipma` are 1-based positions in `ipco`; the high bit
+    // marks a property as essential.
+    let mut ipco = Vec::new();
+    let mut ipma = (items.len() as u32).to_be_bytes().to_vec();
+    let mut index = 0u8;
+    let mut add = |ipco: &mut Vec<u8>, property: Vec<u8>| {
+        ipco.extend(property);
+        index += 1;
+        index
+    };
+    let mut ispe = items[0].1.width.to_be_bytes().to_vec();
+    ispe.extend_from_slice(&items[0].1.height.to_be_bytes());
+    let ispe = add(&mut ipco, make_full_box(b"ispe", 0, 0, &ispe));
+    for (id, item) in items {
+        // `pixi` describes the reconstructed image and `av1C` the bitstream,
+        // so an alpha item has one channel even when it is coded as 4:2:0
+        // (`mono_chrome = 0`): an alpha image is defined by its luma plane
+        // alone and readers discard the chroma. libavif writes the same pair
+        // for alpha coded by encoders without 4:0:0 support, and readers only
+        // require each `pixi` depth to match the `av1C` bit depth.
+        let channels = if *id == COLOR_ITEM_ID && !item.config.monochrome {
+            3
+        } else {
+            1
+        };
+        let mut pixi = vec![channels];
+        pixi.extend(std::iter::repeat_n(
+            item.config.bit_depth(),
+            channels as usize,
+        ));
+        let mut associations = vec![
+            ispe,
+            add(&mut ipco, make_full_box(b"pixi", 0, 0, &pixi)),
+            0x80 | add(&mut ipco, make_box(b"av1C", &item.config.to_bytes())),
+        ];
+        if *id == COLOR_ITEM_ID {
+            let mut colr = b"nclx".to_vec();
+            colr.extend_from_slice(&nclx.color_primaries.to_be_bytes());
+            colr.extend_from_slice(&nclx.transfer_characteristics.to_be_bytes());
+            colr.extend_from_slice(&nclx.matrix_coefficients.to_be_bytes());
+            colr.push((nclx.full_range as u8) << 7);
+            associations.push(add(&mut ipco, make_box(b"colr", &colr)));
+        } else {
+            associations.push(0x80 | add(&mut ipco, make_full_box(b"auxC", 0, 0, ALPHA_URN)));
+        }
+        ipma.extend_from_slice(&id.to_be_bytes());
+        ipma.push(associations.len() as u8);
+        ipma.extend(associations);
+    }
+    let mut iprp = make_box(b"ipco", &ipco);
+    iprp.extend(make_full_box(b"ipma", 0, 0, &ipma));
+    meta.extend(make_box(b"iprp", &iprp));
+
+    make_full_box(b"meta", 0, 0, &meta)
+}
@@ -0,0 +1,270 @@
+use std::io::Write;
+
+use crate::obu::{read_leb128, write_leb128, ObuHeader, ObuReader, ObuType};
+use crate::{Error, Result};
+
+/// How OBUs are delimited in a byte stream.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Framing {
+    /// Low-overhead bitstream format (AV1 spec section 5, `.obu` files): every
+    /// OBU carries a size field and each temporal unit starts with a temporal
+    /// delimiter. This is what the encoder emits.
+    Section5,
+    /// Length-delimited bitstream format (AV1 spec Annex B): temporal units and
+    /// frame units are prefixed with their sizes, OBUs with `obu_length` and
+    /// written without a size field.
+    AnnexB,
+    /// The per-sample form stored by ISO-BMFF and Matroska: a single temporal
+    /// unit with its temporal delimiter removed and size fields on every OBU.
+    Sample,
+}
+
+/// An OBU owning its payload.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct OwnedObu {
+    pub header: ObuHeader,
+    pub payload: Vec<u8>,
+}
+
+impl OwnedObu {
+    pub fn obu_type(&self) -> ObuType {
+        self.header.obu_type
+    }
+
+    fn write(&self, out: &mut Vec<u8>, size_field: bool) {
+        let header = ObuHeader {
+            has_size_field: size_field,
+            ..self.header
+        };
+        out.extend(header.to_bytes());
+        if size_field {
+            write_leb128(out, self.payload.len() as u64);
+        }
+        out.extend_from_slice(&self.payload);
+    }
+
+    fn to_bytes(&self, size_field: bool) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.payload.len() + 10);
+        self.write(&mut out, size_field);
+        out
+    }
+}
+
+/// All OBUs sharing one presentation time, independent of framing.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TemporalUnit {
+    pub obus: Vec<OwnedObu>,
+}
+
+impl TemporalUnit {
+    /// Serializes the temporal unit. A temporal delimiter is inserted for the
+    /// Section 5 and Annex B framings if missing and dropped for `Sample`.
+    pub fn write(&self, framing: Framing, out: &mut Vec<u8>) {
+        let body = self
+            .obus
+            .iter()
+            .filter(|obu| obu.obu_type() != ObuType::TemporalDelimiter);
+        let delimiter = OwnedObu {
+            header: ObuHeader {
+                obu_type: ObuType::TemporalDelimiter,
+                extension: None,
+                has_size_field: true,
+            },
+            payload: Vec::new(),
+        };
+        match framing {
+            Framing::Sample => body.for_each(|obu| obu.write(out, true)),
+            Framing::Section5 => {
+                delimiter.write(out, true);
+                body.for_each(|obu| obu.write(out, true));
+            }
+            Framing::AnnexB => {
+                let mut units: Vec<Vec<&OwnedObu>> = vec![vec![&delimiter]];
+                let mut has_frame = false;
+                for obu in body {
+                    let starts_frame =
+                        matches!(obu.obu_type(), ObuType::FrameHeader | ObuType::Frame);
+                    if starts_frame && has_frame {
+                        units.push(Vec::new());
+                    }
+                    has_frame |= starts_frame;
+                    units.last_mut().unwrap().push(obu);
+                }
+                let mut temporal_unit = Vec::new();
+                for unit in units {
+                    let mut frame_unit = Vec::new();
+                    for obu in unit {
+                        let bytes = obu.to_bytes(false);
+                        write_leb128(&mut frame_unit, bytes.len() as u64);
+                        frame_unit.extend(bytes);
+                    }
+                    write_leb128(&mut temporal_unit, frame_unit.len() as u64);
+                    temporal_unit.extend(frame_unit);
+                }
+                write_leb128(out, temporal_unit.len() as u64);
+                out.extend(temporal_unit);
+            }
+        }
+    }
+
+    pub fn to_bytes(&self, framing: Framing) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write(framing, &mut out);
+        out
+    }
+}
+
+fn collect_obus(data: &[u8]) -> Result<Vec<OwnedObu>> {
+    ObuReader::new(data)
+        .map(|obu| {
+            obu.map(|obu| OwnedObu {
+                header: obu.header,
+                payload: obu.payload.to_vec(),
+            })
+        })
+        .collect()
+}
+
+fn split_leb(data: &[u8], what: &str) -> Result<(usize, usize)> {
+    let (size, len) = read_leb128(data)?;
+    usize::try_from(size)
+        .ok()
+        .filter(|size| len + size <= data.len())
+        .map(|size| (len, size))
+        .ok_or_else(|| Error::InvalidData(format!("{what} size exceeds buffer")))
+}
+
+/// Splits `data` into temporal units. `Sample` input is a single temporal unit.
+pub fn temporal_units(data: &[u8], framing: Framing) -> Result<Vec<TemporalUnit>> {
+    match framing {
+        Framing::Sample => Ok(vec![TemporalUnit {
+            obus: collect_obus(data)?,
+        }]),
+        Framing::Section5 => {
+            let mut units: Vec<TemporalUnit> = Vec::new();
+            for obu in collect_obus(data)? {
+                if obu.obu_type() == ObuType::TemporalDelimiter || units.is_empty() {
+                    units.push(TemporalUnit::default());
+                }
+                units.last_mut().unwrap().obus.push(obu);
+            }
+            Ok(units)
+        }
+        Framing::AnnexB => {
+            let mut units = Vec::new();
+            let mut rest = data;
+            while !rest.is_empty() {
+                let (len, size) = split_leb(rest, "temporal unit")?;
+                let mut tu = &rest[len..len + size];
+                rest = &rest[len + size..];
+                let mut unit = TemporalUnit::default();
+                while !tu.is_empty() {
+                    let (len, size) = split_leb(tu, "frame unit")?;
+                    let mut fu = &tu[len..len + size];
+                    tu = &tu[len + size..];
+                    while !fu.is_empty() {
+                        let (len, size) = split_leb(fu, "OBU")?;
+                        let obu = &fu[len..len + size];
+                        fu = &fu[len + size..];
+                        let (header, header_len) = ObuHeader::parse(obu)?;
+                        let payload = if header.has_size_field {
+                            let body = &obu[header_len..];
+                            let (len, size) = split_leb(body, "OBU payload")?;
+                            &body[len..len + size]
+                        } else {
+                            &obu[header_len..]
+                        };
+                        unit.obus.push(OwnedObu {
+                            header,
+                            payload: payload.to_vec(),
+                        });
+                    }
+                }
+                units.push(unit);
+            }
+            Ok(units)
+        }
+    }
+}
+
+/// Converts a bitstream between framings. Converting to `Sample` requires the
+/// input to hold exactly one temporal unit; use [`temporal_units`] to get one
+/// sample per temporal unit.
+pub fn convert(data: &[u8], from: Framing, to: Framing) -> Result<Vec<u8>> {
+    let units = temporal_units(data, from)?;
+    if to == Framing::Sample && units.len() > 1 {
+        return Err(Error::InvalidArgument(format!(
+            "sample framing holds one temporal unit, input has {}",
+            units.len()
+        )));
+    }
+    let mut out = Vec::with_capacity(data.len() + 16);
+    for unit in &units {
+        unit.write(to, &mut out);
+    }
+    Ok(out)
+}
+
+/// Writes temporal units to `inner` in the chosen framing.
+///
+/// Input is in the Section 5 form the encoder emits, one temporal unit per
+/// call. With `Framing::Sample` the samples are written back to back; use
+/// [`BitstreamWriter::unit_sizes`] to find their boundaries.
+pub struct BitstreamWriter<W: Write> {
+    inner: W,
+    framing: Framing,
+    unit_sizes: Vec<usize>,
+}
+
+impl<W: Write> BitstreamWriter<W> {
+    pub fn new(inner: W, framing: Framing) -> Self {
+        Self {
+            inner,
+            framing,
+            unit_sizes: Vec::new(),
+        }
+    }
+
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Converts and writes one temporal unit given in Section 5 form.
+    pub fn write_temporal_unit(&mut self, data: &[u8]) -> Result<()> {
+        let unit = TemporalUnit {
+            obus: collect_obus(data)?,
+        };
+        self.write_unit(&unit)
+    }
+
+    /// Writes an already split temporal unit.
+    pub fn write_unit(&mut self, unit: &TemporalUnit) -> Result<()> {
+        let bytes = unit.to_bytes(self.framing);
+        self.inner.write_all(&bytes)?;
+        self.unit_sizes.push(bytes.len());
+        Ok(())
+    }
+
+    /// Writes the data of an encoder packet.
+    #[cfg(feature = "encoder")]
+    pub fn write_packet(&mut self, packet: &crate::encoder::Packet) -> Result<()> {
+        if packet.data().is_empty() {
+            return Ok(());
+        }
+        self.write_temporal_unit(packet.data())
+    }
+
+    /// Size in bytes of every temporal unit written so far.
+    pub fn unit_sizes(&self) -> &[usize] {
+        &self.unit_sizes
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::BTreeMap;
+use std::io::{Seek, Write};
+use std::ops::Range;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::bitstream::{BitstreamWriter, Framing};
+use crate::container::ivf::IvfWriter;
+use crate::encoder::{Configuration, Encoder, KeyframePolicy};
+use crate::frame::Frame;
+use crate::{Error, Result};
+
+/// Where one chunk ends and the next begins, by frame index in input order.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChunkSplit {
+    /// A new chunk every `n` frames.
+    Interval(usize),
+    /// A new chunk at every listed frame index (e.g. scene cuts), and after
+    /// `max_len` frames without one.
+    SceneCuts { cuts: Vec<usize>, max_len: usize },
+}
+
+impl ChunkSplit {
+    /// Whether frame `index` starts a new chunk when the current one began
+    /// at `chunk_start`.
+    fn starts_chunk(&self, index: usize, chunk_start: usize) -> bool {
+        if index == chunk_start {
+            return false;
+        }
+        match self {
+            Self::Interval(n) => index - chunk_start >= *n,
+            Self::SceneCuts { cuts, max_len } => {
+                index - chunk_start >= *max_len || cuts.binary_search(&index).is_ok()
+            }
+        }
+    }
+}
+
+/// An encoded temporal unit of the concatenated stream.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChunkPacket {
+    pub data: Vec<u8>,
+    /// Presentation index of the frame in the whole input.
+    pub pts: i64,
+    pub dts: i64,
+    pub keyframe: bool,
+    /// Index of the chunk that produced the packet.
+    pub chunk: usize,
+}
+
+struct Job {
+    index: usize,
+    start: usize,
+    frames: Vec<Frame>,
+}
+
+type JobResult = (usize, Result<Vec<ChunkPacket>>);
+
+/// The base configuration, read by every chunk worker.
+struct BaseConfig(Configuration);
+
+// SAFETY: `Configuration` is not `Sync` only because of its pointer fields
+// (the film grain table and frame scale events). Workers never write through
+// them; each passes a copy to `svt_av1_enc_set_parameter`, which only reads
+// the data they point at.
+unsafe impl Sync for BaseConfig {}
+
+/// Encodes a source as independent chunks on parallel encoders.
+///
+/// Input is cut into chunks by a [`ChunkSplit`]. Up to `jobs` chunks are
+/// encoded at once, each by a fresh [`Encoder`], so every chunk opens with a
+/// keyframe and sequence header. Packets are then concatenated in input
+/// order into one stream. Input pts are ignored: each chunk's encoder sees
+/// pts from zero, and output pts and dts are shifted by the chunk's first
+/// frame index, so they count frames across the whole input and increase
+/// monotonically.
+///
+/// Each chunk is held in memory while it is encoded, so at most `jobs + 1`
+/// chunks of raw frames are alive at once.
+pub struct ChunkedEncoder {
+    base: Configuration,
+    split: ChunkSplit,
+    jobs: usize,
+    keyframes: KeyframePolicy,
+}
+
+impl ChunkedEncoder {
+    /// Encodes with `base` and one job per available CPU.
+    pub fn new(base: Configuration, split: ChunkSplit) -> Self {
+        Self {
+            base,
+            split,
+            jobs: thread::available_parallelism().map_or(1, |n| n.get()),
+            keyframes: KeyframePolicy::Auto,
+        }
+    }
+
+    /// Chunks encoded concurrently.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Keyframe policy evaluated on frame indices of the whole input, so
+    /// forced keyframes do not move with chunk boundaries.
+    pub fn keyframe_policy(mut self, policy: KeyframePolicy) -> Self {
+        self.keyframes = policy;
+        self
+    }
+
+    /// Encodes `frames` and passes the packets to `on_packet` in stream
+    /// order. Returns the frame range of every chunk.
+    pub fn encode<I, F>(&self, frames: I, mut on_packet: F) -> Result<Vec<Range<usize>>>
+    where
+        I: IntoIterator<Item = Result<Frame>>,
+        F: FnMut(ChunkPacket) -> Result<()>,
+    {
+        match self.split {
+            ChunkSplit::Interval(0) | ChunkSplit::SceneCuts { max_len: 0, .. } => {
+                return Err(Error::InvalidArgument(
+                    "chunks must hold at least one frame".into(),
+                ))
+            }
+            _ => {}
+        }
+        if self.jobs == 0 {
+            return Err(Error::InvalidArgument("at least one job is needed".into()));
+        }
+        crate::encoder::validate(&self.base)?;
+        let mut split = self.split.clone();
+        if let ChunkSplit::SceneCuts { cuts, .. } = &mut split {
+            cuts.sort_unstable();
+        }
+        let mut keyframes = self.keyframes.clone();
+        keyframes.normalize()?;
+
+        // A rendezvous channel: the reader hands a chunk over only when a
+        // worker is free, which bounds the frames held in memory.
+        let (jobs_tx, jobs_rx) = mpsc::sync_channel::<Job>(0);
+        let jobs_rx = Arc::new(Mutex::new(jobs_rx));
+        let (results_tx, results_rx) = mpsc::channel::<JobResult>();
+        let base = BaseConfig(self.base);
+
+        thread::scope(|scope| {
+            for i in 0..self.jobs {
+                let jobs = Arc::clone(&jobs_rx);
+                let results = results_tx.clone();
+                let (base, keyframes) = (&base, &keyframes);
+                thread::Builder::new()
+                    .name(format!("svt-av1-chunk-{i}"))
+                    .spawn_scoped(scope, move || loop {
+                        let job = jobs.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                        let Ok(job) = job else { break };
+                        let packets = encode_chunk(&base.0, keyframes, &job);
+                        if results.send((job.index, packets)).is_err() {
+                            break;
+                        }
+                    })?;
+            }
+            drop(results_tx);
+
+            let mut emitter = Emitter {
+                next: 0,
+                done: BTreeMap::new(),
+                on_packet: &mut on_packet,
+            };
+            let mut ranges = Vec::new();
+            let mut chunk = Vec::new();
+            let mut start = 0;
+            for (index, frame) in frames.into_iter().enumerate() {
+                let mut frame = frame?;
+                if split.starts_chunk(index, start) {
+                    dispatch(&jobs_tx, &mut ranges, start, std::mem::take(&mut chunk))?;
+                    start = index;
+                    while let Ok(result) = results_rx.try_recv() {
+                        emitter.accept(result)?;
+                    }
+                }
+                frame.pts = (index - start) as i64;
+                chunk.push(frame);
+            }
+            if !chunk.is_empty() {
+                dispatch(&jobs_tx, &mut ranges, start, chunk)?;
+            }
+            drop(jobs_tx);
+
+            for result in results_rx {
+                emitter.accept(result)?;
+            }
+            debug_assert_eq!(emitter.next, ranges.len());
+            Ok(ranges)
+        })
+    }
+
+    /// Encodes `frames` into an IVF file sized from the base configuration.
+    pub fn encode_ivf<I, W>(&self, frames: I, out: W) -> Result<W>
+    where
+        I: IntoIterator<Item = Result<Frame>>,
+        W: Write + Seek,
+    {
+        let mut writer = IvfWriter::from_configuration(out, &self.base)?;
+        self.encode(frames, |packet| {
+            writer.write_frame(&packet.data, packet.pts)
+        })?;
+        writer.finish()
+    }
+
+    /// Encodes `frames` into a Section 5 low-overhead OBU stream (`.obu`).
+    pub fn encode_obu<I, W>(&self, frames: I, out: W) -> Result<W>
+    where
+        I: IntoIterator<Item = Result<Frame>>,
+        W: Write,
+    {
+        let mut writer = BitstreamWriter::new(out, Framing::Section5);
+        self.encode(frames, |packet| writer.write_temporal_unit(&packet.data))?;
+        writer.flush()?;
+        Ok(writer.into_inner())
+    }
+}
+
+/// Hands the frames `start..` to a worker as the next chunk.
+fn dispatch(
+    jobs: &mpsc::SyncSender<Job>,
+    ranges: &mut Vec<Range<usize>>,
+    start: usize,
+    frames: Vec<Frame>,
+) -> Result<()> {
+    let index = ranges.len();
+    ranges.push(start..start + frames.len());
+    jobs.send(Job {
+        index,
+        start,
+        frames,
+    })
+    .map_err(|_| Error::InvalidArgument("chunk workers have stopped".into()))
+}
+
+/// Releases finished chunks to the callback in chunk order.
+struct Emitter<'a, F> {
+    next: usize,
+    done: BTreeMap<usize, Vec<ChunkPacket>>,
+    on_packet: &'a mut F,
+}
+
+impl<F: FnMut(ChunkPacket) -> Result<()>> Emitter<'_, F> {
+    fn accept(&mut self, (index, packets): JobResult) -> Result<()> {
+        self.done.insert(index, packets?);
+        while let Some(packets) = self.done.remove(&self.next) {
+            for packet in packets {
+                (self.on_packet)(packet)?;
+            }
+            self.next += 1;
+        }
+        Ok(())
+    }
+}
+
+/// Encodes one chunk on a fresh encoder, collecting packets on a second
+/// thread so the library's output queue never fills while frames are sent.
+fn encode_chunk(
+    base: &Configuration,
+    keyframes: &KeyframePolicy,
+    job: &Job,
+) -> Result<Vec<ChunkPacket>> {
+    let local = match keyframes {
+        KeyframePolicy::Auto => KeyframePolicy::Auto,
+        policy => KeyframePolicy::Pts(
+            (0..job.frames.len() as i64)
+                .filter(|&pts| policy.is_keyframe(job.start as i64 + pts))
+                .collect(),
+        ),
+    };
+    let encoder = Encoder::builder()?
+        .with_config(*base)
+        .keyframe_policy(local)
+        .build()?;
+    let (mut sender, receiver) = encoder.split();
+    let offset = job.start as i64;
+
+    thread::scope(|scope| {
+        let collector = scope.spawn(move || -> Result<Vec<ChunkPacket>> {
+            let mut packets = Vec::new();
+            for packet in receiver {
+                let packet = packet?;
+                if packet.data().is_empty() {
+                    continue;
+                }
+                packets.push(ChunkPacket {
+                    data: packet.data().to_vec(),
+                    pts: packet.pts() + offset,
+                    dts: packet.dts() + offset,
+                    keyframe: packet.is_keyframe(),
+                    chunk: job.index,
+                });
+            }
+            Ok(packets)
+        });
+        let sent = job
+            .frames
+            .iter()
+            .try_for_each(|frame| sender.send_frame(frame));
+        // Finish even after a failed send so the collector sees end of stream.
+        let finished = sender.finish();
+        let packets = collector.join().expect("chunk collector panicked");
+        sent.and(finished).and(packets)
+    })
+}
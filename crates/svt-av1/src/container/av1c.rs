@@ -0,0 +1,76 @@
+use crate::config::{BitDepth, ColorFormat};
+use crate::obu::SequenceHeader;
+use crate::Result;
+
+/// `AV1CodecConfigurationRecord` (`av1C`) shared by Matroska, ISO-BMFF and AVIF.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Av1CodecConfig {
+    pub seq_profile: u8,
+    pub seq_level_idx_0: u8,
+    pub seq_tier_0: u8,
+    pub high_bitdepth: bool,
+    pub twelve_bit: bool,
+    pub monochrome: bool,
+    pub chroma_subsampling_x: bool,
+    pub chroma_subsampling_y: bool,
+    pub chroma_sample_position: u8,
+    /// Sequence header OBU (with size field) stored as `configOBUs`.
+    pub config_obus: Vec<u8>,
+}
+
+impl Av1CodecConfig {
+    /// Builds the record from the bytes returned by `Encoder::get_stream_header`.
+    ///
+    /// The buffer may contain a temporal delimiter; only the sequence header OBU
+    /// is kept in `configOBUs`.
+    pub fn from_sequence_header(stream_header: &[u8]) -> Result<Self> {
+        Ok(Self::new(&SequenceHeader::from_bytes(stream_header)?))
+    }
+
+    /// Builds the record from a decoded sequence header.
+    pub fn new(header: &SequenceHeader) -> Self {
+        let color = &header.color;
+        Self {
+            seq_profile: header.profile as u8,
+            seq_level_idx_0: header.seq_level_idx(),
+            seq_tier_0: header.tier() as u8,
+            high_bitdepth: color.bit_depth != BitDepth::Eight,
+            twelve_bit: color.bit_depth == BitDepth::Twelve,
+            monochrome: color.color_format == ColorFormat::Yuv400,
+            chroma_subsampling_x: color.subsampling_x,
+            chroma_subsampling_y: color.subsampling_y,
+            chroma_sample_position: color.chroma_sample_position as u8,
+            config_obus: header.bytes.clone(),
+        }
+    }
+
+    /// Bit depth implied by the record (8, 10 or 12).
+    pub fn bit_depth(&self) -> u8 {
+        match (self.high_bitdepth, self.twelve_bit) {
+            (false, _) => 8,
+            (true, false) => 10,
+            (true, true) => 12,
+        }
+    }
+
+    /// Serializes the record (without any box or element header).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(4 + self.config_obus.len());
+        // marker (1) | version (7) = 1
+        out.push(0x81);
+        out.push((self.seq_profile << 5) | (self.seq_level_idx_0 & 0x1f));
+        out.push(
+            (self.seq_tier_0 << 7)
+                | ((self.high_bitdepth as u8) << 6)
+                | ((self.twelve_bit as u8) << 5)
+                | ((self.monochrome as u8) << 4)
+                | ((self.chroma_subsampling_x as u8) << 3)
+                | ((self.chroma_subsampling_y as u8) << 2)
+                | (self.chroma_sample_position & 0x3),
+        );
+        // reserved (3) | initial_presentation_delay_present (1) = 0 | reserved (4)
+        out.push(0);
+        out.extend_from_slice(&self.config_obus);
+        out
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{Error, Result};
+
+/// Wraps `payload` in a box header.
+pub fn make_box(fourcc: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(8 + payload.len());
+    out.extend_from_slice(&(8 + payload.len() as u32).to_be_bytes());
+    out.extend_from_slice(fourcc);
+    out.extend_from_slice(payload);
+    out
+}
+
+/// Wraps `payload` in a full box header carrying `version` and 24-bit `flags`.
+pub fn make_full_box(fourcc: &[u8; 4], version: u8, flags: u32, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(4 + payload.len());
+    body.extend_from_slice(&(((version as u32) << 24) | (flags & 0x00ff_ffff)).to_be_bytes());
+    body.extend_from_slice(payload);
+    make_box(fourcc, &body)
+}
+
+/// Unity transformation matrix used by `mvhd` and `tkhd`.
+pub(crate) const UNITY_MATRIX: [u32; 9] = [0x0001_0000, 0, 0, 0, 0x0001_0000, 0, 0, 0, 0x4000_0000];
+
+/// A parsed box borrowing its payload.
+#[derive(Clone, Copy, Debug)]
+pub struct BmffBox<'a> {
+    pub fourcc: [u8; 4],
+    /// Byte offset of the box header within the buffer passed to the reader.
+    pub offset: usize,
+    pub header_len: usize,
+    pub data: &'a [u8],
+}
+
+impl<'a> BmffBox<'a> {
+    /// Iterates child boxes, skipping `skip` bytes of fields before them
+    /// (e.g. 4 for a full box such as `meta`, 8 for `stsd`).
+    pub fn children(&self, skip: usize) -> BoxReader<'a> {
+        BoxReader::new(self.data.get(skip..).unwrap_or(&[]))
+    }
+
+    /// Finds the first direct child with `fourcc` (no leading fields skipped).
+    pub fn child(&self, fourcc: &[u8; 4]) -> Result<Option<BmffBox<'a>>> {
+        for b in self.children(0) {
+            let b = b?;
+            if &b.fourcc == fourcc {
+                return Ok(Some(b));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Follows a path of plain container boxes (boxes without leading fields).
+    pub fn find(&self, path: &[&[u8; 4]]) -> Result<Option<BmffBox<'a>>> {
+        let mut current = *self;
+        for fourcc in path {
+            match current.child(fourcc)? {
+                Some(b) => current = b,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(current))
+    }
+
+    /// Version and flags of a full box.
+    pub fn version_flags(&self) -> (u8, u32) {
+        let v = u32::from_be_bytes(self.data[..4].try_into().unwrap());
+        ((v >> 24) as u8, v & 0x00ff_ffff)
+    }
+}
+
+/// Iterator over consecutive boxes in a buffer.
+pub struct BoxReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BoxReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn read_box(&mut self) -> Result<BmffBox<'a>> {
+        let rest = &self.data[self.pos..];
+        if rest.len() < 8 {
+            return Err(Error::InvalidData("truncated box header".into()));
+        }
+        let size32 = u32::from_be_bytes(rest[..4].try_into().unwrap()) as u64;
+        let fourcc: [u8; 4] = rest[4..8].try_into().unwrap();
+        let (size, header_len) = match size32 {
+            0 => (rest.len() as u64, 8),
+            1 => {
+                if rest.len() < 16 {
+                    return Err(Error::InvalidData("truncated largesize box".into()));
+                }
+                (u64::from_be_bytes(rest[8..16].try_into().unwrap()), 16)
+            }
+            n => (n, 8),
+        };
+        if size < header_len as u64 || size > rest.len() as u64 {
+            return Err(Error::InvalidData(format!(
+                "box {} has invalid size {size}",
+                String::from_utf8_lossy(&fourcc)
+            )));
+        }
+        let b = BmffBox {
+            fourcc,
+            offset: self.pos,
+            header_len,
+            data: &rest[header_len..size as usize],
+        };
+        self.pos += size as usize;
+        Ok(b)
+    }
+}
+
+impl<'a> Iterator for BoxReader<'a> {
+    type Item = Result<BmffBox<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let item = self.read_box();
+        if item.is_err() {
+            self.pos = self.data.len();
+        }
+        Some(item)
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{Error, Result};
+
+/// Size value meaning "unknown size" in an 8-byte EBML size field.
+pub const UNKNOWN_SIZE: u64 = 0x00ff_ffff_ffff_ffff;
+
+/// Appends an element ID (IDs already include their length marker bits).
+pub fn write_id(buf: &mut Vec<u8>, id: u32) {
+    let bytes = id.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(3);
+    buf.extend_from_slice(&bytes[skip..]);
+}
+
+/// Appends a variable-length size using the shortest encoding.
+pub fn write_size(buf: &mut Vec<u8>, size: u64) {
+    let mut len = 1;
+    while len < 8 && size >= (1u64 << (7 * len)) - 1 {
+        len += 1;
+    }
+    write_size_fixed(buf, size, len);
+}
+
+/// Appends a variable-length size encoded on exactly `len` bytes.
+pub fn write_size_fixed(buf: &mut Vec<u8>, size: u64, len: usize) {
+    let marked = size | (1u64 << (7 * len));
+    buf.extend_from_slice(&marked.to_be_bytes()[8 - len..]);
+}
+
+/// Appends a complete element with the given payload.
+pub fn write_binary(buf: &mut Vec<u8>, id: u32, data: &[u8]) {
+    write_id(buf, id);
+    write_size(buf, data.len() as u64);
+    buf.extend_from_slice(data);
+}
+
+pub fn write_uint(buf: &mut Vec<u8>, id: u32, value: u64) {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(7);
+    write_binary(buf, id, &bytes[skip..]);
+}
+
+pub fn write_float(buf: &mut Vec<u8>, id: u32, value: f64) {
+    write_binary(buf, id, &value.to_be_bytes());
+}
+
+pub fn write_string(buf: &mut Vec<u8>, id: u32, value: &str) {
+    write_binary(buf, id, value.as_bytes());
+}
+
+/// Appends a `Void` element occupying exactly `total` bytes (at least 2).
+pub fn write_void(buf: &mut Vec<u8>, total: usize) {
+    debug_assert!(total >= 2);
+    // Use an 8-byte size field when the payload would not fit a 1-byte one so
+    // that any total >= 9 can be matched exactly.
+    let (size_len, payload) = if total - 2 < 0x7f {
+        (1, total - 2)
+    } else {
+        (8, total - 9)
+    };
+    write_id(buf, ids::VOID);
+    write_size_fixed(buf, payload as u64, size_len);
+    buf.resize(buf.len() + payload, 0);
+}
+
+/// Reads a variable-length integer, returning `(value, length)`.
+///
+/// With `keep_marker` the length marker bit is preserved (as for element IDs).
+pub fn read_vint(data: &[u8], keep_marker: bool) -> Result<(u64, usize)> {
+    let first = *data
+        .first()
+        .ok_or_else(|| Error::InvalidData("truncated EBML vint".into()))?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > 8 || data.len() < len {
+        return Err(Error::InvalidData("invalid EBML vint".into()));
+    }
+    let mut value = if keep_marker {
+        first as u64
+    } else {
+        (first as u64) & ((1u64 << (8 - len)) - 1)
+    };
+    for b in &data[1..len] {
+        value = (value << 8) | *b as u64;
+    }
+    Ok((value, len))
+}
+
+/// A parsed element borrowing its payload.
+#[derive(Clone, Copy, Debug)]
+pub struct Element<'a> {
+    pub id: u32,
+    /// Byte offset of the element header within the buffer passed to the reader.
+    pub offset: usize,
+    pub header_len: usize,
+    pub data: &'a [u8],
+}
+
+impl<'a> Element<'a> {
+    /// Iterates the children of a master element.
+    pub fn children(&self) -> EbmlReader<'a> {
+        EbmlReader::new(self.data)
+    }
+
+    /// Finds the first direct child with `id`.
+    pub fn child(&self, id: u32) -> Result<Option<Element<'a>>> {
+        for el in self.children() {
+            let el = el?;
+            if el.id == id {
+                return Ok(Some(el));
+            }
+        }
+        Ok(None)
+    }
+
+    /// All direct children with `id`.
+    pub fn children_with_id(&self, id: u32) -> Result<Vec<Element<'a>>> {
+        self.children()
+            .filter(|el| el.as_ref().map_or(true, |el| el.id == id))
+            .collect()
+    }
+
+    pub fn as_uint(&self) -> u64 {
+        self.data.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64)
+    }
+
+    pub fn as_float(&self) -> Result<f64> {
+        match self.data.len() {
+            4 => Ok(f32::from_be_bytes(self.data.try_into().unwrap()) as f64),
+            8 => Ok(f64::from_be_bytes(self.data.try_into().unwrap())),
+            n => Err(Error::InvalidData(format!("EBML float of {n} bytes"))),
+        }
+    }
+
+    pub fn as_str(&self) -> Result<&'a str> {
+        std::str::from_utf8(self.data)
+            .map(|s| s.trim_end_matches('\0'))
+            .map_err(|_| Error::InvalidData("EBML string is not UTF-8".into()))
+    }
+}
+
+/// Iterator over consecutive EBML elements in a buffer.
+///
+/// Elements with unknown size extend to the end of the buffer.
+pub struct EbmlReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> EbmlReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn read_element(&mut self) -> Result<Element<'a>> {
+        let rest = &self.data[self.pos..];
+        let (id, id_len) = read_vint(rest, true)?;
+        let (size, size_len) = read_vint(&rest[id_len..], false)?;
+        let header_len = id_len + size_len;
+        let unknown = size == (1u64 << (7 * size_len)) - 1;
+        let available = rest.len() - header_len;
+        let len = if unknown {
+            available
+        } else if size as usize > available {
+            return Err(Error::InvalidData(format!(
+                "EBML element {id:#x} overruns its parent"
+            )));
+        } else {
+            size as usize
+        };
+        let el = Element {
+            id: id as u32,
+            offset: self.pos,
+            header_len,
+            data: &rest[header_len..header_len + len],
+        };
+        self.pos += header_len + len;
+        Ok(el)
+    }
+}
+
+impl<'a> Iterator for EbmlReader<'a> {
+    type Item = Result<Element<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let item = self.read_element();
+        if item.is_err() {
+            self.pos = self.data.len();
+        }
+        Some(item)
+    }
+}
+
+/// Element IDs used by the WebM writer.
+pub mod ids {
+    pub const EBML: u32 = 0x1A45_DFA3;
+    pub const EBML_VERSION: u32 = 0x4286;
+    pub const EBML_READ_VERSION: u32 = 0x42F7;
+    pub const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+    pub const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+    pub const DOC_TYPE: u32 = 0x4282;
+    pub const DOC_TYPE_VERSION: u32 = 0x4287;
+    pub const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+    pub const VOID: u32 = 0xEC;
+
+    pub const SEGMENT: u32 = 0x1853_8067;
+    pub const SEEK_HEAD: u32 = 0x114D_9B74;
+    pub const SEEK: u32 = 0x4DBB;
+    pub const SEEK_ID: u32 = 0x53AB;
+    pub const SEEK_POSITION: u32 = 0x53AC;
+
+    pub const INFO: u32 = 0x1549_A966;
+    pub const TIMESTAMP_SCALE: u32 = 0x2AD7B1;
+    pub const DURATION: u32 = 0x4489;
+    pub const MUXING_APP: u32 = 0x4D80;
+    pub const WRITING_APP: u32 = 0x5741;
+
+    pub const TRACKS: u32 = 0x1654_AE6B;
+    pub const TRACK_ENTRY: u32 = 0xAE;
+    pub const TRACK_NUMBER: u32 = 0xD7;
+    pub const TRACK_UID: u32 = 0x73C5;
+    pub const TRACK_TYPE: u32 = 0x83;
+    pub const FLAG_LACING: u32 = 0x9C;
+    pub const CODEC_ID: u32 = 0x86;
+    pub const CODEC_PRIVATE: u32 = 0x63A2;
+    pub const DEFAULT_DURATION: u32 = 0x23E383;
+    pub const VIDEO: u32 = 0xE0;
+    pub const PIXEL_WIDTH: u32 = 0xB0;
+    pub const PIXEL_HEIGHT: u32 = 0xBA;
+
+    pub const CLUSTER: u32 = 0x1F43_B675;
+    pub const TIMESTAMP: u32 = 0xE7;
+    pub const SIMPLE_BLOCK: u32 = 0xA3;
+
+    pub const CUES: u32 = 0x1C53_BB6B;
+    pub const CUE_POINT: u32 = 0xBB;
+    pub const CUE_TIME: u32 = 0xB3;
+    pub const CUE_TRACK_POSITIONS: u32 = 0xB7;
+    pub const CUE_TRACK: u32 = 0xF7;
+    pub const CUE_CLUSTER_POSITION: u32 = 0xF1;
+}
@@ -0,0 +1,187 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use crate::{Error, Result};
+
+/// Length of the IVF file header.
+pub const HEADER_LEN: usize = 32;
+/// Length of the header in front of every frame.
+pub const FRAME_HEADER_LEN: usize = 12;
+
+/// Streams encoded AV1 temporal units into an IVF file.
+///
+/// Frame timestamps count frames, matching the time base of
+/// `frame_rate_den / frame_rate_num` seconds written in the header.
+/// Call [`IvfWriter::finish`] to patch the frame count into the header;
+/// dropping the writer does this on a best-effort basis.
+pub struct IvfWriter<W: Write + Seek> {
+    inner: Option<W>,
+    frame_count_pos: u64,
+    frame_count: u32,
+}
+
+impl<W: Write + Seek> IvfWriter<W> {
+    /// Writes the file header.
+    pub fn new(
+        mut inner: W,
+        width: u32,
+        height: u32,
+        frame_rate_num: u32,
+        frame_rate_den: u32,
+    ) -> Result<Self> {
+        let (Ok(width), Ok(height)) = (u16::try_from(width), u16::try_from(height)) else {
+            return Err(Error::InvalidArgument(format!(
+                "IVF cannot describe a {width}x{height} picture"
+            )));
+        };
+        // 0-3 "DKIF", 4-5 version, 6-7 header size, 8-11 fourcc, 12-15 size,
+        // 16-19 frame rate, 20-23 time scale, 24-27 frame count, 28-31 unused.
+        let mut head = Vec::with_capacity(HEADER_LEN);
+        head.extend_from_slice(b"DKIF");
+        head.extend_from_slice(&0u16.to_le_bytes());
+        head.extend_from_slice(&(HEADER_LEN as u16).to_le_bytes());
+        head.extend_from_slice(b"AV01");
+        head.extend_from_slice(&width.to_le_bytes());
+        head.extend_from_slice(&height.to_le_bytes());
+        head.extend_from_slice(&frame_rate_num.to_le_bytes());
+        head.extend_from_slice(&frame_rate_den.to_le_bytes());
+        head.extend_from_slice(&0u32.to_le_bytes());
+        head.extend_from_slice(&0u32.to_le_bytes());
+        let start = inner.stream_position()?;
+        inner.write_all(&head)?;
+        Ok(Self {
+            inner: Some(inner),
+            frame_count_pos: start + 24,
+            frame_count: 0,
+        })
+    }
+
+    /// Takes dimensions and frame rate from an encoder configuration.
+    #[cfg(feature = "encoder")]
+    pub fn from_configuration(inner: W, cfg: &crate::encoder::Configuration) -> Result<Self> {
+        Self::new(
+            inner,
+            cfg.source_width,
+            cfg.source_height,
+            cfg.frame_rate_numerator,
+            cfg.frame_rate_denominator,
+        )
+    }
+
+    fn inner(&mut self) -> &mut W {
+        self.inner.as_mut().expect("writer already finished")
+    }
+
+    /// Appends one temporal unit with its pts.
+    pub fn write_frame(&mut self, data: &[u8], pts: i64) -> Result<()> {
+        let mut head = [0u8; FRAME_HEADER_LEN];
+        head[..4].copy_from_slice(&(data.len() as u32).to_le_bytes());
+        head[4..].copy_from_slice(&pts.to_le_bytes());
+        let inner = self.inner();
+        inner.write_all(&head)?;
+        inner.write_all(data)?;
+        self.frame_count = self.frame_count.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Appends an encoder packet using its pts.
+    #[cfg(feature = "encoder")]
+    pub fn write_packet(&mut self, packet: &crate::encoder::Packet) -> Result<()> {
+        if packet.data().is_empty() {
+            return Ok(());
+        }
+        self.write_frame(packet.data(), packet.pts())
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.frame_count
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        let (pos, count) = (self.frame_count_pos, self.frame_count);
+        let inner = self.inner();
+        let end = inner.stream_position()?;
+        inner.seek(SeekFrom::Start(pos))?;
+        inner.write_all(&count.to_le_bytes())?;
+        inner.seek(SeekFrom::Start(end))?;
+        inner.flush()?;
+        Ok(())
+    }
+
+    /// Patches the frame count and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.finalize()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write + Seek> Drop for IvfWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.finalize();
+        }
+    }
+}
+
+/// One frame read back from an IVF file.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct IvfFrame<'a> {
+    pub pts: i64,
+    pub data: &'a [u8],
+}
+
+/// Header fields and frames of an IVF file held in memory.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct IvfFile<'a> {
+    pub width: u16,
+    pub height: u16,
+    pub frame_rate_num: u32,
+    pub frame_rate_den: u32,
+    pub frame_count: u32,
+    pub frames: Vec<IvfFrame<'a>>,
+}
+
+impl<'a> IvfFile<'a> {
+    /// Parses an AV1 IVF file.
+    pub fn parse(data: &'a [u8]) -> Result<Self> {
+        let invalid = |what: &str| Error::InvalidData(format!("IVF: {what}"));
+        if data.len() < HEADER_LEN || &data[..4] != b"DKIF" {
+            return Err(invalid("missing DKIF header"));
+        }
+        let u16_at = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+        let u32_at = |at: usize| u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+        if &data[8..12] != b"AV01" {
+            return Err(invalid("fourcc is not AV01"));
+        }
+        let header_len = u16_at(6) as usize;
+        if header_len < HEADER_LEN || header_len > data.len() {
+            return Err(invalid("bad header size"));
+        }
+
+        let mut frames = Vec::new();
+        let mut rest = &data[header_len..];
+        while !rest.is_empty() {
+            if rest.len() < FRAME_HEADER_LEN {
+                return Err(invalid("truncated frame header"));
+            }
+            let size = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+            let pts = i64::from_le_bytes(rest[4..12].try_into().unwrap());
+            let body = &rest[FRAME_HEADER_LEN..];
+            if body.len() < size {
+                return Err(invalid("truncated frame"));
+            }
+            frames.push(IvfFrame {
+                pts,
+                data: &body[..size],
+            });
+            rest = &body[size..];
+        }
+        Ok(Self {
+            width: u16_at(12),
+            height: u16_at(14),
+            frame_rate_num: u32_at(16),
+            frame_rate_den: u32_at(20),
+            frame_count: u32_at(24),
+            frames,
+        })
+    }
+}
@@ -0,0 +1,29 @@
+pub mod av1c;
+pub mod bmff;
+pub mod ebml;
+pub mod ivf;
+pub mod mp4;
+pub mod webm;
+
+use crate::obu::{ObuReader, ObuType};
+use crate::Result;
+
+/// Removes temporal delimiter OBUs, which Matroska and ISO-BMFF samples must not carry.
+pub(crate) fn strip_temporal_delimiters(data: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len());
+    for obu in ObuReader::new(data) {
+        let obu = obu?;
+        if obu.obu_type() != ObuType::TemporalDelimiter {
+            out.extend_from_slice(obu.data);
+        }
+    }
+    Ok(out)
+}
+
+/// Converts a timestamp counted in frames into units of `1 / timescale` seconds,
+/// rounding to the nearest unit.
+pub(crate) fn frames_to_timescale(pts: i64, fps_num: u32, fps_den: u32, timescale: u64) -> i64 {
+    let num = pts as i128 * fps_den as i128 * timescale as i128;
+    let den = fps_num.max(1) as i128;
+    (2 * num + den).div_euclid(2 * den) as i64
+}
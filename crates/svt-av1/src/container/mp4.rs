@@ -0,0 +1,469 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use super::av1c::Av1CodecConfig;
+use super::bmff::{make_box, make_full_box, UNITY_MATRIX};
+use super::strip_temporal_delimiters;
+use crate::{Error, Result};
+
+const TRACK_ID: u32 = 1;
+/// `sample_depends_on = 2` (does not depend on others).
+const SYNC_SAMPLE_FLAGS: u32 = 0x0200_0000;
+/// `sample_depends_on = 1`, `sample_is_non_sync_sample = 1`.
+const NON_SYNC_SAMPLE_FLAGS: u32 = 0x0101_0000;
+
+/// Stream parameters for [`Mp4Writer`].
+#[derive(Clone, Debug)]
+pub struct Mp4Config {
+    pub width: u32,
+    pub height: u32,
+    /// Used as the media timescale; `pts`/`dts` count frames of `den / num` seconds.
+    pub frame_rate_num: u32,
+    pub frame_rate_den: u32,
+    /// Stream header from `Encoder::get_stream_header`; becomes the `av1C` box.
+    pub sequence_header: Vec<u8>,
+    /// Emit an fMP4/CMAF init segment followed by one `moof`/`mdat` pair per GOP
+    /// instead of a single progressive `mdat` with a trailing `moov`.
+    pub fragmented: bool,
+}
+
+impl Mp4Config {
+    /// Takes dimensions and frame rate from an encoder configuration.
+    #[cfg(feature = "encoder")]
+    pub fn from_configuration(
+        cfg: &crate::encoder::Configuration,
+        sequence_header: Vec<u8>,
+        fragmented: bool,
+    ) -> Self {
+        Self {
+            width: cfg.source_width,
+            height: cfg.source_height,
+            frame_rate_num: cfg.frame_rate_numerator,
+            frame_rate_den: cfg.frame_rate_denominator,
+            sequence_header,
+            fragmented,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    size: u32,
+    /// Decode time in media timescale units.
+    dts: i64,
+    /// `pts - dts` in media timescale units.
+    composition_offset: i64,
+    sync: bool,
+}
+
+/// Writes encoded AV1 packets into an ISO-BMFF (MP4) file.
+///
+/// Progressive mode writes `ftyp`, one `mdat` and a trailing `moov` built from
+/// the collected sample table. Fragmented mode writes an init segment
+/// (`ftyp` + `moov` with `mvex`) and then a `moof`/`mdat` pair for every GOP,
+/// i.e. whenever a key frame arrives. Temporal delimiters are removed from samples.
+pub struct Mp4Writer<W: Write + Seek> {
+    inner: Option<W>,
+    config: Mp4Config,
+    av1c: Av1CodecConfig,
+    /// Progressive mode: position of the `mdat` largesize field and of its payload.
+    mdat_size_pos: u64,
+    mdat_start: u64,
+    samples: Vec<Sample>,
+    /// Fragmented mode: samples and data of the GOP being collected.
+    pending: Vec<Sample>,
+    pending_data: Vec<u8>,
+    sequence_number: u32,
+    last_dts: Option<i64>,
+}
+
+impl<W: Write + Seek> Mp4Writer<W> {
+    pub fn new(mut inner: W, config: Mp4Config) -> Result<Self> {
+        if config.frame_rate_num == 0 || config.frame_rate_den == 0 {
+            return Err(Error::InvalidArgument(
+                "MP4 writer needs a non-zero frame rate".into(),
+            ));
+        }
+        let av1c = Av1CodecConfig::from_sequence_header(&config.sequence_header)?;
+        let mut writer = Self {
+            inner: None,
+            config,
+            av1c,
+            mdat_size_pos: 0,
+            mdat_start: 0,
+            samples: Vec::new(),
+            pending: Vec::new(),
+            pending_data: Vec::new(),
+            sequence_number: 0,
+            last_dts: None,
+        };
+
+        if writer.config.fragmented {
+            inner.write_all(&ftyp(b"iso6", &[b"iso6", b"cmfc", b"av01"]))?;
+            inner.write_all(&writer.moov(0))?;
+        } else {
+            inner.write_all(&ftyp(b"isom", &[b"isom", b"iso6", b"mp41", b"av01"]))?;
+            // mdat with a 64-bit largesize patched in `finish`.
+            inner.write_all(&1u32.to_be_bytes())?;
+            inner.write_all(b"mdat")?;
+            writer.mdat_size_pos = inner.stream_position()?;
+            inner.write_all(&0u64.to_be_bytes())?;
+            writer.mdat_start = inner.stream_position()?;
+        }
+        writer.inner = Some(inner);
+        Ok(writer)
+    }
+
+    fn inner(&mut self) -> &mut W {
+        self.inner.as_mut().expect("writer already finished")
+    }
+
+    fn ticks(&self, frames: i64) -> i64 {
+        frames * self.config.frame_rate_den as i64
+    }
+
+    /// Appends one temporal unit. `pts` and `dts` count frames at the configured frame rate.
+    pub fn write_frame(&mut self, data: &[u8], pts: i64, dts: i64, keyframe: bool) -> Result<()> {
+        let data = strip_temporal_delimiters(data)?;
+        let dts_ticks = self.ticks(dts);
+        if self.last_dts.is_some_and(|last| dts_ticks <= last) {
+            return Err(Error::InvalidArgument(format!(
+                "MP4 decode timestamps must increase (dts {dts})"
+            )));
+        }
+        self.last_dts = Some(dts_ticks);
+        let sample = Sample {
+            size: u32::try_from(data.len())
+                .map_err(|_| Error::InvalidArgument("sample larger than 4 GiB".into()))?,
+            dts: dts_ticks,
+            composition_offset: self.ticks(pts) - dts_ticks,
+            sync: keyframe,
+        };
+
+        if self.config.fragmented {
+            if keyframe && !self.pending.is_empty() {
+                self.flush_fragment(Some(dts_ticks))?;
+            }
+            self.pending.push(sample);
+            self.pending_data.extend_from_slice(&data);
+        } else {
+            self.inner().write_all(&data)?;
+            self.samples.push(sample);
+        }
+        Ok(())
+    }
+
+    /// Appends an encoder packet using its pts, dts and key frame flag.
+    #[cfg(feature = "encoder")]
+    pub fn write_packet(&mut self, packet: &crate::encoder::Packet) -> Result<()> {
+        if packet.data().is_empty() {
+            return Ok(());
+        }
+        self.write_frame(
+            packet.data(),
+            packet.pts(),
+            packet.dts(),
+            packet.is_keyframe(),
+        )
+    }
+
+    /// Number of `moof` fragments written so far (fragmented mode).
+    pub fn fragments_written(&self) -> u32 {
+        self.sequence_number
+    }
+
+    fn durations(&self, samples: &[Sample], next_dts: Option<i64>) -> Vec<u32> {
+        let frame = self.config.frame_rate_den as i64;
+        samples
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let next = samples.get(i + 1).map(|n| n.dts).or(next_dts);
+                next.map_or(frame, |n| n - s.dts) as u32
+            })
+            .collect()
+    }
+
+    fn flush_fragment(&mut self, next_dts: Option<i64>) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        self.sequence_number += 1;
+        let samples = std::mem::take(&mut self.pending);
+        let data = std::mem::take(&mut self.pending_data);
+        let durations = self.durations(&samples, next_dts);
+        let signed = samples.iter().any(|s| s.composition_offset < 0);
+
+        let build = |data_offset: u32| {
+            let mut mfhd = Vec::new();
+            mfhd.extend_from_slice(&self.sequence_number.to_be_bytes());
+
+            let mut tfhd = Vec::new();
+            tfhd.extend_from_slice(&TRACK_ID.to_be_bytes());
+            let mut tfdt = Vec::new();
+            tfdt.extend_from_slice(&(samples[0].dts.max(0) as u64).to_be_bytes());
+
+            // data-offset, sample-duration, -size, -flags and composition offsets present
+            let mut trun = Vec::new();
+            trun.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+            trun.extend_from_slice(&data_offset.to_be_bytes());
+            for (s, d) in samples.iter().zip(&durations) {
+                trun.extend_from_slice(&d.to_be_bytes());
+                trun.extend_from_slice(&s.size.to_be_bytes());
+                let flags = if s.sync {
+                    SYNC_SAMPLE_FLAGS
+                } else {
+                    NON_SYNC_SAMPLE_FLAGS
+                };
+                trun.extend_from_slice(&flags.to_be_bytes());
+                trun.extend_from_slice(&(s.composition_offset as i32).to_be_bytes());
+            }
+
+            let mut traf = make_full_box(b"tfhd", 0, 0x02_0000, &tfhd);
+            traf.extend(make_full_box(b"tfdt", 1, 0, &tfdt));
+            traf.extend(make_full_box(b"trun", signed as u8, 0x0f01, &trun));
+            let mut moof = make_full_box(b"mfhd", 0, 0, &mfhd);
+            moof.extend(make_box(b"traf", &traf));
+            make_box(b"moof", &moof)
+        };
+        // The data offset is relative to the start of `moof` and points past the
+        // `mdat` header; the moof size does not depend on its value.
+        let moof_len = build(0).len() as u32;
+        let moof = build(moof_len + 8);
+
+        let inner = self.inner();
+        inner.write_all(&moof)?;
+        inner.write_all(&(8 + data.len() as u32).to_be_bytes())?;
+        inner.write_all(b"mdat")?;
+        inner.write_all(&data)?;
+        Ok(())
+    }
+
+    fn sample_entry(&self) -> Vec<u8> {
+        let mut av01 = Vec::new();
+        av01.extend_from_slice(&[0; 6]);
+        av01.extend_from_slice(&1u16.to_be_bytes()); // data_reference_index
+        av01.extend_from_slice(&[0; 16]);
+        av01.extend_from_slice(&(self.config.width as u16).to_be_bytes());
+        av01.extend_from_slice(&(self.config.height as u16).to_be_bytes());
+        av01.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+        av01.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+        av01.extend_from_slice(&0u32.to_be_bytes());
+        av01.extend_from_slice(&1u16.to_be_bytes()); // frame_count
+        let mut compressor = [0u8; 32];
+        let name = b"AOM Coding";
+        compressor[0] = name.len() as u8;
+        compressor[1..=name.len()].copy_from_slice(name);
+        av01.extend_from_slice(&compressor);
+        av01.extend_from_slice(&0x0018u16.to_be_bytes());
+        av01.extend_from_slice(&(-1i16).to_be_bytes());
+        av01.extend(make_box(b"av1C", &self.av1c.to_bytes()));
+        make_box(b"av01", &av01)
+    }
+
+    fn sample_table(&self, chunk_offset: u64) -> Vec<u8> {
+        let mut stsd = 1u32.to_be_bytes().to_vec();
+        stsd.extend(self.sample_entry());
+        let mut stbl = make_full_box(b"stsd", 0, 0, &stsd);
+
+        let samples = &self.samples;
+        let durations = self.durations(samples, None);
+        let mut stts_entries: Vec<(u32, u32)> = Vec::new();
+        for d in &durations {
+            match stts_entries.last_mut() {
+                Some((count, delta)) if delta == d => *count += 1,
+                _ => stts_entries.push((1, *d)),
+            }
+        }
+        stbl.extend(make_full_box(b"stts", 0, 0, &count_pairs(&stts_entries)));
+
+        if samples.iter().any(|s| s.composition_offset != 0) {
+            let mut ctts_entries: Vec<(u32, u32)> = Vec::new();
+            for s in samples {
+                let off = s.composition_offset as i32 as u32;
+                match ctts_entries.last_mut() {
+                    Some((count, o)) if *o == off => *count += 1,
+                    _ => ctts_entries.push((1, off)),
+                }
+            }
+            let version = samples.iter().any(|s| s.composition_offset < 0) as u8;
+            stbl.extend(make_full_box(
+                b"ctts",
+                version,
+                0,
+                &count_pairs(&ctts_entries),
+            ));
+        }
+
+        let mut stsc = Vec::new();
+        if !samples.is_empty() {
+            stsc.extend_from_slice(&1u32.to_be_bytes());
+            stsc.extend_from_slice(&1u32.to_be_bytes());
+            stsc.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+            stsc.extend_from_slice(&1u32.to_be_bytes());
+        } else {
+            stsc.extend_from_slice(&0u32.to_be_bytes());
+        }
+        stbl.extend(make_full_box(b"stsc", 0, 0, &stsc));
+
+        let mut stsz = 0u32.to_be_bytes().to_vec();
+        stsz.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+        for s in samples {
+            stsz.extend_from_slice(&s.size.to_be_bytes());
+        }
+        stbl.extend(make_full_box(b"stsz", 0, 0, &stsz));
+
+        let chunks = u32::from(!samples.is_empty());
+        if chunk_offset > u32::MAX as u64 {
+            let mut co64 = chunks.to_be_bytes().to_vec();
+            if chunks > 0 {
+                co64.extend_from_slice(&chunk_offset.to_be_bytes());
+            }
+            stbl.extend(make_full_box(b"co64", 0, 0, &co64));
+        } else {
+            let mut stco = chunks.to_be_bytes().to_vec();
+            if chunks > 0 {
+                stco.extend_from_slice(&(chunk_offset as u32).to_be_bytes());
+            }
+            stbl.extend(make_full_box(b"stco", 0, 0, &stco));
+        }
+
+        if samples.iter().any(|s| !s.sync) {
+            let sync: Vec<u32> = (1..=samples.len() as u32)
+                .filter(|i| samples[*i as usize - 1].sync)
+                .collect();
+            let mut stss = (sync.len() as u32).to_be_bytes().to_vec();
+            for i in sync {
+                stss.extend_from_slice(&i.to_be_bytes());
+            }
+            stbl.extend(make_full_box(b"stss", 0, 0, &stss));
+        }
+        make_box(b"stbl", &stbl)
+    }
+
+    fn moov(&self, chunk_offset: u64) -> Vec<u8> {
+        let timescale = self.config.frame_rate_num;
+        let duration = match (self.samples.first(), self.samples.last()) {
+            (Some(first), Some(last)) => {
+                (last.dts - first.dts) as u64 + self.config.frame_rate_den as u64
+            }
+            _ => 0,
+        };
+
+        let mut mvhd = Vec::new();
+        mvhd.extend_from_slice(&[0; 8]); // creation/modification time
+        mvhd.extend_from_slice(&timescale.to_be_bytes());
+        mvhd.extend_from_slice(&(duration as u32).to_be_bytes());
+        mvhd.extend_from_slice(&0x0001_0000u32.to_be_bytes()); // rate
+        mvhd.extend_from_slice(&0x0100u16.to_be_bytes()); // volume
+        mvhd.extend_from_slice(&[0; 10]);
+        for m in UNITY_MATRIX {
+            mvhd.extend_from_slice(&m.to_be_bytes());
+        }
+        mvhd.extend_from_slice(&[0; 24]);
+        mvhd.extend_from_slice(&(TRACK_ID + 1).to_be_bytes());
+
+        let mut tkhd = Vec::new();
+        tkhd.extend_from_slice(&[0; 8]);
+        tkhd.extend_from_slice(&TRACK_ID.to_be_bytes());
+        tkhd.extend_from_slice(&[0; 4]);
+        tkhd.extend_from_slice(&(duration as u32).to_be_bytes());
+        tkhd.extend_from_slice(&[0; 8]);
+        tkhd.extend_from_slice(&[0; 8]); // layer, alternate_group, volume, reserved
+        for m in UNITY_MATRIX {
+            tkhd.extend_from_slice(&m.to_be_bytes());
+        }
+        tkhd.extend_from_slice(&(self.config.width << 16).to_be_bytes());
+        tkhd.extend_from_slice(&(self.config.height << 16).to_be_bytes());
+
+        let mut mdhd = Vec::new();
+        mdhd.extend_from_slice(&[0; 8]);
+        mdhd.extend_from_slice(&timescale.to_be_bytes());
+        mdhd.extend_from_slice(&(duration as u32).to_be_bytes());
+        mdhd.extend_from_slice(&0x55c4u16.to_be_bytes()); // "und"
+        mdhd.extend_from_slice(&[0; 2]);
+
+        let mut hdlr = vec![0; 4];
+        hdlr.extend_from_slice(b"vide");
+        hdlr.extend_from_slice(&[0; 12]);
+        hdlr.extend_from_slice(b"VideoHandler\0");
+
+        let mut dref = 1u32.to_be_bytes().to_vec();
+        dref.extend(make_full_box(b"url ", 0, 1, &[]));
+        let dinf = make_box(b"dinf", &make_full_box(b"dref", 0, 0, &dref));
+
+        let mut minf = make_full_box(b"vmhd", 0, 1, &[0; 8]);
+        minf.extend(dinf);
+        minf.extend(self.sample_table(chunk_offset));
+
+        let mut mdia = make_full_box(b"mdhd", 0, 0, &mdhd);
+        mdia.extend(make_full_box(b"hdlr", 0, 0, &hdlr));
+        mdia.extend(make_box(b"minf", &minf));
+
+        let mut trak = make_full_box(b"tkhd", 0, 3, &tkhd);
+        trak.extend(make_box(b"mdia", &mdia));
+
+        let mut moov = make_full_box(b"mvhd", 0, 0, &mvhd);
+        moov.extend(make_box(b"trak", &trak));
+        if self.config.fragmented {
+            let mut trex = Vec::new();
+            trex.extend_from_slice(&TRACK_ID.to_be_bytes());
+            trex.extend_from_slice(&1u32.to_be_bytes());
+            trex.extend_from_slice(&self.config.frame_rate_den.to_be_bytes());
+            trex.extend_from_slice(&0u32.to_be_bytes());
+            trex.extend_from_slice(&NON_SYNC_SAMPLE_FLAGS.to_be_bytes());
+            moov.extend(make_box(b"mvex", &make_full_box(b"trex", 0, 0, &trex)));
+        }
+        make_box(b"moov", &moov)
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        if self.config.fragmented {
+            self.flush_fragment(None)?;
+            self.inner().flush()?;
+            return Ok(());
+        }
+        let end = self.inner().stream_position()?;
+        let mdat_size = end - self.mdat_start + 16;
+        let (mdat_size_pos, mdat_start) = (self.mdat_size_pos, self.mdat_start);
+        let moov = self.moov(mdat_start);
+        let inner = self.inner();
+        inner.seek(SeekFrom::Start(mdat_size_pos))?;
+        inner.write_all(&mdat_size.to_be_bytes())?;
+        inner.seek(SeekFrom::Start(end))?;
+        inner.write_all(&moov)?;
+        inner.flush()?;
+        Ok(())
+    }
+
+    /// Writes the remaining fragment or the `moov` box and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.finalize()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write + Seek> Drop for Mp4Writer<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.finalize();
+        }
+    }
+}
+
+fn ftyp(major: &[u8; 4], compatible: &[&[u8; 4]]) -> Vec<u8> {
+    let mut payload = major.to_vec();
+    payload.extend_from_slice(&0u32.to_be_bytes());
+    for brand in compatible {
+        payload.extend_from_slice(*brand);
+    }
+    make_box(b"ftyp", &payload)
+}
+
+fn count_pairs(entries: &[(u32, u32)]) -> Vec<u8> {
+    let mut out = (entries.len() as u32).to_be_bytes().to_vec();
+    for (a, b) in entries {
+        out.extend_from_slice(&a.to_be_bytes());
+        out.extend_from_slice(&b.to_be_bytes());
+    }
+    out
+}
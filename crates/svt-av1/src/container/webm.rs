@@ -0,0 +1,294 @@
+use std::io::{Seek, SeekFrom, Write};
+
+use super::av1c::Av1CodecConfig;
+use super::ebml::{self, ids};
+use super::{frames_to_timescale, strip_temporal_delimiters};
+use crate::Result;
+
+/// Nanoseconds per timestamp tick; blocks are timestamped in milliseconds.
+const TIMESTAMP_SCALE: u64 = 1_000_000;
+/// Space reserved after the segment header for the SeekHead written on close.
+const SEEK_HEAD_RESERVED: usize = 96;
+
+/// Stream parameters for [`WebmWriter`].
+#[derive(Clone, Debug)]
+pub struct WebmConfig {
+    pub width: u32,
+    pub height: u32,
+    pub frame_rate_num: u32,
+    pub frame_rate_den: u32,
+    /// Stream header from `Encoder::get_stream_header`; becomes `CodecPrivate`.
+    pub sequence_header: Vec<u8>,
+}
+
+impl WebmConfig {
+    /// Takes dimensions and frame rate from an encoder configuration.
+    #[cfg(feature = "encoder")]
+    pub fn from_configuration(
+        cfg: &crate::encoder::Configuration,
+        sequence_header: Vec<u8>,
+    ) -> Self {
+        Self {
+            width: cfg.source_width,
+            height: cfg.source_height,
+            frame_rate_num: cfg.frame_rate_numerator,
+            frame_rate_den: cfg.frame_rate_denominator,
+            sequence_header,
+        }
+    }
+}
+
+struct Cluster {
+    timestamp: i64,
+    position: u64,
+    blocks: Vec<u8>,
+}
+
+/// Streams encoded AV1 packets into a WebM (Matroska) file.
+///
+/// Clusters start at every key frame, which is also recorded as a cue point.
+/// Call [`WebmWriter::finish`] to write the cues and patch the segment size,
+/// SeekHead and duration; dropping the writer does this on a best-effort basis.
+pub struct WebmWriter<W: Write + Seek> {
+    inner: Option<W>,
+    config: WebmConfig,
+    segment_size_pos: u64,
+    segment_data_start: u64,
+    info_pos: u64,
+    tracks_pos: u64,
+    duration_pos: u64,
+    cluster: Option<Cluster>,
+    cues: Vec<(i64, u64)>,
+    max_timestamp: Option<i64>,
+}
+
+impl<W: Write + Seek> WebmWriter<W> {
+    /// Writes the EBML header, segment info and track entry.
+    pub fn new(mut inner: W, config: WebmConfig) -> Result<Self> {
+        let codec_private = Av1CodecConfig::from_sequence_header(&config.sequence_header)?;
+
+        let mut head = Vec::new();
+        let mut ebml_header = Vec::new();
+        ebml::write_uint(&mut ebml_header, ids::EBML_VERSION, 1);
+        ebml::write_uint(&mut ebml_header, ids::EBML_READ_VERSION, 1);
+        ebml::write_uint(&mut ebml_header, ids::EBML_MAX_ID_LENGTH, 4);
+        ebml::write_uint(&mut ebml_header, ids::EBML_MAX_SIZE_LENGTH, 8);
+        ebml::write_string(&mut ebml_header, ids::DOC_TYPE, "webm");
+        ebml::write_uint(&mut ebml_header, ids::DOC_TYPE_VERSION, 4);
+        ebml::write_uint(&mut ebml_header, ids::DOC_TYPE_READ_VERSION, 2);
+        ebml::write_binary(&mut head, ids::EBML, &ebml_header);
+
+        let start = inner.stream_position()?;
+        ebml::write_id(&mut head, ids::SEGMENT);
+        let segment_size_pos = start + head.len() as u64;
+        ebml::write_size_fixed(&mut head, ebml::UNKNOWN_SIZE, 8);
+        let segment_data_start = start + head.len() as u64;
+        ebml::write_void(&mut head, SEEK_HEAD_RESERVED);
+
+        let info_pos = start + head.len() as u64;
+        let mut info = Vec::new();
+        ebml::write_uint(&mut info, ids::TIMESTAMP_SCALE, TIMESTAMP_SCALE);
+        ebml::write_string(&mut info, ids::MUXING_APP, "svt-av1-rs");
+        ebml::write_string(&mut info, ids::WRITING_APP, "svt-av1-rs");
+        ebml::write_float(&mut info, ids::DURATION, 0.0);
+        // The 8-byte payload is patched with the real duration on finish.
+        let duration_offset = info.len() - 8;
+        ebml::write_id(&mut head, ids::INFO);
+        ebml::write_size(&mut head, info.len() as u64);
+        let duration_pos = start + (head.len() + duration_offset) as u64;
+        head.extend_from_slice(&info);
+
+        let tracks_pos = start + head.len() as u64;
+        let mut video = Vec::new();
+        ebml::write_uint(&mut video, ids::PIXEL_WIDTH, config.width as u64);
+        ebml::write_uint(&mut video, ids::PIXEL_HEIGHT, config.height as u64);
+        let mut entry = Vec::new();
+        ebml::write_uint(&mut entry, ids::TRACK_NUMBER, 1);
+        ebml::write_uint(&mut entry, ids::TRACK_UID, 1);
+        ebml::write_uint(&mut entry, ids::TRACK_TYPE, 1);
+        ebml::write_uint(&mut entry, ids::FLAG_LACING, 0);
+        ebml::write_string(&mut entry, ids::CODEC_ID, "V_AV1");
+        ebml::write_binary(&mut entry, ids::CODEC_PRIVATE, &codec_private.to_bytes());
+        if config.frame_rate_num > 0 {
+            let frame_ns = frames_to_timescale(
+                1,
+                config.frame_rate_num,
+                config.frame_rate_den,
+                1_000_000_000,
+            );
+            ebml::write_uint(&mut entry, ids::DEFAULT_DURATION, frame_ns as u64);
+        }
+        ebml::write_binary(&mut entry, ids::VIDEO, &video);
+        let mut tracks = Vec::new();
+        ebml::write_binary(&mut tracks, ids::TRACK_ENTRY, &entry);
+        ebml::write_binary(&mut head, ids::TRACKS, &tracks);
+
+        inner.write_all(&head)?;
+        Ok(Self {
+            inner: Some(inner),
+            config,
+            segment_size_pos,
+            segment_data_start,
+            info_pos,
+            tracks_pos,
+            duration_pos,
+            cluster: None,
+            cues: Vec::new(),
+            max_timestamp: None,
+        })
+    }
+
+    fn inner(&mut self) -> &mut W {
+        self.inner.as_mut().expect("writer already finished")
+    }
+
+    /// Appends one temporal unit. `pts` counts frames at the configured frame rate.
+    pub fn write_frame(&mut self, data: &[u8], pts: i64, keyframe: bool) -> Result<()> {
+        let data = strip_temporal_delimiters(data)?;
+        let timestamp = frames_to_timescale(
+            pts,
+            self.config.frame_rate_num,
+            self.config.frame_rate_den,
+            1_000_000_000 / TIMESTAMP_SCALE,
+        );
+
+        let fits = self
+            .cluster
+            .as_ref()
+            .is_some_and(|c| (0..=i16::MAX as i64).contains(&(timestamp - c.timestamp)));
+        if keyframe || !fits {
+            self.flush_cluster()?;
+            let position = self.inner().stream_position()? - self.segment_data_start;
+            if keyframe {
+                self.cues.push((timestamp, position));
+            }
+            self.cluster = Some(Cluster {
+                timestamp,
+                position,
+                blocks: Vec::new(),
+            });
+        }
+
+        let cluster = self.cluster.as_mut().unwrap();
+        let relative = (timestamp - cluster.timestamp) as i16;
+        ebml::write_id(&mut cluster.blocks, ids::SIMPLE_BLOCK);
+        ebml::write_size(&mut cluster.blocks, 4 + data.len() as u64);
+        cluster.blocks.push(0x81); // track number 1 as a vint
+        cluster.blocks.extend_from_slice(&relative.to_be_bytes());
+        cluster.blocks.push(if keyframe { 0x80 } else { 0 });
+        cluster.blocks.extend_from_slice(&data);
+
+        self.max_timestamp = Some(self.max_timestamp.map_or(timestamp, |t| t.max(timestamp)));
+        Ok(())
+    }
+
+    /// Appends an encoder packet using its pts and key frame flag.
+    #[cfg(feature = "encoder")]
+    pub fn write_packet(&mut self, packet: &crate::encoder::Packet) -> Result<()> {
+        if packet.data().is_empty() {
+            return Ok(());
+        }
+        self.write_frame(packet.data(), packet.pts(), packet.is_keyframe())
+    }
+
+    fn flush_cluster(&mut self) -> Result<()> {
+        let Some(cluster) = self.cluster.take() else {
+            return Ok(());
+        };
+        let mut payload = Vec::with_capacity(cluster.blocks.len() + 16);
+        ebml::write_uint(
+            &mut payload,
+            ids::TIMESTAMP,
+            cluster.timestamp.max(0) as u64,
+        );
+        payload.extend_from_slice(&cluster.blocks);
+        let mut out = Vec::with_capacity(payload.len() + 12);
+        ebml::write_binary(&mut out, ids::CLUSTER, &payload);
+        debug_assert_eq!(
+            self.inner().stream_position()? - self.segment_data_start,
+            cluster.position
+        );
+        self.inner().write_all(&out)?;
+        Ok(())
+    }
+
+    fn finalize(&mut self) -> Result<()> {
+        self.flush_cluster()?;
+
+        let cues_pos = self.inner().stream_position()?;
+        let mut cues = Vec::new();
+        for (time, position) in &self.cues {
+            let mut track_positions = Vec::new();
+            ebml::write_uint(&mut track_positions, ids::CUE_TRACK, 1);
+            ebml::write_uint(&mut track_positions, ids::CUE_CLUSTER_POSITION, *position);
+            let mut point = Vec::new();
+            ebml::write_uint(&mut point, ids::CUE_TIME, (*time).max(0) as u64);
+            ebml::write_binary(&mut point, ids::CUE_TRACK_POSITIONS, &track_positions);
+            ebml::write_binary(&mut cues, ids::CUE_POINT, &point);
+        }
+        if !self.cues.is_empty() {
+            let mut out = Vec::new();
+            ebml::write_binary(&mut out, ids::CUES, &cues);
+            self.inner().write_all(&out)?;
+        }
+        let end = self.inner().stream_position()?;
+
+        let mut seek_head = Vec::new();
+        let mut entries = vec![(ids::INFO, self.info_pos), (ids::TRACKS, self.tracks_pos)];
+        if !self.cues.is_empty() {
+            entries.push((ids::CUES, cues_pos));
+        }
+        for (id, pos) in entries {
+            let mut seek = Vec::new();
+            ebml::write_binary(&mut seek, ids::SEEK_ID, &id.to_be_bytes());
+            ebml::write_uint(&mut seek, ids::SEEK_POSITION, pos - self.segment_data_start);
+            ebml::write_binary(&mut seek_head, ids::SEEK, &seek);
+        }
+        let mut reserved = Vec::with_capacity(SEEK_HEAD_RESERVED);
+        ebml::write_binary(&mut reserved, ids::SEEK_HEAD, &seek_head);
+        let remaining = SEEK_HEAD_RESERVED - reserved.len();
+        ebml::write_void(&mut reserved, remaining);
+
+        let duration = self.max_timestamp.map_or(0.0, |t| {
+            t as f64
+                + frames_to_timescale(
+                    1,
+                    self.config.frame_rate_num,
+                    self.config.frame_rate_den,
+                    1_000_000_000 / TIMESTAMP_SCALE,
+                ) as f64
+        });
+        let mut segment_size = Vec::with_capacity(8);
+        ebml::write_size_fixed(&mut segment_size, end - self.segment_data_start, 8);
+
+        let (segment_size_pos, segment_data_start, duration_pos) = (
+            self.segment_size_pos,
+            self.segment_data_start,
+            self.duration_pos,
+        );
+        let inner = self.inner();
+        inner.seek(SeekFrom::Start(segment_size_pos))?;
+        inner.write_all(&segment_size)?;
+        inner.seek(SeekFrom::Start(segment_data_start))?;
+        inner.write_all(&reserved)?;
+        inner.seek(SeekFrom::Start(duration_pos))?;
+        inner.write_all(&duration.to_be_bytes())?;
+        inner.seek(SeekFrom::Start(end))?;
+        inner.flush()?;
+        Ok(())
+    }
+
+    /// Writes cues, patches sizes and duration, and returns the underlying writer.
+    pub fn finish(mut self) -> Result<W> {
+        self.finalize()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write + Seek> Drop for WebmWriter<W> {
+    fn drop(&mut self) {
+        if self.inner.is_some() {
+            let _ = self.finalize();
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use crate::config::{ChromaSamplePosition, ColorFormat, ColorRange, MatrixCoefficients};
+use crate::frame::Frame;
+use crate::{Error, Result};
+
+/// Memory layout of a packed RGB picture.
+///
+/// The 8-bit layouts hold one byte per channel. `Rgb48` holds 16-bit
+/// little-endian channels. Alpha is ignored.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RgbLayout {
+    Rgb24,
+    Bgra,
+    Rgba,
+    Rgb48,
+}
+
+impl RgbLayout {
+    /// Bytes per pixel.
+    pub fn bytes_per_pixel(self) -> usize {
+        match self {
+            RgbLayout::Rgb24 => 3,
+            RgbLayout::Bgra | RgbLayout::Rgba => 4,
+            RgbLayout::Rgb48 => 6,
+        }
+    }
+
+    /// Reads pixel `x` of `row` as normalized `[r, g, b]`.
+    fn pixel(self, row: &[u8], x: usize) -> [f32; 3] {
+        let px = &row[x * self.bytes_per_pixel()..];
+        let byte = |i: usize| px[i] as f32 / 255.0;
+        match self {
+            RgbLayout::Rgb24 | RgbLayout::Rgba => [byte(0), byte(1), byte(2)],
+            RgbLayout::Bgra => [byte(2), byte(1), byte(0)],
+            RgbLayout::Rgb48 => {
+                let word =
+                    |i: usize| u16::from_le_bytes([px[2 * i], px[2 * i + 1]]) as f32 / 65535.0;
+                [word(0), word(1), word(2)]
+            }
+        }
+    }
+}
+
+/// Dimensions, layout and row stride of a packed RGB picture.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RgbFormat {
+    pub width: u32,
+    pub height: u32,
+    pub layout: RgbLayout,
+    /// Distance between rows, in bytes.
+    pub stride: usize,
+}
+
+impl RgbFormat {
+    /// Tightly packed picture (no row padding).
+    pub fn new(width: u32, height: u32, layout: RgbLayout) -> Self {
+        Self {
+            width,
+            height,
+            layout,
+            stride: width as usize * layout.bytes_per_pixel(),
+        }
+    }
+
+    /// Overrides the row stride, in bytes.
+    pub fn with_stride(mut self, stride: usize) -> Self {
+        self.stride = stride;
+        self
+    }
+
+    fn row_bytes(&self) -> usize {
+        self.width as usize * self.layout.bytes_per_pixel()
+    }
+
+    /// Bytes needed to hold one picture, without padding after the last row.
+    pub fn frame_size(&self) -> usize {
+        match self.height as usize {
+            0 => 0,
+            h => (h - 1) * self.stride + self.row_bytes(),
+        }
+    }
+}
+
+/// How RGB is turned into YUV: matrix, quantization range and chroma siting.
+///
+/// Chroma is horizontally co-sited with even luma columns (filtered with
+/// `[1, 2, 1]`) unless the position is `Unknown`, in which case it sits
+/// between them. For 4:2:0, `Vertical` places chroma between luma rows,
+/// `Colocated` on even rows, and `Unknown` at the centre of each 2x2 block.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ColorConversion {
+    pub matrix: MatrixCoefficients,
+    pub range: ColorRange,
+    pub chroma_position: ChromaSamplePosition,
+}
+
+impl Default for ColorConversion {
+    /// BT.709, studio range, chroma between luma rows.
+    fn default() -> Self {
+        Self {
+            matrix: MatrixCoefficients::Bt709,
+            range: ColorRange::Studio,
+            chroma_position: ChromaSamplePosition::Vertical,
+        }
+    }
+}
+
+impl ColorConversion {
+    pub fn new(
+        matrix: MatrixCoefficients,
+        range: ColorRange,
+        chroma_position: ChromaSamplePosition,
+    ) -> Self {
+        Self {
+            matrix,
+            range,
+            chroma_position,
+        }
+    }
+
+    /// Writes the matrix, range and chroma position into `cfg` so the stream
+    /// signals the conversion.
+    #[cfg(feature = "encoder")]
+    pub fn apply_to(&self, cfg: &mut crate::encoder::Configuration) {
+        use crate::config::ConfigExt;
+
+        cfg.set_matrix_coefficients(self.matrix)
+            .set_color_range(self.range)
+            .set_chroma_sample_position(self.chroma_position);
+    }
+
+    /// Luma weights `(Kr, Kb)`.
+    fn weights(&self) -> (f32, f32) {
+        match self.matrix {
+            MatrixCoefficients::Bt601 => (0.299, 0.114),
+            MatrixCoefficients::Bt709 => (0.2126, 0.0722),
+            MatrixCoefficients::Bt2020Ncl => (0.2627, 0.0593),
+        }
+    }
+
+    /// Converts one RGB picture into `frame`, which sets the output size,
+    /// bit depth and color format. `frame.pts` is left unchanged.
+    pub fn convert(&self, format: &RgbFormat, data: &[u8], frame: &mut Frame) -> Result<()> {
+        if format.width == 0 || format.height == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "RGB picture must be non-empty, got {}x{}",
+                format.width, format.height
+            )));
+        }
+        if format.stride < format.row_bytes() {
+            return Err(Error::InvalidArgument(format!(
+                "RGB stride {} is shorter than a {}-byte row",
+                format.stride,
+                format.row_bytes()
+            )));
+        }
+        if data.len() < format.frame_size() {
+            return Err(Error::InvalidArgument(format!(
+                "RGB buffer holds {} bytes, expected {}",
+                data.len(),
+                format.frame_size()
+            )));
+        }
+        if frame.width() != format.width || frame.height() != format.height {
+            return Err(Error::InvalidArgument(
+                "frame size does not match the RGB picture".into(),
+            ));
+        }
+
+        let (kr, kb) = self.weights();
+        let kg = 1.0 - kr - kb;
+        let quant = Quantizer::new(self.range, frame.bit_depth().bits());
+        let bps = frame.bytes_per_sample();
+        let width = format.width as usize;
+        let height = format.height as usize;
+        let row = |y: usize| &data[y * format.stride..][..format.row_bytes()];
+
+        let color_format = frame.color_format();
+        if color_format == ColorFormat::Yuv400 {
+            for y in 0..height {
+                let src = row(y);
+                let out = frame.row_mut(0, y);
+                for x in 0..width {
+                    let [r, g, b] = format.layout.pixel(src, x);
+                    store(out, x, bps, quant.luma(kr * r + kg * g + kb * b));
+                }
+            }
+            return Ok(());
+        }
+
+        let (ss_x, ss_y) = color_format.subsampling();
+        let centred_x = self.chroma_position == ChromaSamplePosition::Unknown;
+        let cosited_y = self.chroma_position == ChromaSamplePosition::Colocated;
+        let chroma_width = frame.plane_width(1) as usize;
+        // Full-resolution Cb/Cr of one row, and the last three horizontally
+        // filtered chroma rows for vertical filtering.
+        let mut full = [vec![0f32; width], vec![0f32; width]];
+        let mut filtered = vec![[vec![0f32; chroma_width], vec![0f32; chroma_width]]; 3];
+
+        for y in 0..height {
+            let src = row(y);
+            let out = frame.row_mut(0, y);
+            let [cb_row, cr_row] = &mut full;
+            for (x, (cb, cr)) in cb_row.iter_mut().zip(cr_row.iter_mut()).enumerate() {
+                let [r, g, b] = format.layout.pixel(src, x);
+                let luma = kr * r + kg * g + kb * b;
+                store(out, x, bps, quant.luma(luma));
+                *cb = (b - luma) / (2.0 * (1.0 - kb));
+                *cr = (r - luma) / (2.0 * (1.0 - kr));
+            }
+            for (src, dst) in full.iter().zip(filtered[y % 3].iter_mut()) {
+                downsample_row(src, dst, ss_x == 1, centred_x);
+            }
+
+            if ss_y == 0 {
+                for (c, src) in filtered[y % 3].iter().enumerate() {
+                    let out = frame.row_mut(c + 1, y);
+                    for (x, &v) in src.iter().enumerate() {
+                        store(out, x, bps, quant.chroma(v));
+                    }
+                }
+            } else if y % 2 == 1 || y == height - 1 {
+                // Chroma row `j` needs luma rows up to `2j + 1`, clamped.
+                let j = y as isize / 2;
+                let at = |r: isize| &filtered[r.clamp(0, height as isize - 1) as usize % 3];
+                let (prev, even, odd) = (at(2 * j - 1), at(2 * j), at(2 * j + 1));
+                for c in 0..2 {
+                    let out = frame.row_mut(c + 1, j as usize);
+                    let rows = prev[c].iter().zip(&even[c]).zip(&odd[c]);
+                    for (x, ((&p, &e), &o)) in rows.enumerate() {
+                        let v = if cosited_y {
+                            (p + 2.0 * e + o) / 4.0
+                        } else {
+                            (e + o) / 2.0
+                        };
+                        store(out, x, bps, quant.chroma(v));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Converts one RGB picture into a newly allocated frame.
+    pub fn convert_new(
+        &self,
+        format: &RgbFormat,
+        data: &[u8],
+        bit_depth: crate::config::BitDepth,
+        color_format: ColorFormat,
+    ) -> Result<Frame> {
+        let mut frame = Frame::new(format.width, format.height, bit_depth, color_format);
+        self.convert(format, data, &mut frame)?;
+        Ok(frame)
+    }
+}
+
+/// Maps normalized luma `[0, 1]` and chroma `[-0.5, 0.5]` onto integer codes.
+struct Quantizer {
+    luma_scale: f32,
+    luma_offset: f32,
+    chroma_scale: f32,
+    chroma_offset: f32,
+    max: f32,
+}
+
+impl Quantizer {
+    fn new(range: ColorRange, bits: u32) -> Self {
+        let max = ((1u32 << bits) - 1) as f32;
+        let step = (1u32 << (bits - 8)) as f32;
+        let chroma_offset = (1u32 << (bits - 1)) as f32;
+        match range {
+            ColorRange::Full => Self {
+                luma_scale: max,
+                luma_offset: 0.0,
+                chroma_scale: max,
+                chroma_offset,
+                max,
+            },
+            ColorRange::Studio => Self {
+                luma_scale: 219.0 * step,
+                luma_offset: 16.0 * step,
+                chroma_scale: 224.0 * step,
+                chroma_offset,
+                max,
+            },
+        }
+    }
+
+    fn luma(&self, v: f32) -> u16 {
+        (v * self.luma_scale + self.luma_offset)
+            .round()
+            .clamp(0.0, self.max) as u16
+    }
+
+    fn chroma(&self, v: f32) -> u16 {
+        (v * self.chroma_scale + self.chroma_offset)
+            .round()
+            .clamp(0.0, self.max) as u16
+    }
+}
+
+/// Halves `src` into `dst` when `subsample` is set, either centred between
+/// sample pairs or co-sited with even samples; otherwise copies it.
+fn downsample_row(src: &[f32], dst: &mut [f32], subsample: bool, centred: bool) {
+    if !subsample {
+        dst.copy_from_slice(src);
+        return;
+    }
+    let last = src.len() - 1;
+    for (x, out) in dst.iter_mut().enumerate() {
+        let even = 2 * x;
+        let odd = (even + 1).min(last);
+        *out = if centred {
+            (src[even] + src[odd]) / 2.0
+        } else {
+            (src[even.saturating_sub(1)] + 2.0 * src[even] + src[odd]) / 4.0
+        };
+    }
+}
+
+fn store(row: &mut [u8], x: usize, bps: usize, v: u16) {
+    if bps == 1 {
+        row[x] = v as u8;
+    } else {
+        row[2 * x..2 * x + 2].copy_from_slice(&v.to_le_bytes());
+    }
+}
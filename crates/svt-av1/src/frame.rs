@@ -0,0 +1,371 @@
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::config::{BitDepth, ColorFormat};
+
+/// Alignment of every plane's first sample, enough for AVX-512 loads.
+pub const PLANE_ALIGN: usize = 64;
+
+#[derive(Copy, Clone)]
+#[repr(C, align(64))]
+struct Block([u8; PLANE_ALIGN]);
+
+/// Zero-initialised byte buffer starting on a [`PLANE_ALIGN`] boundary.
+#[derive(Clone, Default)]
+struct PlaneBuf {
+    blocks: Box<[Block]>,
+    len: usize,
+}
+
+impl PlaneBuf {
+    fn zeroed(len: usize) -> Self {
+        Self {
+            blocks: vec![Block([0; PLANE_ALIGN]); len.div_ceil(PLANE_ALIGN)].into_boxed_slice(),
+            len,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: `blocks` holds at least `len` initialised bytes; `Block` is
+        // plain bytes with no padding.
+        unsafe { std::slice::from_raw_parts(self.blocks.as_ptr().cast(), self.len) }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [u8] {
+        // SAFETY: as in `as_slice`, and `&mut self` makes the borrow unique.
+        unsafe { std::slice::from_raw_parts_mut(self.blocks.as_mut_ptr().cast(), self.len) }
+    }
+}
+
+impl PartialEq for PlaneBuf {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for PlaneBuf {}
+
+impl fmt::Debug for PlaneBuf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_slice().fmt(f)
+    }
+}
+
+/// An owned planar picture in the layout `EbSvtIOFormat` expects.
+///
+/// Samples are stored as one byte for 8-bit content and as 16-bit little-endian
+/// words for higher bit depths. Strides are measured in samples, matching the
+/// C API. Monochrome (`Yuv400`) frames carry empty chroma planes. Each plane
+/// starts on a [`PLANE_ALIGN`]-byte boundary.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame {
+    width: u32,
+    height: u32,
+    bit_depth: BitDepth,
+    color_format: ColorFormat,
+    planes: [PlaneBuf; 3],
+    strides: [usize; 3],
+    /// Presentation timestamp attached to the picture when it is sent.
+    pub pts: i64,
+}
+
+impl Frame {
+    /// Allocates a zero-filled frame with tightly packed planes.
+    pub fn new(width: u32, height: u32, bit_depth: BitDepth, color_format: ColorFormat) -> Self {
+        let mut frame = Self {
+            width,
+            height,
+            bit_depth,
+            color_format,
+            planes: Default::default(),
+            strides: [0; 3],
+            pts: 0,
+        };
+        for p in 0..color_format.num_planes() {
+            let stride = frame.plane_width(p) as usize;
+            frame.strides[p] = stride;
+            frame.planes[p] = PlaneBuf::zeroed(
+                stride * frame.plane_height(p) as usize * frame.bytes_per_sample(),
+            );
+        }
+        frame
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn bit_depth(&self) -> BitDepth {
+        self.bit_depth
+    }
+
+    pub fn color_format(&self) -> ColorFormat {
+        self.color_format
+    }
+
+    /// 1 for 8-bit content, 2 for 10/12-bit content.
+    pub fn bytes_per_sample(&self) -> usize {
+        if self.bit_depth == BitDepth::Eight {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn num_planes(&self) -> usize {
+        self.color_format.num_planes()
+    }
+
+    /// Width in samples of plane `p` (0 = Y, 1 = Cb, 2 = Cr).
+    pub fn plane_width(&self, p: usize) -> u32 {
+        let (ss_x, _) = self.color_format.subsampling();
+        if p == 0 {
+            self.width
+        } else {
+            (self.width + ss_x) >> ss_x
+        }
+    }
+
+    /// Height in rows of plane `p` (0 = Y, 1 = Cb, 2 = Cr).
+    pub fn plane_height(&self, p: usize) -> u32 {
+        let (_, ss_y) = self.color_format.subsampling();
+        if p == 0 {
+            self.height
+        } else {
+            (self.height + ss_y) >> ss_y
+        }
+    }
+
+    /// Row stride of plane `p`, in samples.
+    pub fn stride(&self, p: usize) -> usize {
+        self.strides[p]
+    }
+
+    pub fn plane(&self, p: usize) -> &[u8] {
+        self.planes[p].as_slice()
+    }
+
+    pub fn plane_mut(&mut self, p: usize) -> &mut [u8] {
+        self.planes[p].as_mut_slice()
+    }
+
+    /// Byte slice of row `y` of plane `p`, without stride padding.
+    pub fn row(&self, p: usize, y: usize) -> &[u8] {
+        let bps = self.bytes_per_sample();
+        let start = y * self.strides[p] * bps;
+        &self.plane(p)[start..start + self.plane_width(p) as usize * bps]
+    }
+
+    pub fn row_mut(&mut self, p: usize, y: usize) -> &mut [u8] {
+        let bps = self.bytes_per_sample();
+        let start = y * self.strides[p] * bps;
+        let len = self.plane_width(p) as usize * bps;
+        &mut self.plane_mut(p)[start..start + len]
+    }
+
+    /// Total number of bytes held by all planes.
+    pub fn data_len(&self) -> usize {
+        self.planes.iter().map(PlaneBuf::len).sum()
+    }
+
+    /// Describes the planes as an `EbSvtIOFormat` for `svt_av1_enc_send_picture`.
+    ///
+    /// The returned struct borrows the plane memory through raw pointers; it must
+    /// not outlive `self`. The encoder only reads through them and copies the
+    /// planes before `send_picture` returns.
+    #[cfg(feature = "encoder")]
+    pub fn io_format(&self) -> svt_av1_sys::enc_bindings::EbSvtIOFormat {
+        fn ptr(plane: &[u8]) -> *mut u8 {
+            if plane.is_empty() {
+                std::ptr::null_mut()
+            } else {
+                plane.as_ptr() as *mut u8
+            }
+        }
+        svt_av1_sys::enc_bindings::EbSvtIOFormat {
+            luma: ptr(self.plane(0)),
+            cb: ptr(self.plane(1)),
+            cr: ptr(self.plane(2)),
+            y_stride: self.strides[0] as u32,
+            cr_stride: self.strides[2] as u32,
+            cb_stride: self.strides[1] as u32,
+            width: self.width,
+            height: self.height,
+            org_x: 0,
+            org_y: 0,
+            color_fmt: self.color_format as u32,
+            bit_depth: self.bit_depth as u32,
+        }
+    }
+}
+
+struct PoolShared {
+    width: u32,
+    height: u32,
+    bit_depth: BitDepth,
+    color_format: ColorFormat,
+    free: Mutex<Vec<Frame>>,
+    allocations: AtomicUsize,
+}
+
+/// Hands out reusable frames of one format.
+///
+/// [`FramePool::get`] returns an idle frame or allocates a new one; the
+/// [`PooledFrame`] goes back to the pool when dropped. `send_frame` copies the
+/// picture into the encoder's own buffers before it returns, so a pooled frame
+/// can be dropped right after the send. Once as many frames exist as are in use
+/// at the same time, encoding allocates nothing: [`FramePool::allocations`]
+/// stops growing. Recycled frames keep their previous samples.
+#[derive(Clone)]
+pub struct FramePool {
+    shared: Arc<PoolShared>,
+}
+
+impl FramePool {
+    pub fn new(width: u32, height: u32, bit_depth: BitDepth, color_format: ColorFormat) -> Self {
+        Self {
+            shared: Arc::new(PoolShared {
+                width,
+                height,
+                bit_depth,
+                color_format,
+                free: Mutex::new(Vec::new()),
+                allocations: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    /// Pool of frames matching the encoder input described by `cfg`.
+    #[cfg(feature = "encoder")]
+    pub fn for_config(cfg: &crate::encoder::Configuration) -> crate::Result<Self> {
+        use crate::Error;
+
+        if cfg.source_width == 0 || cfg.source_height == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "resolution must be non-zero, got {}x{}",
+                cfg.source_width, cfg.source_height
+            )));
+        }
+        let bit_depth = match cfg.encoder_bit_depth {
+            8 => BitDepth::Eight,
+            10 => BitDepth::Ten,
+            12 => BitDepth::Twelve,
+            b => {
+                return Err(Error::InvalidArgument(format!(
+                    "unsupported input bit depth {b}"
+                )))
+            }
+        };
+        let color_format = match cfg.encoder_color_format {
+            0 => ColorFormat::Yuv400,
+            1 => ColorFormat::Yuv420,
+            2 => ColorFormat::Yuv422,
+            3 => ColorFormat::Yuv444,
+            f => {
+                return Err(Error::InvalidArgument(format!(
+                    "unsupported input color format {f}"
+                )))
+            }
+        };
+        Ok(Self::new(
+            cfg.source_width,
+            cfg.source_height,
+            bit_depth,
+            color_format,
+        ))
+    }
+
+    /// Takes an idle frame, allocating one if none is available.
+    pub fn get(&self) -> PooledFrame {
+        let idle = self
+            .shared
+            .free
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .pop();
+        let frame = idle.unwrap_or_else(|| {
+            self.shared.allocations.fetch_add(1, Ordering::Relaxed);
+            let s = &self.shared;
+            Frame::new(s.width, s.height, s.bit_depth, s.color_format)
+        });
+        PooledFrame {
+            frame: Some(frame),
+            pool: Arc::clone(&self.shared),
+        }
+    }
+
+    /// Frames allocated by this pool so far.
+    pub fn allocations(&self) -> usize {
+        self.shared.allocations.load(Ordering::Relaxed)
+    }
+
+    /// Frames currently waiting in the pool.
+    pub fn idle(&self) -> usize {
+        self.shared
+            .free
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .len()
+    }
+}
+
+/// A [`Frame`] on loan from a [`FramePool`], returned to it on drop.
+pub struct PooledFrame {
+    frame: Option<Frame>,
+    pool: Arc<PoolShared>,
+}
+
+impl PooledFrame {
+    /// Keeps the frame instead of returning it to the pool.
+    pub fn detach(mut self) -> Frame {
+        self.frame.take().unwrap()
+    }
+}
+
+impl Deref for PooledFrame {
+    type Target = Frame;
+
+    fn deref(&self) -> &Frame {
+        self.frame.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledFrame {
+    fn deref_mut(&mut self) -> &mut Frame {
+        self.frame.as_mut().unwrap()
+    }
+}
+
+impl AsRef<Frame> for PooledFrame {
+    fn as_ref(&self) -> &Frame {
+        self
+    }
+}
+
+impl fmt::Debug for PooledFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("PooledFrame").field(&**self).finish()
+    }
+}
+
+impl Drop for PooledFrame {
+    fn drop(&mut self) {
+        if let Some(frame) = self.frame.take() {
+            self.pool
+                .free
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(frame);
+        }
+    }
+}
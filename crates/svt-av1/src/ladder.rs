@@ -0,0 +1,219 @@
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::config::{ChromaSamplePosition, ConfigExt, RcMode};
+use crate::encoder::{Configuration, Encoder, FrameSender, KeyframePolicy, PacketReceiver};
+use crate::frame::{Frame, FramePool};
+use crate::scale::{Filter, Scaler};
+use crate::{Error, Result};
+
+/// Rate control for one rendition.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RateControl {
+    /// Constant quality at the given QP (`--crf`).
+    Crf(u32),
+    /// Variable bitrate, in bits per second.
+    Vbr(u32),
+    /// Constant bitrate, in bits per second.
+    Cbr(u32),
+}
+
+/// One output of a [`Ladder`]: a resolution plus optional preset and rate
+/// control overrides on top of the ladder's base configuration.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Rendition {
+    pub width: u32,
+    pub height: u32,
+    /// Encoder preset (`enc_mode`).
+    pub preset: Option<i8>,
+    pub rate_control: Option<RateControl>,
+}
+
+impl Rendition {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            preset: None,
+            rate_control: None,
+        }
+    }
+
+    pub fn with_preset(mut self, preset: i8) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+
+    pub fn with_rate_control(mut self, rate_control: RateControl) -> Self {
+        self.rate_control = Some(rate_control);
+        self
+    }
+
+    /// Applies this rendition to a copy of the ladder's base configuration.
+    ///
+    /// Scene-change detection is turned off so that, under
+    /// `KeyframePolicy::Auto`, keyframes only come from the shared
+    /// `intra_period_length` and land on the same pts in every rendition.
+    pub fn configure(&self, cfg: &mut Configuration) {
+        cfg.set_resolution(self.width, self.height);
+        if let Some(preset) = self.preset {
+            cfg.enc_mode = preset;
+        }
+        match self.rate_control {
+            Some(RateControl::Crf(qp)) => {
+                cfg.set_rc_mode(RcMode::CqpOrCrf).set_qp(qp);
+            }
+            Some(RateControl::Vbr(bps)) => {
+                cfg.set_rc_mode(RcMode::Vbr).set_target_bitrate(bps);
+            }
+            Some(RateControl::Cbr(bps)) => {
+                cfg.set_rc_mode(RcMode::Cbr).set_target_bitrate(bps);
+            }
+            None => {}
+        }
+        cfg.scene_change_detection = 0;
+    }
+}
+
+/// Frames queued per rendition ahead of its worker thread.
+const FRAME_QUEUE: usize = 1;
+
+struct Worker {
+    frames: SyncSender<Arc<Frame>>,
+    thread: JoinHandle<Result<()>>,
+}
+
+/// Encodes one source into several renditions in parallel.
+///
+/// Every rendition gets its own [`Encoder`] on a worker thread that scales
+/// the shared source frame to the rendition's size and sends it. Frames are
+/// handed out by reference count, so the source is read and decoded once.
+/// The slowest rendition sets the pace of [`Ladder::send_frame`].
+///
+/// Keyframes stay aligned for ABR switching: every rendition's encoder gets
+/// the same [`KeyframePolicy`] and sees the same pts. With
+/// `KeyframePolicy::Auto` the renditions share the base configuration's GOP
+/// settings and run without scene-change keyframes (see
+/// [`Rendition::configure`]).
+pub struct Ladder {
+    renditions: Vec<Rendition>,
+    workers: Vec<Worker>,
+}
+
+impl Ladder {
+    /// Builds one encoder per rendition from `base` and starts the workers.
+    ///
+    /// Returns the ladder and, in rendition order, the packet stream of each
+    /// rendition. Source frames must match the bit depth and color format of
+    /// `base`; they are scaled with `filter`, siting chroma as `base` signals.
+    pub fn new(
+        base: &Configuration,
+        renditions: &[Rendition],
+        filter: Filter,
+        keyframes: KeyframePolicy,
+    ) -> Result<(Self, Vec<PacketReceiver>)> {
+        if renditions.is_empty() {
+            return Err(Error::InvalidArgument(
+                "a ladder needs at least one rendition".into(),
+            ));
+        }
+        let chroma_position = match base.chroma_sample_position {
+            1 => ChromaSamplePosition::Vertical,
+            2 => ChromaSamplePosition::Colocated,
+            _ => ChromaSamplePosition::Unknown,
+        };
+        let scaler = Scaler::new(filter).with_chroma_position(chroma_position);
+
+        let mut encoders = Vec::with_capacity(renditions.len());
+        for rendition in renditions {
+            let mut cfg = *base;
+            rendition.configure(&mut cfg);
+            let pool = FramePool::for_config(&cfg)?;
+            let encoder = Encoder::builder()?
+                .with_config(cfg)
+                .keyframe_policy(keyframes.clone())
+                .build()?;
+            encoders.push((encoder, pool));
+        }
+
+        let mut workers = Vec::with_capacity(renditions.len());
+        let mut outputs = Vec::with_capacity(renditions.len());
+        for (i, ((encoder, pool), &rendition)) in encoders.into_iter().zip(renditions).enumerate() {
+            let (sender, receiver) = encoder.split();
+            let (frames_tx, frames_rx) = mpsc::sync_channel(FRAME_QUEUE);
+            let thread = thread::Builder::new()
+                .name(format!("svt-av1-ladder-{i}"))
+                .spawn(move || run_worker(frames_rx, sender, rendition, scaler, pool))?;
+            workers.push(Worker {
+                frames: frames_tx,
+                thread,
+            });
+            outputs.push(receiver);
+        }
+        Ok((
+            Self {
+                renditions: renditions.to_vec(),
+                workers,
+            },
+            outputs,
+        ))
+    }
+
+    pub fn renditions(&self) -> &[Rendition] {
+        &self.renditions
+    }
+
+    /// Queues `frame` for every rendition, waiting while any worker is busy.
+    ///
+    /// A failed rendition stops accepting frames; its error is reported by
+    /// [`Ladder::finish`].
+    pub fn send_frame(&self, frame: Frame) -> Result<()> {
+        let frame = Arc::new(frame);
+        for (i, worker) in self.workers.iter().enumerate() {
+            worker.frames.send(Arc::clone(&frame)).map_err(|_| {
+                Error::InvalidArgument(format!("rendition {i} has stopped encoding"))
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Signals end of stream to every rendition and waits for the workers to
+    /// send their remaining frames. Returns the first worker error.
+    pub fn finish(self) -> Result<()> {
+        let (inputs, threads): (Vec<_>, Vec<_>) = self
+            .workers
+            .into_iter()
+            .map(|worker| (worker.frames, worker.thread))
+            .unzip();
+        drop(inputs);
+        let mut result = Ok(());
+        for thread in threads {
+            let outcome = thread.join().expect("ladder worker panicked");
+            if result.is_ok() {
+                result = outcome;
+            }
+        }
+        result
+    }
+}
+
+fn run_worker(
+    frames: Receiver<Arc<Frame>>,
+    mut sender: FrameSender,
+    rendition: Rendition,
+    scaler: Scaler,
+    pool: FramePool,
+) -> Result<()> {
+    while let Ok(source) = frames.recv() {
+        if (source.width(), source.height()) == (rendition.width, rendition.height) {
+            sender.send_frame(&source)?;
+            continue;
+        }
+        let mut scaled = pool.get();
+        scaler.scale(&source, &mut scaled)?;
+        scaled.pts = source.pts;
+        sender.send_frame(&scaled)?;
+    }
+    sender.finish()
+}
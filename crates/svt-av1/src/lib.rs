@@ -9,6 +9,14 @@ pub enum Error {
     Code(i32),
     #[error("Null pointer")]
     Null,
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+    #[error("Invalid parameter `{key}`: {reason}")]
+    Parameter { key: String, reason: String },
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -42,6 +50,33 @@ pub mod config {
         Yuv444 = 3,
     }
 
+    impl BitDepth {
+        /// Number of bits per sample.
+        pub fn bits(self) -> u32 {
+            self as u32
+        }
+    }
+
+    impl ColorFormat {
+        /// Horizontal and vertical chroma subsampling shifts.
+        pub fn subsampling(self) -> (u32, u32) {
+            match self {
+                ColorFormat::Yuv400 | ColorFormat::Yuv420 => (1, 1),
+                ColorFormat::Yuv422 => (1, 0),
+                ColorFormat::Yuv444 => (0, 0),
+            }
+        }
+
+        /// Number of coded planes (1 for monochrome, 3 otherwise).
+        pub fn num_planes(self) -> usize {
+            if self == ColorFormat::Yuv400 {
+                1
+            } else {
+                3
+            }
+        }
+    }
+
     #[derive(Copy, Clone, Debug, Eq, PartialEq)]
     #[repr(u32)]
     pub enum ColorRange {
@@ -57,6 +92,16 @@ pub mod config {
         Colocated = 2,
     }
 
+    /// Matrix coefficients signalled in the sequence header, using the codes
+    /// of ISO/IEC 23091-4.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    #[repr(u32)]
+    pub enum MatrixCoefficients {
+        Bt709 = 1,
+        Bt601 = 6,
+        Bt2020Ncl = 9,
+    }
+
     #[derive(Copy, Clone, Debug, Eq, PartialEq)]
     #[repr(u32)]
     pub enum Profile {
@@ -95,6 +140,7 @@ pub mod config {
         fn set_color_format(&mut self, fmt: ColorFormat) -> &mut Self;
         fn set_color_range(&mut self, range: ColorRange) -> &mut Self;
         fn set_chroma_sample_position(&mut self, csp: ChromaSamplePosition) -> &mut Self;
+        fn set_matrix_coefficients(&mut self, mc: MatrixCoefficients) -> &mut Self;
         fn set_profile(&mut self, profile: Profile) -> &mut Self;
         fn set_tier(&mut self, tier: Tier) -> &mut Self;
         fn set_level_auto(&mut self) -> &mut Self;
@@ -109,6 +155,31 @@ pub mod config {
         /// with `ROI_MAP_EVENT` attached to `BufferHeader.p_app_private`.
         fn enable_roi_map(&mut self, enable: bool) -> &mut Self;
         fn enable_recon(&mut self, enable: bool) -> &mut Self;
+        /// Applies SvtAv1EncApp options, either as a command line
+        /// (`--preset 6 --crf 30 --tune 0`, also `--key=value`) or in the
+        /// `--svtav1-params` form (`preset=6:crf=30:tune=0`).
+        ///
+        /// Keys are the app's long option names, with values in its units
+        /// (e.g. `tbr` in kbps). `crf` sets the QP and the CRF rate control
+        /// mode; `keyint` takes frames, or seconds with an `s` suffix.
+        /// Nothing is changed unless every option applies; the error names
+        /// the first key that failed and why.
+        fn apply_params(&mut self, params: &str) -> crate::Result<&mut Self>;
+        /// Dumps every field that has an SvtAv1EncApp option as a command
+        /// line that [`apply_params`](Self::apply_params) and the reference
+        /// CLI accept. Applied to the library defaults, the options give
+        /// back this configuration; where they cannot, e.g. for a bit rate
+        /// that is not whole kbps or a changed field without an option,
+        /// `Error::Parameter` names the field. Pointer fields are not
+        /// compared.
+        fn to_params_string(&self) -> crate::Result<String>;
+        /// Readable dump of every field in
+        /// [`settings::fields`](crate::settings::fields), grouped by
+        /// section, for logs.
+        ///
+        /// See [`ConfigDiff`](crate::settings::ConfigDiff) to compare two
+        /// configurations.
+        fn summary(&self) -> crate::settings::Summary<'_>;
     }
 
     impl ConfigExt for sys::enc_bindings::EbSvtAv1EncConfiguration {
@@ -138,6 +209,10 @@ pub mod config {
             self.chroma_sample_position = csp as u32;
             self
         }
+        fn set_matrix_coefficients(&mut self, mc: MatrixCoefficients) -> &mut Self {
+            self.matrix_coefficients = mc as u32;
+            self
+        }
         fn set_profile(&mut self, profile: Profile) -> &mut Self {
             self.profile = profile as u32;
             self
@@ -178,13 +253,81 @@ pub mod config {
             self.recon_enabled = enable as u8;
             self
         }
+        fn apply_params(&mut self, params: &str) -> crate::Result<&mut Self> {
+            crate::settings::apply_params(self, params)?;
+            Ok(self)
+        }
+        fn to_params_string(&self) -> crate::Result<String> {
+            crate::settings::to_params_string(self)
+        }
+        fn summary(&self) -> crate::settings::Summary<'_> {
+            crate::settings::Summary(self)
+        }
     }
 }
 
+/// Owned planar pictures used as encoder input.
+pub mod frame;
+
+/// Raw YUV readers for planar and semi-planar capture layouts.
+pub mod yuv;
+
+/// Packed RGB to YUV conversion into encoder input frames.
+pub mod convert;
+
+/// Bilinear, bicubic and Lanczos frame scaling for encoding ladders.
+pub mod scale;
+
+/// Scene-cut detection on luma thumbnails to place chunks and keyframes.
+pub mod scenecut;
+
+/// Quality metrics and the target-quality CRF search built on them.
+pub mod tq;
+
+/// Multi-rendition encoding of one source with aligned keyframes.
+#[cfg(feature = "encoder")]
+pub mod ladder;
+
+/// Chunked parallel encoding with the chunks concatenated into one stream.
+#[cfg(feature = "encoder")]
+pub mod chunked;
+
+/// Optional-field mirror of the encoder configuration for stored profiles.
+#[cfg(feature = "encoder")]
+pub mod settings;
+
+/// AV1 OBU (open bitstream unit) parsing for inspecting encoded packets.
+pub mod obu;
+
+/// Conversion between Section 5, Annex B and per-sample AV1 framings.
+pub mod bitstream;
+
+/// Container muxers (and the small parsers they need) for encoded AV1 packets.
+pub mod container;
+
+/// Still-image AVIF encoding and the HEIF container writer behind it.
+pub mod avif;
+
 #[cfg(feature = "encoder")]
 pub mod encoder {
     use super::*;
+    use crate::frame::Frame;
+    #[cfg(test)]
+    use crate::mock::enc as ffi;
+    #[cfg(loom)]
+    use loom::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+    #[cfg(loom)]
+    use loom::sync::{Arc, Condvar, Mutex, MutexGuard};
+    use std::cell::Cell;
     use std::ffi::{CStr, CString};
+    use std::marker::PhantomData;
+    #[cfg(not(loom))]
+    use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+    #[cfg(not(loom))]
+    use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+    use std::time::Duration;
+    #[cfg(not(test))]
+    use sys::enc_bindings as ffi;
 
     pub use sys::enc_bindings::EbBufferHeaderType as BufferHeader;
     pub use sys::enc_bindings::EbComponentType as Component;
@@ -199,10 +342,18 @@ pub mod encoder {
         sys::enc_bindings::PrivDataType_ROI_MAP_EVENT;
     // The public API primarily uses BufferHeader and Configuration for I/O and params.
 
-    pub struct Handle(*mut Component);
+    /// Owns an encoder component for its whole lifecycle.
+    ///
+    /// `svt_av1_enc_deinit` runs on drop only if `svt_av1_enc_init` succeeded,
+    /// followed by `svt_av1_enc_deinit_handle`. The handle is `Send` but not
+    /// `Sync`: its raw pointer reaches library state that is not safe to touch
+    /// from several threads at once.
+    pub struct Handle {
+        ptr: *mut Component,
+        initialized: bool,
+    }
 
     unsafe impl Send for Handle {}
-    unsafe impl Sync for Handle {}
 
     impl Default for Handle {
         fn default() -> Self {
@@ -212,113 +363,347 @@ pub mod encoder {
 
     impl Handle {
         pub fn new() -> Self {
-            Self(std::ptr::null_mut())
+            Self {
+                ptr: std::ptr::null_mut(),
+                initialized: false,
+            }
         }
         pub fn as_mut_ptr(&mut self) -> *mut *mut Component {
-            &mut self.0 as *mut _
+            &mut self.ptr as *mut _
         }
         pub fn as_ptr(&self) -> *mut Component {
-            self.0
+            self.ptr
         }
         pub fn is_null(&self) -> bool {
-            self.0.is_null()
+            self.ptr.is_null()
+        }
+        /// True once `svt_av1_enc_init` has succeeded on this handle.
+        pub fn is_initialized(&self) -> bool {
+            self.initialized
         }
     }
 
     impl Drop for Handle {
         fn drop(&mut self) {
-            if !self.0.is_null() {
-                unsafe {
-                    let _ = sys::enc_bindings::svt_av1_enc_deinit_handle(self.as_ptr());
+            if self.ptr.is_null() {
+                return;
+            }
+            unsafe {
+                if self.initialized {
+                    let _ = ffi::svt_av1_enc_deinit(self.ptr);
+                    self.initialized = false;
                 }
-                self.0 = std::ptr::null_mut();
+                let _ = ffi::svt_av1_enc_deinit_handle(self.ptr);
             }
+            self.ptr = std::ptr::null_mut();
         }
     }
 
-    pub struct Encoder {
-        handle: Handle,
+    /// Snapshot of an encoder's input queue.
+    ///
+    /// SVT-AV1 emits one packet per input picture, so pictures sent but not
+    /// yet returned as packets are the ones occupying the library's input
+    /// pool and pipeline.
+    #[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+    pub struct QueueStats {
+        /// Pictures `try_send_frame` lets in flight before returning `WouldBlock`.
+        pub capacity: usize,
+        /// Pictures sent and not yet returned as packets.
+        pub depth: usize,
+        /// Highest `depth` seen so far.
+        pub peak_depth: usize,
+        /// Pictures accepted by `svt_av1_enc_send_picture`.
+        pub sent: u64,
+        /// Packets returned, not counting the end-of-stream packet.
+        pub received: u64,
+        /// `try_send_frame` calls rejected because the queue was full.
+        pub would_block: u64,
     }
 
-    impl Encoder {
-        /// Returns a static version string from the library.
-        pub fn version() -> &'static CStr {
-            unsafe { CStr::from_ptr(sys::enc_bindings::svt_av1_get_version()) }
+    /// Serializes picture input, bounds the pictures in flight and remembers
+    /// whether end of stream was sent.
+    ///
+    /// Every `svt_av1_enc_send_picture` call, including the EOS buffer, runs
+    /// inside [`InputGate::send`], [`InputGate::try_send`] or
+    /// [`InputGate::finish`], so calls never overlap and none can follow EOS.
+    ///
+    /// Only senders take the send lock, and a sender may hold it while
+    /// `send_picture` waits for the pipeline, which needs the receiver to keep
+    /// taking and releasing packets. Everything the receiver touches, the
+    /// counters, the finished flag, the end-of-stream wakeup and the EOS
+    /// failure, therefore lives outside it.
+    pub(crate) struct InputGate {
+        /// Held across each send; true once `finish` has started.
+        closed: Mutex<bool>,
+        /// Set after the EOS buffer has been sent.
+        finished: AtomicBool,
+        /// Why the EOS buffer could not be sent, once `finish` has failed.
+        failure: Mutex<Option<String>>,
+        capacity: usize,
+        sent: AtomicU64,
+        received: AtomicU64,
+        peak_depth: AtomicUsize,
+        would_block: AtomicU64,
+        eos_lock: Mutex<()>,
+        eos: Condvar,
+    }
+
+    impl InputGate {
+        pub(crate) fn new(capacity: usize) -> Self {
+            Self {
+                closed: Mutex::new(false),
+                finished: AtomicBool::new(false),
+                failure: Mutex::new(None),
+                capacity,
+                sent: AtomicU64::new(0),
+                received: AtomicU64::new(0),
+                peak_depth: AtomicUsize::new(0),
+                would_block: AtomicU64::new(0),
+                eos_lock: Mutex::new(()),
+                eos: Condvar::new(),
+            }
         }
 
-        /// Prints version/build info to stderr or SVT_LOG_FILE (if set).
-        pub fn print_version() {
-            unsafe { sys::enc_bindings::svt_av1_print_version() }
+        /// Takes the send lock, failing once end of stream was sent.
+        fn lock(&self) -> Result<MutexGuard<'_, bool>> {
+            let closed = self.closed.lock().unwrap_or_else(|e| e.into_inner());
+            if *closed {
+                return Err(Error::InvalidArgument(
+                    "picture sent after end of stream".into(),
+                ));
+            }
+            Ok(closed)
         }
 
-        pub fn init_default() -> Result<(Self, Configuration)> {
-            let mut handle = Handle::new();
-            let mut cfg: Configuration = unsafe { std::mem::zeroed() };
-            let code = unsafe {
-                sys::enc_bindings::svt_av1_enc_init_handle(
-                    handle.as_mut_ptr(),
-                    std::ptr::null_mut(),
-                    &mut cfg,
-                )
-            };
-            super::ok(code)?;
-            Ok((Self { handle }, cfg))
+        fn depth(&self) -> usize {
+            let received = self.received.load(Ordering::Acquire);
+            let sent = self.sent.load(Ordering::Acquire);
+            sent.saturating_sub(received) as usize
         }
 
-        pub fn set_parameter(&mut self, cfg: &Configuration) -> Result<()> {
-            let code = unsafe {
-                sys::enc_bindings::svt_av1_enc_set_parameter(
-                    self.handle.as_ptr(),
-                    cfg as *const _ as *mut _,
-                )
-            };
-            super::ok(code)
+        /// Counts a sent picture; callers hold the send lock.
+        fn sent(&self) {
+            self.sent.fetch_add(1, Ordering::AcqRel);
+            let depth = self.depth();
+            if depth > self.peak_depth.load(Ordering::Relaxed) {
+                self.peak_depth.store(depth, Ordering::Relaxed);
+            }
         }
 
-        /// Convenience to set a single parameter by name/value using the C parser.
-        pub fn parse_parameter(cfg: &mut Configuration, name: &CStr, value: &CStr) -> Result<()> {
-            let code = unsafe {
-                sys::enc_bindings::svt_av1_enc_parse_parameter(
-                    cfg as *mut _,
-                    name.as_ptr(),
-                    value.as_ptr(),
-                )
-            };
-            super::ok(code)
+        /// Runs `send` unless end of stream was already signalled. Does not
+        /// look at the capacity; the library blocks if its pool is exhausted.
+        pub(crate) fn send<R>(&self, send: impl FnOnce() -> Result<R>) -> Result<R> {
+            let _closed = self.lock()?;
+            let result = send()?;
+            self.sent();
+            Ok(result)
         }
 
-        /// Convenience string version of `parse_parameter`. Returns EB_ErrorBadParameter on failure.
-        pub fn parse_parameter_str(cfg: &mut Configuration, name: &str, value: &str) -> Result<()> {
-            let n = CString::new(name).map_err(|_| Error::Code(0x80001005u32 as i32))?; // EB_ErrorBadParameter
-            let v = CString::new(value).map_err(|_| Error::Code(0x80001005u32 as i32))?;
-            Self::parse_parameter(cfg, &n, &v)
+        /// Like `send`, but returns `Ok(None)` without calling `send` when
+        /// `capacity` pictures are already in flight.
+        pub(crate) fn try_send<R>(&self, send: impl FnOnce() -> Result<R>) -> Result<Option<R>> {
+            let _closed = self.lock()?;
+            if self.depth() >= self.capacity {
+                self.would_block.fetch_add(1, Ordering::Relaxed);
+                return Ok(None);
+            }
+            let result = send()?;
+            self.sent();
+            Ok(Some(result))
         }
 
-        pub fn init(&mut self) -> Result<()> {
-            let code = unsafe { sys::enc_bindings::svt_av1_enc_init(self.handle.as_ptr()) };
-            super::ok(code)
+        /// Records a packet leaving the encoder, freeing one slot.
+        pub(crate) fn received(&self) {
+            self.received.fetch_add(1, Ordering::AcqRel);
         }
 
-        pub fn send_picture(&mut self, pic: &mut BufferHeader) -> Result<()> {
-            let code = unsafe {
-                sys::enc_bindings::svt_av1_enc_send_picture(self.handle.as_ptr(), pic as *mut _)
+        pub(crate) fn stats(&self) -> QueueStats {
+            QueueStats {
+                capacity: self.capacity,
+                depth: self.depth(),
+                peak_depth: self.peak_depth.load(Ordering::Relaxed),
+                sent: self.sent.load(Ordering::Acquire),
+                received: self.received.load(Ordering::Acquire),
+                would_block: self.would_block.load(Ordering::Relaxed),
+            }
+        }
+
+        /// Runs `eos` once; later `send` and `finish` calls fail. Waiters are
+        /// woken only if `eos` succeeds; a failure is kept for [`Self::failed`].
+        pub(crate) fn finish<R>(&self, eos: impl FnOnce() -> Result<R>) -> Result<R> {
+            let mut closed = self
+                .lock()
+                .map_err(|_| Error::InvalidArgument("end of stream already sent".into()))?;
+            *closed = true;
+            let result = eos();
+            drop(closed);
+            match &result {
+                Ok(_) => {
+                    self.finished.store(true, Ordering::Release);
+                    // Taking the lock orders the store before a waiter's check.
+                    drop(self.eos_lock.lock().unwrap_or_else(|e| e.into_inner()));
+                    self.eos.notify_all();
+                }
+                Err(e) => {
+                    let mut failure = self.failure.lock().unwrap_or_else(|e| e.into_inner());
+                    *failure = Some(e.to_string());
+                }
+            }
+            result
+        }
+
+        /// Fails if `finish` could not send the EOS buffer, so no end of
+        /// stream will ever come.
+        pub(crate) fn failed(&self) -> Result<()> {
+            let failure = self.failure.lock().unwrap_or_else(|e| e.into_inner());
+            match &*failure {
+                Some(reason) => Err(Error::InvalidArgument(format!(
+                    "end of stream could not be sent: {reason}"
+                ))),
+                None => Ok(()),
+            }
+        }
+
+        /// True once `finish` has run its `eos` callback.
+        pub(crate) fn is_finished(&self) -> bool {
+            self.finished.load(Ordering::Acquire)
+        }
+
+        /// Waits up to `timeout` for `finish`, returning whether it has run.
+        pub(crate) fn wait_finished(&self, timeout: Duration) -> bool {
+            if self.is_finished() {
+                return true;
+            }
+            let guard = self.eos_lock.lock().unwrap_or_else(|e| e.into_inner());
+            if self.is_finished() {
+                return true;
+            }
+            let _ = self
+                .eos
+                .wait_timeout(guard, timeout)
+                .unwrap_or_else(|e| e.into_inner());
+            self.is_finished()
+        }
+    }
+
+    /// Error from `try_send_frame`.
+    #[derive(Debug, thiserror::Error)]
+    pub enum TrySendError {
+        /// The input queue is at capacity; the frame is handed back so the
+        /// caller can drop it or retry after draining packets.
+        #[error("encoder input queue is full")]
+        WouldBlock(Frame),
+        #[error(transparent)]
+        Failed(#[from] Error),
+    }
+
+    impl TrySendError {
+        /// The rejected frame, if the queue was full.
+        pub fn into_frame(self) -> Option<Frame> {
+            match self {
+                Self::WouldBlock(frame) => Some(frame),
+                Self::Failed(_) => None,
+            }
+        }
+    }
+
+    /// State shared by the [`Encoder`], its [`InputHandle`]s and outstanding
+    /// [`Packet`]s; the component is torn down when the last of them is dropped.
+    struct Shared {
+        handle: Handle,
+        input: InputGate,
+        keyframes: KeyframePolicy,
+        /// Configured `source_width`, `source_height`, `encoder_bit_depth`
+        /// and `encoder_color_format`, which reconstructed pictures share.
+        picture: (u32, u32, u32, u32),
+    }
+
+    // SAFETY: through `&Shared` the component is only reached by
+    // `svt_av1_enc_send_picture` (serialized by `input`), by the single
+    // `Encoder` owning the output side (`&mut Encoder`, and `Encoder` is not
+    // `Sync`) and by `svt_av1_enc_release_out_buffer`, which the library
+    // guards with the buffer's own mutex. Input and output queues are designed
+    // for use from separate threads.
+    unsafe impl Sync for Shared {}
+
+    impl Shared {
+        fn send_picture(&self, pic: &mut BufferHeader) -> Result<()> {
+            if pic.flags & sys::enc_bindings::EB_BUFFERFLAG_EOS != 0 {
+                return Err(Error::InvalidArgument(
+                    "end of stream is signalled by Encoder::finish".into(),
+                ));
+            }
+            if pic.pic_type == sys::enc_bindings::EbAv1PictureType_EB_AV1_INVALID_PICTURE
+                && self.keyframes.is_keyframe(pic.pts)
+            {
+                pic.pic_type = sys::enc_bindings::EbAv1PictureType_EB_AV1_KEY_PICTURE;
+            }
+            self.input.send(|| {
+                let code = unsafe { ffi::svt_av1_enc_send_picture(self.handle.as_ptr(), pic) };
+                super::ok(code)
+            })
+        }
+
+        fn send_eos(&self) -> Result<()> {
+            let mut eos: BufferHeader = unsafe { std::mem::zeroed() };
+            eos.size = std::mem::size_of::<BufferHeader>() as u32;
+            eos.flags = sys::enc_bindings::EB_BUFFERFLAG_EOS;
+            eos.pic_type = sys::enc_bindings::EbAv1PictureType_EB_AV1_INVALID_PICTURE;
+            self.input.finish(|| {
+                let code = unsafe { ffi::svt_av1_enc_send_picture(self.handle.as_ptr(), &mut eos) };
+                super::ok(code)
+            })
+        }
+
+        fn send_frame(&self, frame: &Frame) -> Result<()> {
+            self.input.send(|| self.send_frame_unchecked(frame))
+        }
+
+        fn try_send_frame(&self, frame: Frame) -> std::result::Result<(), TrySendError> {
+            match self.input.try_send(|| self.send_frame_unchecked(&frame))? {
+                Some(()) => Ok(()),
+                None => Err(TrySendError::WouldBlock(frame)),
+            }
+        }
+
+        /// Sends without going through the gate; callers hold it.
+        fn send_frame_unchecked(&self, frame: &Frame) -> Result<()> {
+            self.check_format(frame, "input")?;
+            let mut io_format = frame.io_format();
+            let mut pic: BufferHeader = unsafe { std::mem::zeroed() };
+            pic.size = std::mem::size_of::<BufferHeader>() as u32;
+            pic.p_buffer = &mut io_format as *mut sys::enc_bindings::EbSvtIOFormat as *mut u8;
+            pic.n_filled_len = frame.data_len() as u32;
+            pic.n_alloc_len = frame.data_len() as u32;
+            pic.pts = frame.pts;
+            pic.pic_type = if self.keyframes.is_keyframe(frame.pts) {
+                sys::enc_bindings::EbAv1PictureType_EB_AV1_KEY_PICTURE
+            } else {
+                sys::enc_bindings::EbAv1PictureType_EB_AV1_INVALID_PICTURE
             };
+            let code = unsafe { ffi::svt_av1_enc_send_picture(self.handle.as_ptr(), &mut pic) };
             super::ok(code)
         }
 
-        pub fn get_packet(&mut self, pic_send_done: bool) -> Result<Option<*mut BufferHeader>> {
+        /// Only called by the single owner of the output side: an `Encoder` or
+        /// the `PacketReceiver` split from it.
+        fn get_packet(&self, pic_send_done: bool) -> Result<Option<*mut BufferHeader>> {
             // EB_NoErrorEmptyQueue indicates no packet available yet; not an error.
             const EB_NO_ERROR_EMPTY_QUEUE: i32 =
                 sys::enc_bindings::EbErrorType_EB_NoErrorEmptyQueue;
             let mut packet: *mut BufferHeader = std::ptr::null_mut();
             let code: i32 = unsafe {
-                sys::enc_bindings::svt_av1_enc_get_packet(
+                ffi::svt_av1_enc_get_packet(
                     self.handle.as_ptr(),
                     &mut packet as *mut _,
                     if pic_send_done { 1 } else { 0 },
                 )
             };
             if code == 0 {
+                // SAFETY: the library hands out a valid header until it is released.
+                if unsafe { (*packet).flags } & sys::enc_bindings::EB_BUFFERFLAG_EOS == 0 {
+                    self.input.received();
+                }
                 return Ok(Some(packet));
             }
             if code == EB_NO_ERROR_EMPTY_QUEUE {
@@ -327,33 +712,439 @@ pub mod encoder {
             Err(super::Error::Code(code))
         }
 
-        pub fn release_out_buffer(&mut self, packet: &mut *mut BufferHeader) {
-            unsafe { sys::enc_bindings::svt_av1_enc_release_out_buffer(packet as *mut _) };
+        /// Copies the next reconstructed picture into `frame`; same caller
+        /// rules as `get_packet`. The library fills the buffer at the
+        /// configured size, so a `frame` of another size or format is
+        /// rejected before it is called.
+        /// Refuses a `frame` whose size, bit depth or color format differs
+        /// from the configured picture; the library reads and writes planes
+        /// using the configured layout.
+        fn check_format(&self, frame: &Frame, role: &str) -> Result<()> {
+            let given = (
+                frame.width(),
+                frame.height(),
+                frame.bit_depth().bits(),
+                frame.color_format() as u32,
+            );
+            if given == self.picture {
+                return Ok(());
+            }
+            let (w, h, depth, format) = self.picture;
+            Err(Error::InvalidArgument(format!(
+                "{role} frame is {}x{} {}-bit {:?}, the encoder is configured for \
+                 {w}x{h} {depth}-bit with color format {format}",
+                given.0,
+                given.1,
+                given.2,
+                frame.color_format()
+            )))
         }
 
-        pub fn get_stream_header(&mut self, packet: &mut *mut BufferHeader) -> Result<()> {
+        fn get_recon(&self, frame: &mut Frame) -> Result<bool> {
+            self.check_format(frame, "recon")?;
+            let format = crate::yuv::RawYuvFormat::new(
+                frame.width(),
+                frame.height(),
+                crate::yuv::PixelLayout::planar(frame.color_format()),
+                frame.bit_depth(),
+            );
+            let mut data = vec![0u8; format.frame_size()];
+            let mut recon: BufferHeader = unsafe { std::mem::zeroed() };
+            recon.size = std::mem::size_of::<BufferHeader>() as u32;
+            recon.p_buffer = data.as_mut_ptr();
+            recon.n_alloc_len = data.len() as u32;
+            let code = unsafe { ffi::svt_av1_get_recon(self.handle.as_ptr(), &mut recon) };
+            if code == sys::enc_bindings::EbErrorType_EB_NoErrorEmptyQueue {
+                return Ok(false);
+            }
+            super::ok(code)?;
+            if (recon.n_filled_len as usize) < data.len() {
+                // The end-of-stream marker carries no picture.
+                return Ok(false);
+            }
+            format.unpack(&data, frame)?;
+            frame.pts = recon.pts;
+            Ok(true)
+        }
+    }
+
+    /// Checks the parts of a configuration the library would otherwise reject
+    /// with an opaque error code during `svt_av1_enc_set_parameter`.
+    pub(crate) fn validate(cfg: &Configuration) -> Result<()> {
+        if cfg.source_width == 0 || cfg.source_height == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "resolution must be non-zero, got {}x{}",
+                cfg.source_width, cfg.source_height
+            )));
+        }
+        if cfg.frame_rate_numerator == 0 || cfg.frame_rate_denominator == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "frame rate must be non-zero, got {}/{}",
+                cfg.frame_rate_numerator, cfg.frame_rate_denominator
+            )));
+        }
+        Ok(())
+    }
+
+    /// Where keyframes go, decided per picture from its pts.
+    ///
+    /// Every policy except `Auto` turns off the library's scene-change
+    /// detection and periodic keyframes and sends the chosen pictures as
+    /// forced keyframes instead. The decision depends only on pts, so encoders
+    /// of different renditions, or of different chunks of one source, put
+    /// keyframes on identical pts. The first picture of a stream is always a
+    /// keyframe.
+    #[derive(Clone, Debug, Default, Eq, PartialEq)]
+    pub enum KeyframePolicy {
+        /// Keyframes as the configuration's `intra_period_length` and
+        /// `scene_change_detection` place them.
+        #[default]
+        Auto,
+        /// A keyframe on every pts that is a multiple of the interval.
+        Interval(u32),
+        /// Keyframes on exactly these pts.
+        Pts(Vec<i64>),
+        /// Keyframes on the given scene cuts, and every `max_interval` pts
+        /// after the last cut (counting from pts 0 before the first).
+        SceneCut { cuts: Vec<i64>, max_interval: u32 },
+    }
+
+    impl KeyframePolicy {
+        /// Whether a picture with this pts is forced to be a keyframe.
+        pub fn is_keyframe(&self, pts: i64) -> bool {
+            match self {
+                Self::Auto => false,
+                Self::Interval(n) => pts.rem_euclid(*n as i64) == 0,
+                Self::Pts(list) => list.binary_search(&pts).is_ok(),
+                Self::SceneCut { cuts, max_interval } => {
+                    let last_cut = match cuts.binary_search(&pts) {
+                        Ok(_) => return true,
+                        Err(0) => 0,
+                        Err(i) => cuts[i - 1],
+                    };
+                    (pts - last_cut).rem_euclid(*max_interval as i64) == 0
+                }
+            }
+        }
+
+        /// Checks intervals and sorts pts lists for lookup.
+        pub(crate) fn normalize(&mut self) -> Result<()> {
+            match self {
+                Self::Auto => {}
+                Self::Interval(0)
+                | Self::SceneCut {
+                    max_interval: 0, ..
+                } => {
+                    return Err(Error::InvalidArgument(
+                        "keyframe interval must be at least one picture".into(),
+                    ))
+                }
+                Self::Interval(_) => {}
+                Self::Pts(list) | Self::SceneCut { cuts: list, .. } => {
+                    list.sort_unstable();
+                    list.dedup();
+                }
+            }
+            Ok(())
+        }
+
+        /// Hands keyframe placement to the forced picture types.
+        pub(crate) fn configure(&self, cfg: &mut Configuration) {
+            if *self != Self::Auto {
+                cfg.scene_change_detection = 0;
+                cfg.intra_period_length = -1;
+                cfg.force_key_frames = 1;
+            }
+        }
+    }
+
+    /// Collects the configuration for a new encoder.
+    ///
+    /// The builder owns the component handle with the library defaults loaded;
+    /// [`EncoderBuilder::build`] validates the configuration, applies it and
+    /// initializes the encoder, consuming the builder. Parameters therefore
+    /// cannot change after `init`, and pictures cannot be sent before it.
+    pub struct EncoderBuilder {
+        handle: Handle,
+        cfg: Configuration,
+        input_capacity: Option<usize>,
+        keyframes: KeyframePolicy,
+    }
+
+    /// A conservative estimate of the library's input pool, which the API
+    /// does not report: the look-ahead window (capped as the library does)
+    /// plus one mini-GOP. The library holds at least that many pictures
+    /// before the first packet comes out and sizes its pool to fit them, so
+    /// the estimate stays at or below the real pool when the look-ahead is
+    /// set explicitly. Left to the library, the cap is assumed, and the
+    /// estimate can exceed a pool sized for a shorter automatic look-ahead.
+    fn default_input_capacity(cfg: &Configuration) -> usize {
+        const MAX_LOOK_AHEAD: u32 = 120;
+        let look_ahead = cfg.look_ahead_distance.min(MAX_LOOK_AHEAD);
+        let mini_gop = 1u32 << cfg.hierarchical_levels.min(5);
+        (look_ahead + mini_gop) as usize
+    }
+
+    impl EncoderBuilder {
+        /// Creates the component handle and loads the default configuration.
+        pub fn new() -> Result<Self> {
+            let mut handle = Handle::new();
+            let mut cfg: Configuration = unsafe { std::mem::zeroed() };
             let code = unsafe {
-                sys::enc_bindings::svt_av1_enc_stream_header(self.handle.as_ptr(), packet as *mut _)
+                ffi::svt_av1_enc_init_handle(handle.as_mut_ptr(), std::ptr::null_mut(), &mut cfg)
+            };
+            super::ok(code)?;
+            Ok(Self {
+                handle,
+                cfg,
+                input_capacity: None,
+                keyframes: KeyframePolicy::Auto,
+            })
+        }
+
+        pub fn config(&self) -> &Configuration {
+            &self.cfg
+        }
+
+        pub fn config_mut(&mut self) -> &mut Configuration {
+            &mut self.cfg
+        }
+
+        /// Replaces the whole configuration, e.g. one prepared with `ConfigExt`
+        /// from [`EncoderBuilder::config`].
+        pub fn with_config(mut self, cfg: Configuration) -> Self {
+            self.cfg = cfg;
+            self
+        }
+
+        /// Applies the fields set in `settings` on top of the configuration.
+        pub fn with_settings(mut self, settings: &crate::settings::EncoderSettings) -> Self {
+            settings.apply_to(&mut self.cfg);
+            self
+        }
+
+        /// Sets one parameter by name/value using the C parser (`SvtAv1EncApp` names).
+        pub fn parameter(mut self, name: &str, value: &str) -> Result<Self> {
+            Encoder::parse_parameter_str(&mut self.cfg, name, value)?;
+            Ok(self)
+        }
+
+        /// Bounds the pictures in flight for `try_send_frame`. Defaults to a
+        /// conservative estimate of the library's input pool: the look-ahead
+        /// distance plus one mini-GOP. A bound above the real pool lets
+        /// `try_send_frame` block in the library like `send_frame`; one below
+        /// what the library holds before its first packet makes it return
+        /// `WouldBlock` until end of stream.
+        pub fn input_capacity(mut self, pictures: usize) -> Self {
+            self.input_capacity = Some(pictures);
+            self
+        }
+
+        /// Sets where keyframes go; see [`KeyframePolicy`]. The policy's
+        /// configuration changes are applied by `build`.
+        pub fn keyframe_policy(mut self, policy: KeyframePolicy) -> Self {
+            self.keyframes = policy;
+            self
+        }
+
+        /// Validates and applies the configuration, then initializes the encoder.
+        pub fn build(self) -> Result<Encoder> {
+            let Self {
+                mut handle,
+                mut cfg,
+                input_capacity,
+                mut keyframes,
+            } = self;
+            if input_capacity == Some(0) {
+                return Err(Error::InvalidArgument(
+                    "input capacity must be at least one picture".into(),
+                ));
+            }
+            validate(&cfg)?;
+            keyframes.normalize()?;
+            keyframes.configure(&mut cfg);
+            let code = unsafe { ffi::svt_av1_enc_set_parameter(handle.as_ptr(), &mut cfg) };
+            super::ok(code)?;
+            let code = unsafe { ffi::svt_av1_enc_init(handle.as_ptr()) };
+            super::ok(code)?;
+            handle.initialized = true;
+            Ok(Encoder {
+                shared: Arc::new(Shared {
+                    handle,
+                    input: InputGate::new(
+                        input_capacity.unwrap_or_else(|| default_input_capacity(&cfg)),
+                    ),
+                    keyframes,
+                    picture: (
+                        cfg.source_width,
+                        cfg.source_height,
+                        cfg.encoder_bit_depth,
+                        cfg.encoder_color_format,
+                    ),
+                }),
+                _not_sync: PhantomData,
+            })
+        }
+    }
+
+    /// A running encoder, obtained from [`EncoderBuilder::build`].
+    ///
+    /// End of stream is signalled by [`Encoder::finish`], which consumes the
+    /// encoder so no picture can follow it.
+    ///
+    /// The encoder is `Send` but not `Sync`: move it to the thread that collects
+    /// packets and feed pictures from elsewhere through [`Encoder::input`].
+    pub struct Encoder {
+        shared: Arc<Shared>,
+        _not_sync: PhantomData<Cell<()>>,
+    }
+
+    impl Encoder {
+        /// Returns a static version string from the library.
+        pub fn version() -> &'static CStr {
+            unsafe { CStr::from_ptr(ffi::svt_av1_get_version()) }
+        }
+
+        /// Prints version/build info to stderr or SVT_LOG_FILE (if set).
+        pub fn print_version() {
+            unsafe { ffi::svt_av1_print_version() }
+        }
+
+        /// Shorthand for [`EncoderBuilder::new`].
+        pub fn builder() -> Result<EncoderBuilder> {
+            EncoderBuilder::new()
+        }
+
+        /// Convenience to set a single parameter by name/value using the C parser.
+        pub fn parse_parameter(cfg: &mut Configuration, name: &CStr, value: &CStr) -> Result<()> {
+            let code = unsafe {
+                ffi::svt_av1_enc_parse_parameter(cfg as *mut _, name.as_ptr(), value.as_ptr())
             };
             super::ok(code)
         }
 
+        /// Convenience string version of `parse_parameter`. A name or value
+        /// containing a NUL byte is reported as `Error::Parameter`.
+        pub fn parse_parameter_str(cfg: &mut Configuration, name: &str, value: &str) -> Result<()> {
+            let nul = |what: &str| Error::Parameter {
+                key: name.replace('\0', "\\0"),
+                reason: format!("{what} contains a NUL byte"),
+            };
+            let n = CString::new(name).map_err(|_| nul("name"))?;
+            let v = CString::new(value).map_err(|_| nul("value"))?;
+            Self::parse_parameter(cfg, &n, &v)
+        }
+
+        fn handle(&self) -> *mut Component {
+            self.shared.handle.as_ptr()
+        }
+
+        /// Returns a cloneable, thread-safe handle for sending pictures.
+        pub fn input(&self) -> InputHandle {
+            InputHandle {
+                shared: Arc::clone(&self.shared),
+            }
+        }
+
+        /// Sends one picture. Buffers flagged `EB_BUFFERFLAG_EOS` are rejected;
+        /// use [`Encoder::finish`] to end the stream. A `pic_type` left at
+        /// `EB_AV1_INVALID_PICTURE` is set to a keyframe where the
+        /// [`KeyframePolicy`] asks for one.
+        pub fn send_picture(&mut self, pic: &mut BufferHeader) -> Result<()> {
+            self.shared.send_picture(pic)
+        }
+
+        /// Sends a [`Frame`], using its `pts`. The library
+        /// copies the planes before returning. A frame whose size, bit depth
+        /// or color format differs from the configuration is refused with
+        /// `Error::InvalidArgument`.
+        pub fn send_frame(&mut self, frame: &Frame) -> Result<()> {
+            self.shared.send_frame(frame)
+        }
+
+        /// Sends `frame` unless `input_capacity` pictures are already in
+        /// flight, in which case it is returned in `TrySendError::WouldBlock`.
+        /// A live source can drop it instead of stalling on a full pool. If
+        /// the capacity exceeds the library's pool, the send blocks until a
+        /// packet is released, as `send_frame` does.
+        pub fn try_send_frame(&mut self, frame: Frame) -> std::result::Result<(), TrySendError> {
+            self.shared.try_send_frame(frame)
+        }
+
+        /// Maximum pictures in flight accepted by `try_send_frame`.
+        pub fn input_capacity(&self) -> usize {
+            self.shared.input.capacity
+        }
+
+        /// The keyframe policy set on the builder.
+        pub fn keyframe_policy(&self) -> &KeyframePolicy {
+            &self.shared.keyframes
+        }
+
+        /// Current queue depth and counters.
+        pub fn queue_stats(&self) -> QueueStats {
+            self.shared.input.stats()
+        }
+
+        pub fn get_packet(&mut self, pic_send_done: bool) -> Result<Option<*mut BufferHeader>> {
+            self.shared.get_packet(pic_send_done)
+        }
+
+        pub fn release_out_buffer(&mut self, packet: &mut *mut BufferHeader) {
+            unsafe { ffi::svt_av1_enc_release_out_buffer(packet as *mut _) };
+        }
+
+        pub fn get_stream_header(&mut self, packet: &mut *mut BufferHeader) -> Result<()> {
+            let code = unsafe { ffi::svt_av1_enc_stream_header(self.handle(), packet as *mut _) };
+            super::ok(code)
+        }
+
         /// # Safety
         ///
         /// `packet` must be a valid stream header previously returned by
         /// `get_stream_header` for this encoder instance and not already released.
         pub unsafe fn stream_header_release(&mut self, packet: *mut BufferHeader) -> Result<()> {
-            let code = unsafe { sys::enc_bindings::svt_av1_enc_stream_header_release(packet) };
+            let code = unsafe { ffi::svt_av1_enc_stream_header_release(packet) };
             super::ok(code)
         }
 
-        pub fn get_recon(&mut self, buffer: &mut BufferHeader) -> Result<()> {
-            let code = unsafe {
-                sys::enc_bindings::svt_av1_get_recon(self.handle.as_ptr(), buffer as *mut _)
+        /// Fetches the stream header, copies it out and releases it, returning the
+        /// decoded sequence header.
+        pub fn sequence_header(&mut self) -> Result<crate::obu::SequenceHeader> {
+            let mut header: *mut BufferHeader = std::ptr::null_mut();
+            self.get_stream_header(&mut header)?;
+            if header.is_null() {
+                return Err(Error::Null);
+            }
+            // SAFETY: the stream header stays valid until it is released below.
+            let bytes = unsafe {
+                let h = &*header;
+                if h.p_buffer.is_null() {
+                    Vec::new()
+                } else {
+                    std::slice::from_raw_parts(h.p_buffer, h.n_filled_len as usize).to_vec()
+                }
             };
+            // SAFETY: `header` came from `get_stream_header` above and is released once.
+            unsafe { self.stream_header_release(header)? };
+            crate::obu::SequenceHeader::from_bytes(&bytes)
+        }
+
+        pub fn get_recon(&mut self, buffer: &mut BufferHeader) -> Result<()> {
+            let code = unsafe { ffi::svt_av1_get_recon(self.handle(), buffer as *mut _) };
             super::ok(code)
         }
 
+        /// Copies the next reconstructed picture into `frame` and sets its
+        /// pts, returning `false` if none is ready. Needs `recon_enabled` in
+        /// the configuration and a `frame` with the stream's size, bit depth
+        /// and color format; any other frame is refused with
+        /// `Error::InvalidArgument`. Pictures come in coding order, one per
+        /// packet.
+        pub fn recv_recon(&mut self, frame: &mut Frame) -> Result<bool> {
+            self.shared.get_recon(frame)
+        }
+
         /// # Safety
         ///
         /// `info` must point to a writable buffer matching the requested `id`
@@ -363,20 +1154,18 @@ pub mod encoder {
             id: u32,
             info: *mut std::ffi::c_void,
         ) -> Result<()> {
-            let code = unsafe {
-                sys::enc_bindings::svt_av1_enc_get_stream_info(self.handle.as_ptr(), id, info)
-            };
+            let code = unsafe { ffi::svt_av1_enc_get_stream_info(self.handle(), id, info) };
             super::ok(code)
         }
 
         /// Drain available packets non-blocking and call `f` for each.
-        /// Stops when the queue is empty. Set `pic_send_done` to true after feeding all pictures.
-        pub fn drain_packets<F>(&mut self, pic_send_done: bool, mut f: F) -> Result<()>
+        /// Stops when the queue is empty.
+        pub fn drain_packets<F>(&mut self, mut f: F) -> Result<()>
         where
             F: FnMut(&BufferHeader),
         {
             loop {
-                match self.get_packet(pic_send_done)? {
+                match self.get_packet(false)? {
                     Some(ptr) => {
                         // SAFETY: FFI returns valid pointer to BufferHeaderType until released
                         let header = unsafe { &*ptr };
@@ -389,51 +1178,278 @@ pub mod encoder {
             }
         }
 
-        /// Returns an iterator that yields packets (RAII-released on drop of each item) until empty.
-        pub fn packets<'a>(&'a mut self, pic_send_done: bool) -> PacketIter<'a> {
-            PacketIter {
-                enc: self,
-                pic_send_done,
+        /// Returns an iterator over the packets available right now
+        /// (RAII-released on drop of each item); it ends when the queue is empty.
+        pub fn packets(&mut self) -> PacketIter<'_> {
+            PacketIter { enc: self }
+        }
+
+        /// Signals end of stream and returns every remaining packet, the last
+        /// one carrying `EB_BUFFERFLAG_EOS`.
+        pub fn finish(mut self) -> Result<Vec<Packet>> {
+            self.shared.send_eos()?;
+
+            let mut packets = Vec::new();
+            // With `pic_send_done` set the call blocks until a packet is ready.
+            while let Some(ptr) = self.get_packet(true)? {
+                let packet = Packet::new(ptr, &self.shared);
+                let eos = packet.is_eos();
+                packets.push(packet);
+                if eos {
+                    break;
+                }
             }
+            Ok(packets)
         }
+
+        /// Splits the encoder into an input half and an output half that can
+        /// be moved to separate threads, e.g. capture and muxing.
+        pub fn split(self) -> (FrameSender, PacketReceiver) {
+            let sender = FrameSender {
+                input: self.input(),
+                finished: false,
+            };
+            let receiver = PacketReceiver {
+                shared: self.shared,
+                done: false,
+                _not_sync: PhantomData,
+            };
+            (sender, receiver)
+        }
+    }
+
+    /// Input half of a [`split`](Encoder::split) encoder.
+    ///
+    /// Finishing or dropping the sender signals end of stream, after which
+    /// [`PacketReceiver::recv`] drains the remaining packets and returns `None`.
+    /// [`InputHandle`]s taken from it fail once that has happened.
+    pub struct FrameSender {
+        input: InputHandle,
+        finished: bool,
     }
 
-    impl Drop for Encoder {
+    impl FrameSender {
+        /// See [`Encoder::send_picture`].
+        pub fn send_picture(&mut self, pic: &mut BufferHeader) -> Result<()> {
+            self.input.send_picture(pic)
+        }
+
+        /// See [`Encoder::send_frame`].
+        pub fn send_frame(&mut self, frame: &Frame) -> Result<()> {
+            self.input.send_frame(frame)
+        }
+
+        /// See [`Encoder::try_send_frame`].
+        pub fn try_send_frame(&mut self, frame: Frame) -> std::result::Result<(), TrySendError> {
+            self.input.try_send_frame(frame)
+        }
+
+        /// See [`Encoder::queue_stats`].
+        pub fn queue_stats(&self) -> QueueStats {
+            self.input.queue_stats()
+        }
+
+        /// Returns a cloneable handle feeding the same encoder.
+        pub fn input(&self) -> InputHandle {
+            self.input.clone()
+        }
+
+        /// Signals end of stream.
+        pub fn finish(mut self) -> Result<()> {
+            self.finished = true;
+            self.input.shared.send_eos()
+        }
+    }
+
+    impl Drop for FrameSender {
         fn drop(&mut self) {
-            // Safe to call; ignore errors in Drop
-            let _ = unsafe { sys::enc_bindings::svt_av1_enc_deinit(self.handle.as_ptr()) };
+            if !self.finished {
+                let _ = self.input.shared.send_eos();
+            }
+        }
+    }
+
+    /// Output half of a [`split`](Encoder::split) encoder. `Send` but not
+    /// `Sync`, like [`Encoder`].
+    pub struct PacketReceiver {
+        shared: Arc<Shared>,
+        done: bool,
+        _not_sync: PhantomData<Cell<()>>,
+    }
+
+    impl PacketReceiver {
+        /// How long `recv` sleeps between polls before end of stream; waking
+        /// early once the sender finishes.
+        const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+        /// Waits for the next packet. Before end of stream the queue is polled;
+        /// afterwards the library's blocking `get_packet` is used. Returns
+        /// `None` after the packet flagged `EB_BUFFERFLAG_EOS`, and an error
+        /// once the queue is drained if `finish` failed to send end of stream.
+        pub fn recv(&mut self) -> Result<Option<Packet>> {
+            while !self.done {
+                let finished = self.shared.input.is_finished();
+                match self.shared.get_packet(finished)? {
+                    Some(ptr) => {
+                        let packet = Packet::new(ptr, &self.shared);
+                        self.done = packet.is_eos();
+                        return Ok(Some(packet));
+                    }
+                    None if finished => self.done = true,
+                    None => {
+                        self.shared.input.failed()?;
+                        self.shared.input.wait_finished(Self::POLL_INTERVAL);
+                    }
+                }
+            }
+            Ok(None)
         }
+
+        /// Returns a packet if one is ready, without waiting.
+        pub fn try_recv(&mut self) -> Result<Option<Packet>> {
+            if self.done {
+                return Ok(None);
+            }
+            let packet = self
+                .shared
+                .get_packet(false)?
+                .map(|ptr| Packet::new(ptr, &self.shared));
+            self.done = packet.as_ref().is_some_and(Packet::is_eos);
+            Ok(packet)
+        }
+
+        /// See [`Encoder::recv_recon`].
+        pub fn recv_recon(&mut self, frame: &mut Frame) -> Result<bool> {
+            self.shared.get_recon(frame)
+        }
+
+        /// True once the end-of-stream packet has been returned.
+        pub fn is_done(&self) -> bool {
+            self.done
+        }
+
+        /// See [`Encoder::queue_stats`].
+        pub fn queue_stats(&self) -> QueueStats {
+            self.shared.input.stats()
+        }
+    }
+
+    /// Yields packets from [`PacketReceiver::recv`] until end of stream.
+    impl Iterator for PacketReceiver {
+        type Item = Result<Packet>;
+        fn next(&mut self) -> Option<Self::Item> {
+            self.recv().transpose()
+        }
+    }
+
+    /// Sends pictures to an [`Encoder`] from any thread.
+    ///
+    /// Clones share one input queue; sends are serialized and fail with
+    /// `Error::InvalidArgument` once [`Encoder::finish`] has signalled end of
+    /// stream. A handle keeps the encoder component alive until it is dropped.
+    #[derive(Clone)]
+    pub struct InputHandle {
+        shared: Arc<Shared>,
+    }
+
+    impl InputHandle {
+        /// See [`Encoder::send_picture`].
+        pub fn send_picture(&self, pic: &mut BufferHeader) -> Result<()> {
+            self.shared.send_picture(pic)
+        }
+
+        /// See [`Encoder::send_frame`].
+        pub fn send_frame(&self, frame: &Frame) -> Result<()> {
+            self.shared.send_frame(frame)
+        }
+
+        /// See [`Encoder::try_send_frame`].
+        pub fn try_send_frame(&self, frame: Frame) -> std::result::Result<(), TrySendError> {
+            self.shared.try_send_frame(frame)
+        }
+
+        /// See [`Encoder::queue_stats`].
+        pub fn queue_stats(&self) -> QueueStats {
+            self.shared.input.stats()
+        }
+    }
+
+    /// RAII packet wrapper: releases the underlying buffer on drop. Keeps the
+    /// encoder alive, so packets may outlive [`Encoder::finish`].
+    pub struct Packet {
+        ptr: *mut BufferHeader,
+        _encoder: Arc<Shared>,
     }
 
-    /// RAII packet wrapper: releases the underlying buffer on drop.
-    pub struct Packet(*mut BufferHeader);
+    // SAFETY: the buffer is owned by this packet until released, and
+    // `svt_av1_enc_release_out_buffer` may be called from any thread.
+    unsafe impl Send for Packet {}
+
     impl Packet {
+        fn new(ptr: *mut BufferHeader, encoder: &Arc<Shared>) -> Self {
+            Self {
+                ptr,
+                _encoder: Arc::clone(encoder),
+            }
+        }
         pub fn as_ptr(&self) -> *mut BufferHeader {
-            self.0
+            self.ptr
         }
         pub fn header(&self) -> &BufferHeader {
-            unsafe { &*self.0 }
+            unsafe { &*self.ptr }
+        }
+        /// Encoded bitstream bytes of this packet.
+        pub fn data(&self) -> &[u8] {
+            let h = self.header();
+            if h.p_buffer.is_null() || h.n_filled_len == 0 {
+                return &[];
+            }
+            // SAFETY: the encoder guarantees `n_filled_len` valid bytes until release.
+            unsafe { std::slice::from_raw_parts(h.p_buffer, h.n_filled_len as usize) }
+        }
+        pub fn pts(&self) -> i64 {
+            self.header().pts
+        }
+        pub fn dts(&self) -> i64 {
+            self.header().dts
+        }
+        pub fn flags(&self) -> u32 {
+            self.header().flags
+        }
+        pub fn pic_type(&self) -> sys::enc_bindings::EbAv1PictureType {
+            self.header().pic_type
+        }
+        /// True when the packet starts with a key frame (a random access point).
+        pub fn is_keyframe(&self) -> bool {
+            self.pic_type() == sys::enc_bindings::EbAv1PictureType_EB_AV1_KEY_PICTURE
+        }
+        /// True for the final packet signalled after an EOS picture.
+        pub fn is_eos(&self) -> bool {
+            self.flags() & sys::enc_bindings::EB_BUFFERFLAG_EOS != 0
+        }
+        /// Iterates the OBUs of this packet's bitstream.
+        pub fn obus(&self) -> crate::obu::ObuReader<'_> {
+            crate::obu::ObuReader::new(self.data())
         }
     }
     impl Drop for Packet {
         fn drop(&mut self) {
-            if !self.0.is_null() {
-                let mut p = self.0;
-                unsafe { sys::enc_bindings::svt_av1_enc_release_out_buffer(&mut p as *mut _) };
-                self.0 = std::ptr::null_mut();
+            if !self.ptr.is_null() {
+                let mut p = self.ptr;
+                unsafe { ffi::svt_av1_enc_release_out_buffer(&mut p as *mut _) };
+                self.ptr = std::ptr::null_mut();
             }
         }
     }
 
     pub struct PacketIter<'a> {
         enc: &'a mut Encoder,
-        pic_send_done: bool,
     }
     impl<'a> Iterator for PacketIter<'a> {
         type Item = Result<Packet>;
         fn next(&mut self) -> Option<Self::Item> {
-            match self.enc.get_packet(self.pic_send_done) {
-                Ok(Some(ptr)) => Some(Ok(Packet(ptr))),
+            match self.enc.get_packet(false) {
+                Ok(Some(ptr)) => Some(Ok(Packet::new(ptr, &self.enc.shared))),
                 Ok(None) => None,
                 Err(e) => Some(Err(e)),
             }
@@ -441,9 +1457,20 @@ pub mod encoder {
     }
 }
 
+/// Async encoder front end running the blocking calls on dedicated threads.
+#[cfg(feature = "async")]
+pub mod async_encoder;
+
 #[cfg(feature = "decoder")]
 pub mod decoder {
     use super::*;
+    use crate::config::{BitDepth, ColorFormat};
+    use crate::frame::Frame;
+    #[cfg(test)]
+    use crate::mock::dec as ffi;
+    use std::marker::PhantomData;
+    #[cfg(not(test))]
+    use sys::dec_bindings as ffi;
 
     pub use sys::dec_bindings::EbAV1FrameInfo as FrameInfo;
     pub use sys::dec_bindings::EbAV1StreamInfo as StreamInfo;
@@ -451,9 +1478,17 @@ pub mod decoder {
     pub use sys::dec_bindings::EbComponentType as Component;
     pub use sys::dec_bindings::EbSvtAv1DecConfiguration as Configuration;
 
-    pub struct Handle(*mut Component);
+    /// Owns a decoder component for its whole lifecycle.
+    ///
+    /// Teardown mirrors setup: `svt_av1_dec_deinit` runs once if (and only if)
+    /// `svt_av1_dec_init` succeeded, followed by `svt_av1_dec_deinit_handle`.
+    ///
+    /// The handle is `Send` but not `Sync`, like the encoder's.
+    pub struct Handle {
+        ptr: *mut Component,
+        initialized: bool,
+    }
     unsafe impl Send for Handle {}
-    unsafe impl Sync for Handle {}
 
     impl Default for Handle {
         fn default() -> Self {
@@ -463,72 +1498,194 @@ pub mod decoder {
 
     impl Handle {
         pub fn new() -> Self {
-            Self(std::ptr::null_mut())
+            Self {
+                ptr: std::ptr::null_mut(),
+                initialized: false,
+            }
         }
         pub fn as_mut_ptr(&mut self) -> *mut *mut Component {
-            &mut self.0 as *mut _
+            &mut self.ptr as *mut _
         }
         pub fn as_ptr(&self) -> *mut Component {
-            self.0
+            self.ptr
         }
         pub fn is_null(&self) -> bool {
-            self.0.is_null()
+            self.ptr.is_null()
+        }
+        /// True once `svt_av1_dec_init` has succeeded on this handle.
+        pub fn is_initialized(&self) -> bool {
+            self.initialized
         }
     }
 
     impl Drop for Handle {
         fn drop(&mut self) {
-            if !self.0.is_null() {
-                unsafe {
-                    sys::dec_bindings::svt_av1_dec_deinit_handle(self.as_ptr());
+            if self.ptr.is_null() {
+                return;
+            }
+            unsafe {
+                if self.initialized {
+                    ffi::svt_av1_dec_deinit(self.ptr);
+                    self.initialized = false;
                 }
-                self.0 = std::ptr::null_mut();
+                ffi::svt_av1_dec_deinit_handle(self.ptr);
             }
+            self.ptr = std::ptr::null_mut();
         }
     }
 
-    pub struct Decoder {
+    /// Typestate: the handle exists and parameters may be set.
+    #[derive(Debug)]
+    pub enum Configured {}
+
+    /// Typestate: `svt_av1_dec_init` succeeded and data can be decoded.
+    #[derive(Debug)]
+    pub enum Running {}
+
+    /// Output planes handed to `svt_av1_dec_get_picture`.
+    ///
+    /// Every plane is allocated at full luma size so any chroma format fits.
+    struct OutputBuffers {
+        width: u32,
+        height: u32,
+        bytes_per_sample: usize,
+        planes: [Vec<u8>; 3],
+        format: sys::dec_bindings::EbSvtIOFormat,
+    }
+
+    impl OutputBuffers {
+        fn new(width: u32, height: u32, bytes_per_sample: usize) -> Self {
+            let len = width as usize * height as usize * bytes_per_sample;
+            Self {
+                width,
+                height,
+                bytes_per_sample,
+                planes: [vec![0; len], vec![0; len], vec![0; len]],
+                format: unsafe { std::mem::zeroed() },
+            }
+        }
+
+        /// Resets the picture description before a `get_picture` call.
+        fn prepare(&mut self) -> *mut sys::dec_bindings::EbSvtIOFormat {
+            let format = &mut self.format;
+            format.luma = self.planes[0].as_mut_ptr();
+            format.cb = self.planes[1].as_mut_ptr();
+            format.cr = self.planes[2].as_mut_ptr();
+            format.y_stride = self.width;
+            format.cb_stride = self.width;
+            format.cr_stride = self.width;
+            format.width = 0;
+            format.height = 0;
+            format as *mut _
+        }
+    }
+
+    /// A decoded picture copied out of the decoder's output buffers.
+    #[derive(Clone, Debug)]
+    pub struct DecodedFrame {
+        frame: Frame,
+    }
+
+    impl DecodedFrame {
+        pub fn width(&self) -> u32 {
+            self.frame.width()
+        }
+        pub fn height(&self) -> u32 {
+            self.frame.height()
+        }
+        pub fn bit_depth(&self) -> BitDepth {
+            self.frame.bit_depth()
+        }
+        pub fn color_format(&self) -> ColorFormat {
+            self.frame.color_format()
+        }
+        pub fn pts(&self) -> i64 {
+            self.frame.pts
+        }
+        /// Samples of plane `p`; see [`Frame`] for the sample layout.
+        pub fn plane(&self, p: usize) -> &[u8] {
+            self.frame.plane(p)
+        }
+        /// Row stride of plane `p`, in samples.
+        pub fn stride(&self, p: usize) -> usize {
+            self.frame.stride(p)
+        }
+        pub fn frame(&self) -> &Frame {
+            &self.frame
+        }
+        pub fn into_frame(self) -> Frame {
+            self.frame
+        }
+    }
+
+    /// SVT-AV1 decoder. [`Decoder::init_default`] yields a `Decoder<Configured>`;
+    /// [`Decoder::init`] consumes it and returns a `Decoder<Running>`.
+    pub struct Decoder<S = Running> {
         handle: Handle,
+        /// Maximum picture size and bit depth from the configuration.
+        max_width: u32,
+        max_height: u32,
+        max_bit_depth: u32,
+        /// The last sequence header OBU seen by `decode`, so a repeated one is
+        /// not parsed again.
+        sequence_header: Vec<u8>,
+        output: Option<OutputBuffers>,
+        _state: PhantomData<S>,
     }
 
-    impl Decoder {
+    impl Decoder<Configured> {
         pub fn init_default() -> Result<(Self, Configuration)> {
             let mut handle = Handle::new();
             let mut cfg: Configuration = unsafe { std::mem::zeroed() };
             let code = unsafe {
-                sys::dec_bindings::svt_av1_dec_init_handle(
-                    handle.as_mut_ptr(),
-                    std::ptr::null_mut(),
-                    &mut cfg,
-                )
+                ffi::svt_av1_dec_init_handle(handle.as_mut_ptr(), std::ptr::null_mut(), &mut cfg)
             };
             super::ok(code)?;
-            Ok((Self { handle }, cfg))
+            let decoder = Self {
+                handle,
+                max_width: cfg.max_picture_width,
+                max_height: cfg.max_picture_height,
+                max_bit_depth: cfg.max_bit_depth,
+                sequence_header: Vec::new(),
+                output: None,
+                _state: PhantomData,
+            };
+            Ok((decoder, cfg))
         }
 
         pub fn set_parameter(&mut self, cfg: &Configuration) -> Result<()> {
             let code = unsafe {
-                sys::dec_bindings::svt_av1_dec_set_parameter(
-                    self.handle.as_ptr(),
-                    cfg as *const _ as *mut _,
-                )
+                ffi::svt_av1_dec_set_parameter(self.handle.as_ptr(), cfg as *const _ as *mut _)
             };
-            super::ok(code)
+            super::ok(code)?;
+            self.max_width = cfg.max_picture_width;
+            self.max_height = cfg.max_picture_height;
+            self.max_bit_depth = cfg.max_bit_depth;
+            Ok(())
         }
 
-        pub fn init(&mut self) -> Result<()> {
-            let code = unsafe { sys::dec_bindings::svt_av1_dec_init(self.handle.as_ptr()) };
-            super::ok(code)
+        /// Starts the decoder. On failure the handle is released without
+        /// `svt_av1_dec_deinit`, which must only follow a successful init.
+        pub fn init(mut self) -> Result<Decoder<Running>> {
+            let code = unsafe { ffi::svt_av1_dec_init(self.handle.as_ptr()) };
+            super::ok(code)?;
+            self.handle.initialized = true;
+            Ok(Decoder {
+                handle: self.handle,
+                max_width: self.max_width,
+                max_height: self.max_height,
+                max_bit_depth: self.max_bit_depth,
+                sequence_header: self.sequence_header,
+                output: None,
+                _state: PhantomData,
+            })
         }
+    }
 
+    impl Decoder<Running> {
         pub fn send_packet(&mut self, data: &[u8]) -> Result<()> {
             let code = unsafe {
-                sys::dec_bindings::svt_av1_dec_frame(
-                    self.handle.as_ptr(),
-                    data.as_ptr(),
-                    data.len(),
-                    0,
-                )
+                ffi::svt_av1_dec_frame(self.handle.as_ptr(), data.as_ptr(), data.len(), 0)
             };
             super::ok(code)
         }
@@ -540,7 +1697,7 @@ pub mod decoder {
             frame_info: &mut FrameInfo,
         ) -> Result<()> {
             let code = unsafe {
-                sys::dec_bindings::svt_av1_dec_get_picture(
+                ffi::svt_av1_dec_get_picture(
                     self.handle.as_ptr(),
                     picture as *mut _,
                     stream_info as *mut _,
@@ -549,15 +1706,198 @@ pub mod decoder {
             };
             super::ok(code)
         }
+
+        /// Feeds one temporal unit and returns the pictures it made available.
+        ///
+        /// Output buffers are allocated by the decoder from the configured
+        /// maximum picture size, raised to the maximum frame size and bit
+        /// depth of any sequence header in `data`.
+        pub fn decode<'a>(&'a mut self, data: &[u8]) -> DecodedFrames<'a> {
+            self.read_sequence_header(data);
+            let error = self.send_packet(data).err();
+            DecodedFrames {
+                dec: self,
+                error,
+                done: false,
+            }
+        }
+
+        /// Raises the maximum picture size and bit depth to those of the first
+        /// sequence header OBU in `data`. Streams repeat the header at every
+        /// key frame, so it is only parsed when its bytes change.
+        fn read_sequence_header(&mut self, data: &[u8]) {
+            use crate::obu::{ObuReader, ObuType, SequenceHeader};
+
+            let Some(obu) = ObuReader::new(data)
+                .map_while(|obu| obu.ok())
+                .find(|obu| obu.obu_type() == ObuType::SequenceHeader)
+            else {
+                return;
+            };
+            if obu.data == self.sequence_header.as_slice() {
+                return;
+            }
+            if let Ok(seq) = SequenceHeader::parse_payload(obu.payload) {
+                self.max_width = self.max_width.max(seq.max_frame_width);
+                self.max_height = self.max_height.max(seq.max_frame_height);
+                self.max_bit_depth = self.max_bit_depth.max(seq.bit_depth().bits());
+                self.sequence_header = obu.data.to_vec();
+            }
+        }
+
+        /// Pulls one decoded picture; `Ok(None)` once the output queue is empty.
+        ///
+        /// The library writes into buffers sized before the call, so the
+        /// maximum picture size must be known: configured through
+        /// `max_picture_width`/`max_picture_height`, or taken from a sequence
+        /// header passed to [`decode`](Self::decode). Otherwise this returns
+        /// `Error::InvalidArgument`.
+        pub fn next_frame(&mut self) -> Result<Option<DecodedFrame>> {
+            const EB_NO_ERROR_EMPTY_QUEUE: i32 =
+                sys::dec_bindings::EbErrorType_EB_NoErrorEmptyQueue;
+
+            if self.max_width == 0 || self.max_height == 0 {
+                return Err(Error::InvalidArgument(
+                    "maximum picture size unknown: configure max_picture_width and \
+                     max_picture_height or decode a sequence header first"
+                        .into(),
+                ));
+            }
+            let bytes_per_sample = if self.max_bit_depth > 8 { 2 } else { 1 };
+            let fits = self.output.as_ref().is_some_and(|o| {
+                o.width >= self.max_width
+                    && o.height >= self.max_height
+                    && o.bytes_per_sample >= bytes_per_sample
+            });
+            if !fits {
+                self.output = Some(OutputBuffers::new(
+                    self.max_width,
+                    self.max_height,
+                    bytes_per_sample,
+                ));
+            }
+            let output = self.output.as_mut().unwrap();
+            let format = output.prepare();
+
+            let mut picture: BufferHeader = unsafe { std::mem::zeroed() };
+            picture.size = std::mem::size_of::<BufferHeader>() as u32;
+            picture.p_buffer = format as *mut u8;
+            picture.n_alloc_len = (output.planes[0].len() * 3) as u32;
+            let mut stream_info: StreamInfo = unsafe { std::mem::zeroed() };
+            let mut frame_info: FrameInfo = unsafe { std::mem::zeroed() };
+            let code = unsafe {
+                ffi::svt_av1_dec_get_picture(
+                    self.handle.as_ptr(),
+                    &mut picture,
+                    &mut stream_info,
+                    &mut frame_info,
+                )
+            };
+            if code == EB_NO_ERROR_EMPTY_QUEUE {
+                return Ok(None);
+            }
+            super::ok(code)?;
+
+            // Size later output buffers from what the sequence header signals.
+            let color = &stream_info.color_config;
+            self.max_width = self.max_width.max(stream_info.max_picture_width as u32);
+            self.max_height = self.max_height.max(stream_info.max_picture_height as u32);
+            self.max_bit_depth = self.max_bit_depth.max(color.bit_depth);
+
+            let output = self.output.as_ref().unwrap();
+            let io = &output.format;
+            let width = if io.width > 0 {
+                io.width
+            } else {
+                stream_info.max_picture_width as u32
+            };
+            let height = if io.height > 0 {
+                io.height
+            } else {
+                stream_info.max_picture_height as u32
+            };
+            let bit_depth = match io.bit_depth {
+                0 => color.bit_depth,
+                b => b,
+            };
+            let bit_depth = match bit_depth {
+                8 => BitDepth::Eight,
+                10 => BitDepth::Ten,
+                12 => BitDepth::Twelve,
+                b => {
+                    return Err(Error::InvalidData(format!(
+                        "unsupported output bit depth {b}"
+                    )))
+                }
+            };
+            let color_format = if color.mono_chrome != 0 {
+                ColorFormat::Yuv400
+            } else {
+                match (color.subsampling_x != 0, color.subsampling_y != 0) {
+                    (true, true) => ColorFormat::Yuv420,
+                    (true, false) => ColorFormat::Yuv422,
+                    _ => ColorFormat::Yuv444,
+                }
+            };
+            if width > output.width || height > output.height {
+                return Err(Error::InvalidData(format!(
+                    "decoded picture {width}x{height} exceeds output buffers"
+                )));
+            }
+
+            let mut frame = Frame::new(width, height, bit_depth, color_format);
+            frame.pts = picture.pts;
+            let bps = frame.bytes_per_sample();
+            let strides = [io.y_stride, io.cb_stride, io.cr_stride];
+            for (p, stride) in strides.into_iter().enumerate().take(frame.num_planes()) {
+                let stride = stride as usize * bps;
+                for y in 0..frame.plane_height(p) as usize {
+                    let row = frame.row_mut(p, y);
+                    let start = y * stride;
+                    row.copy_from_slice(&output.planes[p][start..start + row.len()]);
+                }
+            }
+            Ok(Some(DecodedFrame { frame }))
+        }
     }
 
-    impl Drop for Decoder {
-        fn drop(&mut self) {
-            // svt_av1_dec_deinit caused double free or crash, relying on Handle::drop
+    /// Iterator returned by [`Decoder::decode`].
+    pub struct DecodedFrames<'a> {
+        dec: &'a mut Decoder<Running>,
+        error: Option<Error>,
+        done: bool,
+    }
+
+    impl<'a> Iterator for DecodedFrames<'a> {
+        type Item = Result<DecodedFrame>;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if self.done {
+                return None;
+            }
+            if let Some(e) = self.error.take() {
+                self.done = true;
+                return Some(Err(e));
+            }
+            match self.dec.next_frame() {
+                Ok(Some(frame)) => Some(Ok(frame)),
+                Ok(None) => {
+                    self.done = true;
+                    None
+                }
+                Err(e) => {
+                    self.done = true;
+                    Some(Err(e))
+                }
+            }
         }
     }
 }
 
+/// Fakes of the library entry points for tests, selected per handle.
+#[cfg(test)]
+mod mock;
+
 #[cfg(test)]
 mod tests;
 /*
@@ -0,0 +1,517 @@
+// In-process stand-ins for the SVT-AV1 entry points, for tests that need
+// library behaviour the real library cannot be made to show on demand.
+//
+// Test builds call the library through these modules. A test arms a mock
+// on its own thread; the next handle created on that thread is a fake
+// served by the mock, and every call on it is answered here. Handles
+// created without an armed mock go to the real library.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Calls per entry point, by function name.
+#[derive(Debug, Default)]
+pub(crate) struct Calls(Mutex<HashMap<&'static str, usize>>);
+
+impl Calls {
+    fn record(&self, name: &'static str) {
+        *self.0.lock().unwrap().entry(name).or_default() += 1;
+    }
+
+    pub(crate) fn get(&self, name: &str) -> usize {
+        self.0.lock().unwrap().get(name).copied().unwrap_or(0)
+    }
+}
+
+/// Fake handles by address.
+struct Registry<T> {
+    live: Mutex<Vec<(usize, Arc<T>)>>,
+}
+
+impl<T> Registry<T> {
+    const fn new() -> Self {
+        Self {
+            live: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn get<H>(&self, handle: *mut H) -> Option<Arc<T>> {
+        let live = self.live.lock().unwrap();
+        live.iter()
+            .find(|(at, _)| *at == handle as usize)
+            .map(|(_, mock)| Arc::clone(mock))
+    }
+
+    /// Allocates a fake component for `mock` and returns its address.
+    fn insert<H>(&self, mock: Arc<T>) -> *mut H {
+        // SAFETY: the component types are plain C structs of integers and
+        // pointers, for which all-zero is a valid value.
+        let handle = Box::into_raw(Box::new(unsafe { std::mem::zeroed::<H>() }));
+        self.live.lock().unwrap().push((handle as usize, mock));
+        handle
+    }
+
+    /// Fake handles currently served by `mock`.
+    #[cfg(feature = "decoder")]
+    fn count(&self, mock: &Arc<T>) -> usize {
+        let live = self.live.lock().unwrap();
+        live.iter().filter(|(_, m)| Arc::ptr_eq(m, mock)).count()
+    }
+
+    fn remove<H>(&self, handle: *mut H) {
+        self.live
+            .lock()
+            .unwrap()
+            .retain(|(at, _)| *at != handle as usize);
+        // SAFETY: `handle` came from `insert` and is removed only once.
+        drop(unsafe { Box::from_raw(handle) });
+    }
+}
+
+#[cfg(feature = "encoder")]
+pub(crate) mod enc {
+    use super::*;
+    use crate::sys;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::os::raw::c_void;
+    use std::sync::{Condvar, MutexGuard};
+
+    pub(crate) use sys::enc_bindings::*;
+
+    /// Encoder answering every picture with one packet, in input order.
+    #[derive(Debug, Default)]
+    pub(crate) struct MockEncoder {
+        /// Returned by `svt_av1_enc_init`.
+        pub init_result: EbErrorType,
+        /// Returned by `svt_av1_enc_send_picture` for the EOS buffer, which
+        /// is dropped unless this is 0.
+        pub eos_result: EbErrorType,
+        /// Input buffers; `send_picture` blocks while this many pictures
+        /// have packets not yet released. Unbounded when `None`.
+        pub pool: Option<usize>,
+        pub calls: Calls,
+        state: Mutex<EncoderState>,
+        released: Condvar,
+    }
+
+    #[derive(Debug, Default)]
+    struct EncoderState {
+        /// Pictures whose packet has not been released.
+        held: usize,
+        /// Pts and picture type of the packets ready to be taken.
+        ready: VecDeque<(i64, EbAv1PictureType)>,
+        eos_sent: bool,
+        eos_taken: bool,
+    }
+
+    impl MockEncoder {
+        pub(crate) fn new() -> Self {
+            Self::default()
+        }
+
+        /// Serves the next encoder handle created on this thread.
+        pub(crate) fn arm(self) -> Arc<Self> {
+            let mock = Arc::new(self);
+            NEXT.with(|next| *next.borrow_mut() = Some(Arc::clone(&mock)));
+            mock
+        }
+
+        fn lock(&self) -> MutexGuard<'_, EncoderState> {
+            self.state.lock().unwrap()
+        }
+
+        fn send_picture(&self, pic: &EbBufferHeaderType) -> EbErrorType {
+            let mut state = self.lock();
+            if pic.flags & EB_BUFFERFLAG_EOS != 0 {
+                state.eos_sent = self.eos_result == 0;
+                return self.eos_result;
+            }
+            while self.pool.is_some_and(|pool| state.held >= pool) {
+                state = self.released.wait(state).unwrap();
+            }
+            state.held += 1;
+            state.ready.push_back((pic.pts, pic.pic_type));
+            0
+        }
+
+        /// Builds the next packet, with the EOS packet after the last one.
+        fn get_packet(&self) -> Option<EbBufferHeaderType> {
+            let mut state = self.lock();
+            // SAFETY: the header is a plain C struct; all-zero is valid.
+            let mut packet: EbBufferHeaderType = unsafe { std::mem::zeroed() };
+            packet.size = std::mem::size_of::<EbBufferHeaderType>() as u32;
+            if let Some((pts, pic_type)) = state.ready.pop_front() {
+                // A temporal delimiter stands in for the coded picture.
+                let data: Box<[u8]> = Box::new([0x12, 0x00]);
+                packet.n_filled_len = data.len() as u32;
+                packet.n_alloc_len = data.len() as u32;
+                packet.p_buffer = Box::into_raw(data) as *mut u8;
+                packet.pts = pts;
+                packet.dts = pts;
+                packet.pic_type = pic_type;
+            } else if state.eos_sent && !state.eos_taken {
+                state.eos_taken = true;
+                packet.flags = EB_BUFFERFLAG_EOS;
+            } else {
+                return None;
+            }
+            Some(packet)
+        }
+
+        fn release(&self, packet: &EbBufferHeaderType) {
+            if packet.flags & EB_BUFFERFLAG_EOS == 0 {
+                self.lock().held -= 1;
+                self.released.notify_all();
+            }
+            if !packet.p_buffer.is_null() {
+                // SAFETY: allocated by `get_packet` with this length.
+                drop(unsafe {
+                    Box::from_raw(std::ptr::slice_from_raw_parts_mut(
+                        packet.p_buffer,
+                        packet.n_alloc_len as usize,
+                    ))
+                });
+            }
+        }
+    }
+
+    thread_local! {
+        static NEXT: RefCell<Option<Arc<MockEncoder>>> = const { RefCell::new(None) };
+    }
+
+    static LIVE: Registry<MockEncoder> = Registry::new();
+    /// Packets handed out and not yet released, by the encoder they came from.
+    static PACKETS: Registry<MockEncoder> = Registry::new();
+
+    pub(crate) unsafe fn svt_av1_enc_init_handle(
+        handle: *mut *mut EbComponentType,
+        app_data: *mut c_void,
+        cfg: *mut EbSvtAv1EncConfiguration,
+    ) -> EbErrorType {
+        let Some(mock) = NEXT.with(|next| next.borrow_mut().take()) else {
+            return unsafe { sys::enc_bindings::svt_av1_enc_init_handle(handle, app_data, cfg) };
+        };
+        mock.calls.record("init_handle");
+        unsafe {
+            *cfg = std::mem::zeroed();
+            *handle = LIVE.insert(mock);
+        }
+        0
+    }
+
+    pub(crate) unsafe fn svt_av1_enc_set_parameter(
+        handle: *mut EbComponentType,
+        cfg: *mut EbSvtAv1EncConfiguration,
+    ) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::enc_bindings::svt_av1_enc_set_parameter(handle, cfg) };
+        };
+        mock.calls.record("set_parameter");
+        0
+    }
+
+    pub(crate) unsafe fn svt_av1_enc_init(handle: *mut EbComponentType) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::enc_bindings::svt_av1_enc_init(handle) };
+        };
+        mock.calls.record("init");
+        mock.init_result
+    }
+
+    pub(crate) unsafe fn svt_av1_enc_send_picture(
+        handle: *mut EbComponentType,
+        pic: *mut EbBufferHeaderType,
+    ) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::enc_bindings::svt_av1_enc_send_picture(handle, pic) };
+        };
+        mock.calls.record("send_picture");
+        // SAFETY: the caller passes a valid header.
+        mock.send_picture(unsafe { &*pic })
+    }
+
+    pub(crate) unsafe fn svt_av1_enc_get_packet(
+        handle: *mut EbComponentType,
+        packet: *mut *mut EbBufferHeaderType,
+        pic_send_done: u8,
+    ) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe {
+                sys::enc_bindings::svt_av1_enc_get_packet(handle, packet, pic_send_done)
+            };
+        };
+        mock.calls.record("get_packet");
+        let Some(header) = mock.get_packet() else {
+            return EbErrorType_EB_NoErrorEmptyQueue;
+        };
+        let ptr = PACKETS.insert::<EbBufferHeaderType>(mock);
+        unsafe {
+            *ptr = header;
+            *packet = ptr;
+        }
+        0
+    }
+
+    pub(crate) unsafe fn svt_av1_enc_release_out_buffer(packet: *mut *mut EbBufferHeaderType) {
+        let ptr = unsafe { *packet };
+        let Some(mock) = PACKETS.get(ptr) else {
+            return unsafe { sys::enc_bindings::svt_av1_enc_release_out_buffer(packet) };
+        };
+        mock.calls.record("release_out_buffer");
+        // SAFETY: `ptr` is a live packet from `svt_av1_enc_get_packet`.
+        mock.release(unsafe { &*ptr });
+        PACKETS.remove(ptr);
+        unsafe { *packet = std::ptr::null_mut() };
+    }
+
+    pub(crate) unsafe fn svt_av1_get_recon(
+        handle: *mut EbComponentType,
+        recon: *mut EbBufferHeaderType,
+    ) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::enc_bindings::svt_av1_get_recon(handle, recon) };
+        };
+        mock.calls.record("get_recon");
+        EbErrorType_EB_NoErrorEmptyQueue
+    }
+
+    pub(crate) unsafe fn svt_av1_enc_deinit(handle: *mut EbComponentType) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::enc_bindings::svt_av1_enc_deinit(handle) };
+        };
+        mock.calls.record("deinit");
+        0
+    }
+
+    pub(crate) unsafe fn svt_av1_enc_deinit_handle(handle: *mut EbComponentType) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::enc_bindings::svt_av1_enc_deinit_handle(handle) };
+        };
+        mock.calls.record("deinit_handle");
+        LIVE.remove(handle);
+        0
+    }
+}
+
+#[cfg(feature = "decoder")]
+pub(crate) mod dec {
+    use super::*;
+    use crate::sys;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::os::raw::c_void;
+
+    pub(crate) use sys::dec_bindings::*;
+
+    /// Picture the mock decoder outputs for every `svt_av1_dec_frame` call.
+    #[derive(Clone, Copy, Debug)]
+    pub(crate) struct MockPicture {
+        pub width: u32,
+        pub height: u32,
+        pub bit_depth: u32,
+        pub color_format: crate::config::ColorFormat,
+    }
+
+    impl MockPicture {
+        /// Sample the mock writes at (`x`, `y`) of `plane` for picture `n`.
+        pub(crate) fn sample(&self, n: i64, plane: usize, x: usize, y: usize) -> u16 {
+            let max = (1u32 << self.bit_depth) - 1;
+            ((n as u32 * 7 + plane as u32 * 61 + x as u32 * 3 + y as u32 * 5) & max) as u16
+        }
+    }
+
+    /// Written past the picture width, where the copy must not read.
+    const PADDING: u8 = 0xee;
+
+    #[derive(Debug)]
+    pub(crate) struct MockDecoder {
+        pub picture: MockPicture,
+        /// Returned by `svt_av1_dec_init`.
+        pub init_result: EbErrorType,
+        pub calls: Calls,
+        queued: Mutex<VecDeque<i64>>,
+    }
+
+    impl MockDecoder {
+        pub(crate) fn new(picture: MockPicture) -> Self {
+            Self {
+                picture,
+                init_result: 0,
+                calls: Calls::default(),
+                queued: Mutex::new(VecDeque::new()),
+            }
+        }
+
+        /// Serves the next decoder handle created on this thread.
+        pub(crate) fn arm(self) -> Arc<Self> {
+            let mock = Arc::new(self);
+            Self::rearm(&mock);
+            mock
+        }
+
+        /// Serves the next decoder handle on this thread with `mock` again.
+        pub(crate) fn rearm(mock: &Arc<Self>) {
+            NEXT.with(|next| *next.borrow_mut() = Some(Arc::clone(mock)));
+        }
+
+        /// Handles served by `mock` that have not been deinitialized.
+        pub(crate) fn live(mock: &Arc<Self>) -> usize {
+            LIVE.count(mock)
+        }
+
+        /// Writes the next queued picture at the strides in `io`, checking
+        /// that every plane fits the `plane_len` bytes behind its pointer.
+        fn get_picture(
+            &self,
+            io: &mut EbSvtIOFormat,
+            plane_len: usize,
+            info: &mut EbAV1StreamInfo,
+        ) -> Option<i64> {
+            let n = self.queued.lock().unwrap().pop_front()?;
+            let pic = self.picture;
+            let bps = if pic.bit_depth > 8 { 2 } else { 1 };
+            let (ss_x, ss_y) = pic.color_format.subsampling();
+            let planes = pic.color_format.num_planes();
+            let targets = [
+                (io.luma, io.y_stride),
+                (io.cb, io.cb_stride),
+                (io.cr, io.cr_stride),
+            ];
+            for (p, (ptr, stride)) in targets.into_iter().enumerate().take(planes) {
+                let (w, h) = if p == 0 {
+                    (pic.width, pic.height)
+                } else {
+                    ((pic.width + ss_x) >> ss_x, (pic.height + ss_y) >> ss_y)
+                };
+                let (w, h, stride) = (w as usize, h as usize, stride as usize);
+                assert!(
+                    stride >= w && stride * h * bps <= plane_len,
+                    "{w}x{h} plane {p} with stride {stride} overruns {plane_len} bytes"
+                );
+                // SAFETY: checked above against the allocation size.
+                let rows = unsafe { std::slice::from_raw_parts_mut(ptr, stride * h * bps) };
+                for (y, row) in rows.chunks_exact_mut(stride * bps).enumerate() {
+                    row.fill(PADDING);
+                    for x in 0..w {
+                        let v = pic.sample(n, p, x, y);
+                        if bps == 1 {
+                            row[x] = v as u8;
+                        } else {
+                            row[2 * x..2 * x + 2].copy_from_slice(&v.to_le_bytes());
+                        }
+                    }
+                }
+            }
+            io.width = pic.width;
+            io.height = pic.height;
+            io.bit_depth = pic.bit_depth;
+            io.color_fmt = pic.color_format as u32;
+            info.max_picture_width = pic.width as u16;
+            info.max_picture_height = pic.height as u16;
+            info.color_config.bit_depth = pic.bit_depth;
+            info.color_config.mono_chrome = (planes == 1) as u8;
+            info.color_config.subsampling_x = ss_x as u8;
+            info.color_config.subsampling_y = ss_y as u8;
+            Some(n)
+        }
+    }
+
+    thread_local! {
+        static NEXT: RefCell<Option<Arc<MockDecoder>>> = const { RefCell::new(None) };
+    }
+
+    static LIVE: Registry<MockDecoder> = Registry::new();
+
+    pub(crate) unsafe fn svt_av1_dec_init_handle(
+        handle: *mut *mut EbComponentType,
+        app_data: *mut c_void,
+        cfg: *mut EbSvtAv1DecConfiguration,
+    ) -> EbErrorType {
+        let Some(mock) = NEXT.with(|next| next.borrow_mut().take()) else {
+            return unsafe { sys::dec_bindings::svt_av1_dec_init_handle(handle, app_data, cfg) };
+        };
+        mock.calls.record("init_handle");
+        unsafe {
+            *cfg = std::mem::zeroed();
+            *handle = LIVE.insert(mock);
+        }
+        0
+    }
+
+    pub(crate) unsafe fn svt_av1_dec_set_parameter(
+        handle: *mut EbComponentType,
+        cfg: *mut EbSvtAv1DecConfiguration,
+    ) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::dec_bindings::svt_av1_dec_set_parameter(handle, cfg) };
+        };
+        mock.calls.record("set_parameter");
+        0
+    }
+
+    pub(crate) unsafe fn svt_av1_dec_init(handle: *mut EbComponentType) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::dec_bindings::svt_av1_dec_init(handle) };
+        };
+        mock.calls.record("init");
+        mock.init_result
+    }
+
+    pub(crate) unsafe fn svt_av1_dec_frame(
+        handle: *mut EbComponentType,
+        data: *const u8,
+        len: usize,
+        is_annexb: u32,
+    ) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::dec_bindings::svt_av1_dec_frame(handle, data, len, is_annexb) };
+        };
+        let n = mock.calls.get("frame") as i64;
+        mock.calls.record("frame");
+        mock.queued.lock().unwrap().push_back(n);
+        0
+    }
+
+    pub(crate) unsafe fn svt_av1_dec_get_picture(
+        handle: *mut EbComponentType,
+        picture: *mut EbBufferHeaderType,
+        stream_info: *mut EbAV1StreamInfo,
+        frame_info: *mut EbAV1FrameInfo,
+    ) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe {
+                sys::dec_bindings::svt_av1_dec_get_picture(handle, picture, stream_info, frame_info)
+            };
+        };
+        mock.calls.record("get_picture");
+        // SAFETY: the caller passes valid headers, with `p_buffer` pointing
+        // at the picture description as the library expects.
+        let (picture, info) = unsafe { (&mut *picture, &mut *stream_info) };
+        let io = unsafe { &mut *(picture.p_buffer as *mut EbSvtIOFormat) };
+        match mock.get_picture(io, picture.n_alloc_len as usize / 3, info) {
+            Some(n) => {
+                picture.pts = n;
+                0
+            }
+            None => EbErrorType_EB_NoErrorEmptyQueue,
+        }
+    }
+
+    pub(crate) unsafe fn svt_av1_dec_deinit(handle: *mut EbComponentType) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::dec_bindings::svt_av1_dec_deinit(handle) };
+        };
+        mock.calls.record("deinit");
+        0
+    }
+
+    pub(crate) unsafe fn svt_av1_dec_deinit_handle(handle: *mut EbComponentType) -> EbErrorType {
+        let Some(mock) = LIVE.get(handle) else {
+            return unsafe { sys::dec_bindings::svt_av1_dec_deinit_handle(handle) };
+        };
+        mock.calls.record("deinit_handle");
+        LIVE.remove(handle);
+        0
+    }
+}
@@ -0,0 +1,245 @@
+pub mod sequence_header;
+
+pub use sequence_header::SequenceHeader;
+
+use crate::{Error, Result};
+
+/// `obu_type` values from the AV1 specification (section 6.2.2).
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ObuType {
+    SequenceHeader,
+    TemporalDelimiter,
+    FrameHeader,
+    TileGroup,
+    Metadata,
+    Frame,
+    RedundantFrameHeader,
+    TileList,
+    Padding,
+    /// Reserved values (0, 9-14) are kept so streams can still be walked.
+    Reserved(u8),
+}
+
+impl ObuType {
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => ObuType::SequenceHeader,
+            2 => ObuType::TemporalDelimiter,
+            3 => ObuType::FrameHeader,
+            4 => ObuType::TileGroup,
+            5 => ObuType::Metadata,
+            6 => ObuType::Frame,
+            7 => ObuType::RedundantFrameHeader,
+            8 => ObuType::TileList,
+            15 => ObuType::Padding,
+            v => ObuType::Reserved(v),
+        }
+    }
+
+    pub fn as_u8(self) -> u8 {
+        match self {
+            ObuType::SequenceHeader => 1,
+            ObuType::TemporalDelimiter => 2,
+            ObuType::FrameHeader => 3,
+            ObuType::TileGroup => 4,
+            ObuType::Metadata => 5,
+            ObuType::Frame => 6,
+            ObuType::RedundantFrameHeader => 7,
+            ObuType::TileList => 8,
+            ObuType::Padding => 15,
+            ObuType::Reserved(v) => v,
+        }
+    }
+}
+
+/// `metadata_type` values carried at the start of a metadata OBU payload.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MetadataType {
+    HdrCll,
+    HdrMdcv,
+    Scalability,
+    ItutT35,
+    Timecode,
+    Other(u64),
+}
+
+impl MetadataType {
+    pub fn from_u64(value: u64) -> Self {
+        match value {
+            1 => MetadataType::HdrCll,
+            2 => MetadataType::HdrMdcv,
+            3 => MetadataType::Scalability,
+            4 => MetadataType::ItutT35,
+            5 => MetadataType::Timecode,
+            v => MetadataType::Other(v),
+        }
+    }
+}
+
+/// Optional `obu_extension_header` fields.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ObuExtension {
+    pub temporal_id: u8,
+    pub spatial_id: u8,
+}
+
+/// Decoded `obu_header()`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ObuHeader {
+    pub obu_type: ObuType,
+    pub extension: Option<ObuExtension>,
+    pub has_size_field: bool,
+}
+
+impl ObuHeader {
+    /// Parses the one- or two-byte OBU header, returning it with its length.
+    pub fn parse(data: &[u8]) -> Result<(Self, usize)> {
+        let &byte = data
+            .first()
+            .ok_or_else(|| Error::InvalidData("truncated OBU header".into()))?;
+        if byte & 0x80 != 0 {
+            return Err(Error::InvalidData("OBU forbidden bit is set".into()));
+        }
+        let has_extension = byte & 0x04 != 0;
+        let extension = if has_extension {
+            let &ext = data
+                .get(1)
+                .ok_or_else(|| Error::InvalidData("truncated OBU extension header".into()))?;
+            Some(ObuExtension {
+                temporal_id: ext >> 5,
+                spatial_id: (ext >> 3) & 0x3,
+            })
+        } else {
+            None
+        };
+        let header = ObuHeader {
+            obu_type: ObuType::from_u8((byte >> 3) & 0xf),
+            extension,
+            has_size_field: byte & 0x02 != 0,
+        };
+        Ok((header, 1 + has_extension as usize))
+    }
+
+    /// Serializes the header (without the size field).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![
+            (self.obu_type.as_u8() << 3)
+                | ((self.extension.is_some() as u8) << 2)
+                | ((self.has_size_field as u8) << 1),
+        ];
+        if let Some(ext) = self.extension {
+            out.push((ext.temporal_id << 5) | ((ext.spatial_id & 0x3) << 3));
+        }
+        out
+    }
+}
+
+/// One OBU borrowed from a bitstream buffer.
+#[derive(Copy, Clone, Debug)]
+pub struct Obu<'a> {
+    pub header: ObuHeader,
+    /// Byte offset of the OBU within the buffer passed to the reader.
+    pub offset: usize,
+    /// Header, size field and payload.
+    pub data: &'a [u8],
+    pub payload: &'a [u8],
+}
+
+impl<'a> Obu<'a> {
+    pub fn obu_type(&self) -> ObuType {
+        self.header.obu_type
+    }
+
+    /// The `metadata_type` of a metadata OBU, `None` for other OBU types.
+    pub fn metadata_type(&self) -> Result<Option<MetadataType>> {
+        if self.obu_type() != ObuType::Metadata {
+            return Ok(None);
+        }
+        let (value, _) = read_leb128(self.payload)?;
+        Ok(Some(MetadataType::from_u64(value)))
+    }
+}
+
+/// Reads an unsigned LEB128 value, returning it with the number of bytes consumed.
+pub fn read_leb128(data: &[u8]) -> Result<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, byte) in data.iter().take(8).enumerate() {
+        value |= u64::from(byte & 0x7f) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Ok((value, i + 1));
+        }
+    }
+    Err(Error::InvalidData("truncated leb128 value".into()))
+}
+
+/// Appends `value` as unsigned LEB128 using the minimal number of bytes.
+pub fn write_leb128(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+/// Iterator over the OBUs of a low-overhead (Section 5) bitstream, such as the
+/// data of an encoder packet. An OBU without a size field extends to the end
+/// of the buffer.
+pub struct ObuReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ObuReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn read_obu(&mut self) -> Result<Obu<'a>> {
+        let rest = &self.data[self.pos..];
+        let (header, header_len) = ObuHeader::parse(rest)?;
+        let (start, end) = if header.has_size_field {
+            let (size, leb_len) = read_leb128(&rest[header_len..])?;
+            let start = header_len + leb_len;
+            let end = usize::try_from(size)
+                .ok()
+                .and_then(|size| start.checked_add(size))
+                .filter(|&end| end <= rest.len())
+                .ok_or_else(|| Error::InvalidData("OBU size exceeds buffer".into()))?;
+            (start, end)
+        } else {
+            (header_len, rest.len())
+        };
+        let obu = Obu {
+            header,
+            offset: self.pos,
+            data: &rest[..end],
+            payload: &rest[start..end],
+        };
+        self.pos += end;
+        Ok(obu)
+    }
+}
+
+impl<'a> Iterator for ObuReader<'a> {
+    type Item = Result<Obu<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+        let item = self.read_obu();
+        if item.is_err() {
+            self.pos = self.data.len();
+        }
+        Some(item)
+    }
+}
+
+/// Splits `data` into OBUs, failing on the first malformed one.
+pub fn parse_obus(data: &[u8]) -> Result<Vec<Obu<'_>>> {
+    ObuReader::new(data).collect()
+}
@@ -0,0 +1,385 @@
+use super::{parse_obus, ObuType};
+use crate::config::{BitDepth, ChromaSamplePosition, ColorFormat, ColorRange, Profile, Tier};
+use crate::{Error, Result};
+
+/// MSB-first bit reader for OBU payloads.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// `f(n)`: reads `n` (at most 32) bits.
+    pub fn f(&mut self, n: u32) -> Result<u32> {
+        let mut v = 0u32;
+        for _ in 0..n {
+            let byte = *self
+                .data
+                .get(self.pos / 8)
+                .ok_or_else(|| Error::InvalidData("truncated OBU payload".into()))?;
+            v = (v << 1) | ((byte >> (7 - self.pos % 8)) & 1) as u32;
+            self.pos += 1;
+        }
+        Ok(v)
+    }
+
+    pub fn flag(&mut self) -> Result<bool> {
+        Ok(self.f(1)? == 1)
+    }
+
+    pub fn uvlc(&mut self) -> Result<u32> {
+        let mut leading_zeros = 0;
+        while !self.flag()? {
+            leading_zeros += 1;
+            if leading_zeros >= 32 {
+                return Ok(u32::MAX);
+            }
+        }
+        Ok(self.f(leading_zeros)? + (1u32 << leading_zeros) - 1)
+    }
+}
+
+/// `timing_info()` fields.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TimingInfo {
+    pub num_units_in_display_tick: u32,
+    pub time_scale: u32,
+    /// `num_ticks_per_picture_minus_1 + 1` when `equal_picture_interval` is set.
+    pub num_ticks_per_picture: Option<u32>,
+}
+
+/// `decoder_model_info()` fields.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DecoderModelInfo {
+    pub buffer_delay_length: u8,
+    pub num_units_in_decoding_tick: u32,
+    pub buffer_removal_time_length: u8,
+    pub frame_presentation_time_length: u8,
+}
+
+/// One entry of the operating point loop.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct OperatingPoint {
+    pub idc: u16,
+    /// `seq_level_idx`; 31 means no level constraints.
+    pub seq_level_idx: u8,
+    pub tier: Tier,
+    pub initial_display_delay: Option<u8>,
+}
+
+/// Coding tools switched on for the whole sequence.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SequenceTools {
+    pub use_128x128_superblock: bool,
+    pub enable_filter_intra: bool,
+    pub enable_intra_edge_filter: bool,
+    pub enable_interintra_compound: bool,
+    pub enable_masked_compound: bool,
+    pub enable_warped_motion: bool,
+    pub enable_dual_filter: bool,
+    pub enable_order_hint: bool,
+    pub enable_jnt_comp: bool,
+    pub enable_ref_frame_mvs: bool,
+    /// 0 = off, 1 = on, 2 = chosen per frame.
+    pub seq_force_screen_content_tools: u8,
+    /// 0 = off, 1 = on, 2 = chosen per frame.
+    pub seq_force_integer_mv: u8,
+    pub order_hint_bits: u8,
+    pub enable_superres: bool,
+    pub enable_cdef: bool,
+    pub enable_restoration: bool,
+}
+
+/// `color_config()` fields.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ColorConfig {
+    pub bit_depth: BitDepth,
+    pub color_format: ColorFormat,
+    pub color_primaries: u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coefficients: u8,
+    pub color_range: ColorRange,
+    pub subsampling_x: bool,
+    pub subsampling_y: bool,
+    pub chroma_sample_position: ChromaSamplePosition,
+    pub separate_uv_delta_q: bool,
+}
+
+/// A decoded sequence header OBU (AV1 spec section 5.5).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SequenceHeader {
+    /// The complete OBU (header, size field and payload) as found in the stream.
+    pub bytes: Vec<u8>,
+    pub profile: Profile,
+    pub still_picture: bool,
+    pub reduced_still_picture_header: bool,
+    pub timing_info: Option<TimingInfo>,
+    pub decoder_model_info: Option<DecoderModelInfo>,
+    pub operating_points: Vec<OperatingPoint>,
+    pub max_frame_width: u32,
+    pub max_frame_height: u32,
+    pub frame_id_numbers_present: bool,
+    pub tools: SequenceTools,
+    pub color: ColorConfig,
+    pub film_grain_params_present: bool,
+}
+
+impl SequenceHeader {
+    /// Finds and decodes the first sequence header OBU in `data`, e.g. the
+    /// bytes returned by the encoder's stream header or a key frame packet.
+    pub fn from_bytes(data: &[u8]) -> Result<Self> {
+        let obu = parse_obus(data)?
+            .into_iter()
+            .find(|obu| obu.obu_type() == ObuType::SequenceHeader)
+            .ok_or_else(|| Error::InvalidData("no sequence header OBU found".into()))?;
+        let mut header = Self::parse_payload(obu.payload)?;
+        header.bytes = obu.data.to_vec();
+        Ok(header)
+    }
+
+    /// Decodes a `sequence_header_obu()` payload. `bytes` is left empty.
+    pub fn parse_payload(payload: &[u8]) -> Result<Self> {
+        let mut r = BitReader::new(payload);
+        let profile = match r.f(3)? {
+            0 => Profile::Main,
+            1 => Profile::High,
+            2 => Profile::Professional,
+            p => return Err(Error::InvalidData(format!("reserved seq_profile {p}"))),
+        };
+        let still_picture = r.flag()?;
+        let reduced_still_picture_header = r.flag()?;
+        let mut timing_info = None;
+        let mut decoder_model_info = None;
+        let mut operating_points = Vec::new();
+        if reduced_still_picture_header {
+            operating_points.push(OperatingPoint {
+                idc: 0,
+                seq_level_idx: r.f(5)? as u8,
+                tier: Tier::Main,
+                initial_display_delay: None,
+            });
+        } else {
+            if r.flag()? {
+                let num_units_in_display_tick = r.f(32)?;
+                let time_scale = r.f(32)?;
+                let num_ticks_per_picture = if r.flag()? {
+                    Some(r.uvlc()?.saturating_add(1))
+                } else {
+                    None
+                };
+                timing_info = Some(TimingInfo {
+                    num_units_in_display_tick,
+                    time_scale,
+                    num_ticks_per_picture,
+                });
+                if r.flag()? {
+                    decoder_model_info = Some(DecoderModelInfo {
+                        buffer_delay_length: r.f(5)? as u8 + 1,
+                        num_units_in_decoding_tick: r.f(32)?,
+                        buffer_removal_time_length: r.f(5)? as u8 + 1,
+                        frame_presentation_time_length: r.f(5)? as u8 + 1,
+                    });
+                }
+            }
+            let initial_display_delay_present = r.flag()?;
+            let count = r.f(5)? + 1;
+            for _ in 0..count {
+                let idc = r.f(12)? as u16;
+                let seq_level_idx = r.f(5)? as u8;
+                let tier = if seq_level_idx > 7 && r.flag()? {
+                    Tier::High
+                } else {
+                    Tier::Main
+                };
+                if let Some(model) = decoder_model_info {
+                    if r.flag()? {
+                        // operating_parameters_info(): decoder/encoder buffer delay, low_delay_mode_flag
+                        let n = model.buffer_delay_length as u32;
+                        r.f(n)?;
+                        r.f(n)?;
+                        r.f(1)?;
+                    }
+                }
+                let initial_display_delay = if initial_display_delay_present && r.flag()? {
+                    Some(r.f(4)? as u8 + 1)
+                } else {
+                    None
+                };
+                operating_points.push(OperatingPoint {
+                    idc,
+                    seq_level_idx,
+                    tier,
+                    initial_display_delay,
+                });
+            }
+        }
+
+        let width_bits = r.f(4)? + 1;
+        let height_bits = r.f(4)? + 1;
+        let max_frame_width = r.f(width_bits)? + 1;
+        let max_frame_height = r.f(height_bits)? + 1;
+        let frame_id_numbers_present = !reduced_still_picture_header && r.flag()?;
+        if frame_id_numbers_present {
+            // delta_frame_id_length_minus_2, additional_frame_id_length_minus_1
+            r.f(4)?;
+            r.f(3)?;
+        }
+
+        let mut tools = SequenceTools {
+            use_128x128_superblock: r.flag()?,
+            enable_filter_intra: r.flag()?,
+            enable_intra_edge_filter: r.flag()?,
+            seq_force_screen_content_tools: 2,
+            seq_force_integer_mv: 2,
+            ..Default::default()
+        };
+        if !reduced_still_picture_header {
+            tools.enable_interintra_compound = r.flag()?;
+            tools.enable_masked_compound = r.flag()?;
+            tools.enable_warped_motion = r.flag()?;
+            tools.enable_dual_filter = r.flag()?;
+            tools.enable_order_hint = r.flag()?;
+            if tools.enable_order_hint {
+                tools.enable_jnt_comp = r.flag()?;
+                tools.enable_ref_frame_mvs = r.flag()?;
+            }
+            if !r.flag()? {
+                tools.seq_force_screen_content_tools = r.f(1)? as u8;
+            }
+            if tools.seq_force_screen_content_tools > 0 {
+                if !r.flag()? {
+                    tools.seq_force_integer_mv = r.f(1)? as u8;
+                }
+            } else {
+                tools.seq_force_integer_mv = 2;
+            }
+            if tools.enable_order_hint {
+                tools.order_hint_bits = r.f(3)? as u8 + 1;
+            }
+        }
+        tools.enable_superres = r.flag()?;
+        tools.enable_cdef = r.flag()?;
+        tools.enable_restoration = r.flag()?;
+
+        let color = parse_color_config(&mut r, profile)?;
+        let film_grain_params_present = r.flag()?;
+
+        Ok(Self {
+            bytes: Vec::new(),
+            profile,
+            still_picture,
+            reduced_still_picture_header,
+            timing_info,
+            decoder_model_info,
+            operating_points,
+            max_frame_width,
+            max_frame_height,
+            frame_id_numbers_present,
+            tools,
+            color,
+            film_grain_params_present,
+        })
+    }
+
+    /// `seq_level_idx` of operating point 0.
+    pub fn seq_level_idx(&self) -> u8 {
+        self.operating_points[0].seq_level_idx
+    }
+
+    /// Level of operating point 0 in the form `ConfigExt::set_level_code` takes
+    /// (e.g. 40 for level 4.0, 51 for 5.1); `None` when unconstrained.
+    pub fn level_code(&self) -> Option<u32> {
+        let idx = self.seq_level_idx() as u32;
+        (idx < 31).then(|| (2 + idx / 4) * 10 + idx % 4)
+    }
+
+    /// Tier of operating point 0.
+    pub fn tier(&self) -> Tier {
+        self.operating_points[0].tier
+    }
+
+    pub fn bit_depth(&self) -> BitDepth {
+        self.color.bit_depth
+    }
+
+    pub fn color_format(&self) -> ColorFormat {
+        self.color.color_format
+    }
+}
+
+fn parse_color_config(r: &mut BitReader, profile: Profile) -> Result<ColorConfig> {
+    let high_bitdepth = r.flag()?;
+    let bit_depth = match (profile, high_bitdepth) {
+        (_, false) => BitDepth::Eight,
+        (Profile::Professional, true) if r.flag()? => BitDepth::Twelve,
+        (_, true) => BitDepth::Ten,
+    };
+    let mono_chrome = profile != Profile::High && r.flag()?;
+    let (mut cp, mut tc, mut mc) = (2, 2, 2);
+    if r.flag()? {
+        cp = r.f(8)? as u8;
+        tc = r.f(8)? as u8;
+        mc = r.f(8)? as u8;
+    }
+    let mut color = ColorConfig {
+        bit_depth,
+        color_format: ColorFormat::Yuv420,
+        color_primaries: cp,
+        transfer_characteristics: tc,
+        matrix_coefficients: mc,
+        color_range: ColorRange::Studio,
+        subsampling_x: true,
+        subsampling_y: true,
+        chroma_sample_position: ChromaSamplePosition::Unknown,
+        separate_uv_delta_q: false,
+    };
+    let range = |full: bool| {
+        if full {
+            ColorRange::Full
+        } else {
+            ColorRange::Studio
+        }
+    };
+    if mono_chrome {
+        color.color_range = range(r.flag()?);
+        color.color_format = ColorFormat::Yuv400;
+        return Ok(color);
+    }
+    if cp == 1 && tc == 13 && mc == 0 {
+        // sRGB: full range 4:4:4
+        color.color_range = ColorRange::Full;
+        color.subsampling_x = false;
+        color.subsampling_y = false;
+    } else {
+        color.color_range = range(r.flag()?);
+        let (x, y) = match profile {
+            Profile::Main => (true, true),
+            Profile::High => (false, false),
+            Profile::Professional if bit_depth == BitDepth::Twelve => {
+                let x = r.flag()?;
+                (x, x && r.flag()?)
+            }
+            Profile::Professional => (true, false),
+        };
+        color.subsampling_x = x;
+        color.subsampling_y = y;
+        if x && y {
+            color.chroma_sample_position = match r.f(2)? {
+                1 => ChromaSamplePosition::Vertical,
+                2 => ChromaSamplePosition::Colocated,
+                _ => ChromaSamplePosition::Unknown,
+            };
+        }
+    }
+    color.color_format = match (color.subsampling_x, color.subsampling_y) {
+        (true, true) => ColorFormat::Yuv420,
+        (true, false) => ColorFormat::Yuv422,
+        _ => ColorFormat::Yuv444,
+    };
+    color.separate_uv_delta_q = r.flag()?;
+    Ok(color)
+}
@@ -0,0 +1,240 @@
+use std::f32::consts::PI;
+
+use crate::config::ChromaSamplePosition;
+use crate::frame::Frame;
+use crate::{Error, Result};
+
+/// Resampling kernel.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Filter {
+    /// Triangle filter, two taps per output sample at unit scale.
+    Bilinear,
+    /// Keys cubic with `a = -0.5` (Catmull-Rom), four taps.
+    Bicubic,
+    /// Three-lobe Lanczos, six taps.
+    Lanczos3,
+}
+
+impl Filter {
+    fn radius(self) -> f32 {
+        match self {
+            Filter::Bilinear => 1.0,
+            Filter::Bicubic => 2.0,
+            Filter::Lanczos3 => 3.0,
+        }
+    }
+
+    fn weight(self, x: f32) -> f32 {
+        let x = x.abs();
+        match self {
+            Filter::Bilinear => (1.0 - x).max(0.0),
+            Filter::Bicubic => {
+                const A: f32 = -0.5;
+                if x < 1.0 {
+                    ((A + 2.0) * x - (A + 3.0)) * x * x + 1.0
+                } else if x < 2.0 {
+                    ((A * x - 5.0 * A) * x + 8.0 * A) * x - 4.0 * A
+                } else {
+                    0.0
+                }
+            }
+            Filter::Lanczos3 => {
+                if x == 0.0 {
+                    1.0
+                } else if x < 3.0 {
+                    let px = PI * x;
+                    3.0 * px.sin() * (px / 3.0).sin() / (px * px)
+                } else {
+                    0.0
+                }
+            }
+        }
+    }
+}
+
+/// Filter taps for one output sample.
+struct Taps {
+    start: usize,
+    weights: Vec<f32>,
+}
+
+/// Resamples [`Frame`]s to a new size.
+///
+/// Each plane is filtered separably in floating point and rounded back to the
+/// frame's bit depth. Chroma samples are positioned on the luma grid
+/// according to the chroma sample position (see
+/// [`ColorConversion`](crate::convert::ColorConversion) for the convention),
+/// so subsampled chroma stays aligned with luma at any scale factor,
+/// including odd frame sizes.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Scaler {
+    pub filter: Filter,
+    pub chroma_position: ChromaSamplePosition,
+}
+
+impl Scaler {
+    /// Scaler with chroma between luma rows, the AV1 default for 4:2:0.
+    pub fn new(filter: Filter) -> Self {
+        Self {
+            filter,
+            chroma_position: ChromaSamplePosition::Vertical,
+        }
+    }
+
+    pub fn with_chroma_position(mut self, position: ChromaSamplePosition) -> Self {
+        self.chroma_position = position;
+        self
+    }
+
+    /// Scales `src` into a new `width` x `height` frame with the same bit
+    /// depth, color format and pts.
+    pub fn scale_to(&self, src: &Frame, width: u32, height: u32) -> Result<Frame> {
+        let mut dst = Frame::new(width, height, src.bit_depth(), src.color_format());
+        self.scale(src, &mut dst)?;
+        dst.pts = src.pts;
+        Ok(dst)
+    }
+
+    /// Scales `src` into `dst`, whose size sets the output resolution.
+    ///
+    /// Both frames must share bit depth and color format.
+    pub fn scale(&self, src: &Frame, dst: &mut Frame) -> Result<()> {
+        if src.bit_depth() != dst.bit_depth() || src.color_format() != dst.color_format() {
+            return Err(Error::InvalidArgument(
+                "scaling cannot change bit depth or color format".into(),
+            ));
+        }
+        if src.width() == 0 || src.height() == 0 || dst.width() == 0 || dst.height() == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "cannot scale {}x{} to {}x{}",
+                src.width(),
+                src.height(),
+                dst.width(),
+                dst.height()
+            )));
+        }
+
+        let (ss_x, ss_y) = src.color_format().subsampling();
+        let offset_x = match self.chroma_position {
+            ChromaSamplePosition::Unknown => 0.5,
+            _ => 0.0,
+        };
+        let offset_y = match self.chroma_position {
+            ChromaSamplePosition::Colocated => 0.0,
+            _ => 0.5,
+        };
+        let max = ((1u32 << src.bit_depth().bits()) - 1) as f32;
+        let scale_x = src.width() as f32 / dst.width() as f32;
+        let scale_y = src.height() as f32 / dst.height() as f32;
+
+        for p in 0..src.color_format().num_planes() {
+            let (factor_x, off_x) = if p > 0 && ss_x == 1 {
+                (2.0, offset_x)
+            } else {
+                (1.0, 0.0)
+            };
+            let (factor_y, off_y) = if p > 0 && ss_y == 1 {
+                (2.0, offset_y)
+            } else {
+                (1.0, 0.0)
+            };
+            let taps_x = self.taps(
+                src.plane_width(p) as usize,
+                dst.plane_width(p) as usize,
+                scale_x,
+                factor_x,
+                off_x,
+            );
+            let taps_y = self.taps(
+                src.plane_height(p) as usize,
+                dst.plane_height(p) as usize,
+                scale_y,
+                factor_y,
+                off_y,
+            );
+            scale_plane(src, dst, p, &taps_x, &taps_y, max);
+        }
+        Ok(())
+    }
+
+    /// Taps mapping `dst_len` output samples onto `src_len` input samples.
+    ///
+    /// Sample `i` of a plane subsampled by `factor` sits at luma position
+    /// `factor * i + offset`; that position is mapped between the luma grids
+    /// with pixel centres aligned and back into source plane coordinates.
+    fn taps(
+        &self,
+        src_len: usize,
+        dst_len: usize,
+        scale: f32,
+        factor: f32,
+        offset: f32,
+    ) -> Vec<Taps> {
+        // Widen the kernel when downscaling so it low-passes the source.
+        let stretch = scale.max(1.0);
+        let radius = self.filter.radius() * stretch;
+        (0..dst_len)
+            .map(|i| {
+                let dst_luma = factor * i as f32 + offset;
+                let src_luma = (dst_luma + 0.5) * scale - 0.5;
+                let centre = (src_luma - offset) / factor;
+                let first = (centre - radius).ceil() as isize;
+                let last = (centre + radius).floor() as isize;
+                // Taps past either edge repeat the edge sample.
+                let clamp = |j: isize| j.clamp(0, src_len as isize - 1) as usize;
+                let start = clamp(first);
+                let mut weights = vec![0f32; clamp(last) - start + 1];
+                for j in first..=last {
+                    weights[clamp(j) - start] += self.filter.weight((j as f32 - centre) / stretch);
+                }
+                let sum: f32 = weights.iter().sum();
+                weights.iter_mut().for_each(|w| *w /= sum);
+                Taps { start, weights }
+            })
+            .collect()
+    }
+}
+
+fn scale_plane(src: &Frame, dst: &mut Frame, p: usize, taps_x: &[Taps], taps_y: &[Taps], max: f32) {
+    let bps = src.bytes_per_sample();
+    let read = |row: &[u8], x: usize| -> f32 {
+        if bps == 1 {
+            row[x] as f32
+        } else {
+            u16::from_le_bytes([row[2 * x], row[2 * x + 1]]) as f32
+        }
+    };
+
+    // Horizontal pass over every source row, then a vertical pass per output row.
+    let dst_width = taps_x.len();
+    let mut rows = vec![0f32; src.plane_height(p) as usize * dst_width];
+    for (y, out) in rows.chunks_exact_mut(dst_width).enumerate() {
+        let row = src.row(p, y);
+        for (v, t) in out.iter_mut().zip(taps_x) {
+            *v = t
+                .weights
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * read(row, t.start + k))
+                .sum();
+        }
+    }
+
+    for (y, t) in taps_y.iter().enumerate() {
+        let out = dst.row_mut(p, y);
+        for x in 0..dst_width {
+            let v: f32 = t
+                .weights
+                .iter()
+                .enumerate()
+                .map(|(k, w)| w * rows[(t.start + k) * dst_width + x])
+                .sum();
+            let v = v.round().clamp(0.0, max) as u16;
+            if bps == 1 {
+                out[x] = v as u8;
+            } else {
+                out[2 * x..2 * x + 2].copy_from_slice(&v.to_le_bytes());
+            }
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::frame::Frame;
+use crate::{Error, Result};
+
+/// Side of the square luma blocks averaged into one thumbnail sample.
+const BLOCK: usize = 8;
+/// Histogram bins over the 8-bit thumbnail range.
+const BINS: usize = 32;
+
+/// Downscaled luma of one frame, normalized to 8 bits.
+#[derive(Clone, Debug)]
+struct Thumbnail {
+    width: u32,
+    height: u32,
+    samples: Vec<u8>,
+    histogram: [u32; BINS],
+}
+
+impl Thumbnail {
+    fn new(frame: &Frame) -> Self {
+        let (width, height) = (frame.width() as usize, frame.height() as usize);
+        let (cols, rows) = (width.div_ceil(BLOCK), height.div_ceil(BLOCK));
+        let shift = frame.bit_depth().bits() - 8;
+        let bps = frame.bytes_per_sample();
+        let mut sums = vec![0u32; cols * rows];
+        for y in 0..height {
+            let row = frame.row(0, y);
+            let sums = &mut sums[y / BLOCK * cols..][..cols];
+            for x in 0..width {
+                let v = if bps == 1 {
+                    row[x] as u32
+                } else {
+                    u16::from_le_bytes([row[2 * x], row[2 * x + 1]]) as u32
+                };
+                sums[x / BLOCK] += v >> shift;
+            }
+        }
+
+        // Edge blocks of odd-sized frames cover fewer samples.
+        let mut samples = Vec::with_capacity(sums.len());
+        let mut histogram = [0u32; BINS];
+        for (i, sum) in sums.into_iter().enumerate() {
+            let (bx, by) = (i % cols, i / cols);
+            let w = BLOCK.min(width - bx * BLOCK) as u32;
+            let h = BLOCK.min(height - by * BLOCK) as u32;
+            let v = (sum / (w * h)).min(255) as u8;
+            histogram[v as usize * BINS / 256] += 1;
+            samples.push(v);
+        }
+        Self {
+            width: frame.width(),
+            height: frame.height(),
+            samples,
+            histogram,
+        }
+    }
+
+    /// Share of samples that moved to another histogram bin, from 0 to 1.
+    fn histogram_difference(&self, other: &Self) -> f32 {
+        let moved: u32 = self
+            .histogram
+            .iter()
+            .zip(&other.histogram)
+            .map(|(a, b)| a.abs_diff(*b))
+            .sum();
+        moved as f32 / (2 * self.samples.len()) as f32
+    }
+
+    /// Mean absolute difference of the samples, from 0 to 1.
+    fn sad(&self, other: &Self) -> f32 {
+        let sad: u64 = self
+            .samples
+            .iter()
+            .zip(&other.samples)
+            .map(|(a, b)| a.abs_diff(*b) as u64)
+            .sum();
+        sad as f32 / (255 * self.samples.len()) as f32
+    }
+}
+
+/// Scores of one frame against the frame before it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FrameScore {
+    /// Share of thumbnail samples whose histogram bin changed, from 0 to 1.
+    pub histogram: f32,
+    /// Mean absolute thumbnail difference, from 0 to 1.
+    pub sad: f32,
+}
+
+/// Finds scene cuts in a frame sequence ahead of encoding.
+///
+/// Each frame's luma is averaged over 8x8 blocks into an 8-bit thumbnail.
+/// A frame starts a new scene when, against the frame before it, both the
+/// thumbnail histogram difference and the mean absolute difference (SAD)
+/// reach their thresholds. The histogram detects the cut and ignores motion
+/// within a scene; the SAD only confirms it, rejecting histogram changes
+/// with little sample change, such as a slight brightness shift across bin
+/// edges. A cut between shots with similar brightness distributions is
+/// therefore missed.
+///
+/// Cuts closer than `min_scene_len` frames to the previous cut are dropped,
+/// and a cut is forced after `max_scene_len` frames without one. Cut
+/// positions are frame indices in input order; frame 0 always starts the
+/// first scene and is not reported.
+#[derive(Clone, Debug)]
+pub struct SceneDetector {
+    pub histogram_threshold: f32,
+    pub sad_threshold: f32,
+    pub min_scene_len: usize,
+    pub max_scene_len: usize,
+    previous: Option<Thumbnail>,
+    scene_start: usize,
+    frames: usize,
+    cuts: Vec<usize>,
+}
+
+impl Default for SceneDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneDetector {
+    /// Detector with thresholds of 0.4 (histogram) and 0.1 (SAD), scenes of
+    /// at least 6 frames and no maximum length.
+    pub fn new() -> Self {
+        Self {
+            histogram_threshold: 0.4,
+            sad_threshold: 0.1,
+            min_scene_len: 6,
+            max_scene_len: usize::MAX,
+            previous: None,
+            scene_start: 0,
+            frames: 0,
+            cuts: Vec::new(),
+        }
+    }
+
+    pub fn with_thresholds(mut self, histogram: f32, sad: f32) -> Self {
+        self.histogram_threshold = histogram;
+        self.sad_threshold = sad;
+        self
+    }
+
+    pub fn with_scene_len(mut self, min: usize, max: usize) -> Self {
+        self.min_scene_len = min;
+        self.max_scene_len = max;
+        self
+    }
+
+    /// Scores `frame` and reports whether it starts a new scene.
+    ///
+    /// Frames must keep the size of the first one; bit depth and color
+    /// format may vary since only 8-bit luma is compared.
+    pub fn push(&mut self, frame: &Frame) -> Result<bool> {
+        if self.max_scene_len == 0 || self.min_scene_len > self.max_scene_len {
+            return Err(Error::InvalidArgument(format!(
+                "scene length bounds {}..={} are empty",
+                self.min_scene_len, self.max_scene_len
+            )));
+        }
+        if let Some(prev) = &self.previous {
+            if (prev.width, prev.height) != (frame.width(), frame.height()) {
+                return Err(Error::InvalidArgument(format!(
+                    "frame {} is {}x{}, earlier frames are {}x{}",
+                    self.frames,
+                    frame.width(),
+                    frame.height(),
+                    prev.width,
+                    prev.height
+                )));
+            }
+        }
+        if frame.width() == 0 || frame.height() == 0 {
+            return Err(Error::InvalidArgument(
+                "cannot analyse an empty frame".into(),
+            ));
+        }
+
+        let thumbnail = Thumbnail::new(frame);
+        let index = self.frames;
+        let len = index - self.scene_start;
+        let cut = match &self.previous {
+            None => false,
+            Some(_) if len >= self.max_scene_len => true,
+            Some(prev) => {
+                let score = score(prev, &thumbnail);
+                len >= self.min_scene_len
+                    && score.histogram >= self.histogram_threshold
+                    && score.sad >= self.sad_threshold
+            }
+        };
+        if cut {
+            self.cuts.push(index);
+            self.scene_start = index;
+        }
+        self.previous = Some(thumbnail);
+        self.frames += 1;
+        Ok(cut)
+    }
+
+    /// Runs [`push`](Self::push) over `frames` and returns every cut so far.
+    pub fn detect<I>(&mut self, frames: I) -> Result<&[usize]>
+    where
+        I: IntoIterator<Item = Result<Frame>>,
+    {
+        for frame in frames {
+            self.push(&frame?)?;
+        }
+        Ok(&self.cuts)
+    }
+
+    /// Cuts found so far.
+    pub fn cuts(&self) -> &[usize] {
+        &self.cuts
+    }
+
+    /// Frames analysed so far.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Chunk boundaries at the cuts found so far, for
+    /// [`ChunkedEncoder`](crate::chunked::ChunkedEncoder).
+    #[cfg(feature = "encoder")]
+    pub fn chunk_split(&self) -> crate::chunked::ChunkSplit {
+        crate::chunked::ChunkSplit::SceneCuts {
+            cuts: self.cuts.clone(),
+            max_len: self.max_scene_len,
+        }
+    }
+
+    /// Forced keyframes on the cuts found so far, for frames whose pts is
+    /// their index in the analysed sequence (as in a chunked encode).
+    #[cfg(feature = "encoder")]
+    pub fn keyframe_policy(&self) -> crate::encoder::KeyframePolicy {
+        crate::encoder::KeyframePolicy::Pts(self.cuts.iter().map(|&c| c as i64).collect())
+    }
+}
+
+/// Compares two frames the way [`SceneDetector`] does.
+pub fn compare(a: &Frame, b: &Frame) -> Result<FrameScore> {
+    if (a.width(), a.height()) != (b.width(), b.height()) || a.width() == 0 || a.height() == 0 {
+        return Err(Error::InvalidArgument(format!(
+            "cannot compare a {}x{} frame with a {}x{} frame",
+            a.width(),
+            a.height(),
+            b.width(),
+            b.height()
+        )));
+    }
+    Ok(score(&Thumbnail::new(a), &Thumbnail::new(b)))
+}
+
+fn score(a: &Thumbnail, b: &Thumbnail) -> FrameScore {
+    FrameScore {
+        histogram: a.histogram_difference(b),
+        sad: a.sad(b),
+    }
+}
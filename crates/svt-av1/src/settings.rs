@@ -0,0 +1,812 @@
+use crate::config::RcMode;
+use crate::encoder::{Configuration, Encoder};
+use crate::{Error, Result};
+
+/// `EbSvtAv1MasteringDisplayInfo` as plain data: chromaticities of the
+/// primaries and the white point as `[x, y]` in units of 0.00002, luminance
+/// in units of 0.0001 cd/m^2.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct MasteringDisplay {
+    pub r: [u16; 2],
+    pub g: [u16; 2],
+    pub b: [u16; 2],
+    pub white_point: [u16; 2],
+    pub max_luma: u32,
+    pub min_luma: u32,
+}
+
+type RawMasteringDisplay = svt_av1_sys::enc_bindings::EbSvtAv1MasteringDisplayInfo;
+
+impl From<RawMasteringDisplay> for MasteringDisplay {
+    fn from(raw: RawMasteringDisplay) -> Self {
+        Self {
+            r: raw.r,
+            g: raw.g,
+            b: raw.b,
+            white_point: raw.white_point,
+            max_luma: raw.max_luma,
+            min_luma: raw.min_luma,
+        }
+    }
+}
+
+impl From<MasteringDisplay> for RawMasteringDisplay {
+    fn from(md: MasteringDisplay) -> Self {
+        Self {
+            r: md.r,
+            g: md.g,
+            b: md.b,
+            white_point: md.white_point,
+            max_luma: md.max_luma,
+            min_luma: md.min_luma,
+        }
+    }
+}
+
+/// `EbContentLightLevel` as plain data, in cd/m^2.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(deny_unknown_fields))]
+pub struct ContentLightLevel {
+    pub max_cll: u16,
+    pub max_fall: u16,
+}
+
+type RawContentLightLevel = svt_av1_sys::enc_bindings::EbContentLightLevel;
+
+impl From<RawContentLightLevel> for ContentLightLevel {
+    fn from(raw: RawContentLightLevel) -> Self {
+        Self {
+            max_cll: raw.max_cll,
+            max_fall: raw.max_fall,
+        }
+    }
+}
+
+impl From<ContentLightLevel> for RawContentLightLevel {
+    fn from(cll: ContentLightLevel) -> Self {
+        Self {
+            max_cll: cll.max_cll,
+            max_fall: cll.max_fall,
+        }
+    }
+}
+
+/// A value as it appears in a parameter string.
+trait Param: Sized {
+    fn parse(value: &str) -> std::result::Result<Self, String>;
+    fn format(self) -> String;
+}
+
+macro_rules! integer_param {
+    ($($ty:ty),*) => {$(
+        impl Param for $ty {
+            fn parse(value: &str) -> std::result::Result<Self, String> {
+                let wide: i128 = match value {
+                    "true" => 1,
+                    "false" => 0,
+                    _ => value
+                        .parse()
+                        .map_err(|_| format!("expected an integer, got `{value}`"))?,
+                };
+                Self::try_from(wide)
+                    .map_err(|_| format!("{wide} is outside {}..={}", Self::MIN, Self::MAX))
+            }
+
+            fn format(self) -> String {
+                self.to_string()
+            }
+        }
+    )*};
+}
+
+integer_param!(i8, u8, i32, u32, i64, u64);
+
+/// Chromaticity unit of the mastering display, 0.00002.
+const CHROMA_SCALE: f64 = 50_000.0;
+/// Luminance unit of the mastering display, 0.0001 cd/m^2.
+const LUMA_SCALE: f64 = 10_000.0;
+
+/// `G(x,y)B(x,y)R(x,y)WP(x,y)L(max,min)` with chromaticities and
+/// luminances in natural units, as SvtAv1EncApp takes them.
+impl Param for MasteringDisplay {
+    fn parse(value: &str) -> std::result::Result<Self, String> {
+        let mut md = Self::default();
+        let mut seen = [false; 5];
+        let mut rest = value.trim();
+        while !rest.is_empty() {
+            let (label, tail) = rest
+                .split_once('(')
+                .ok_or_else(|| format!("expected `label(a,b)` at `{rest}`"))?;
+            let (args, tail) = tail
+                .split_once(')')
+                .ok_or_else(|| format!("unclosed `{label}(`"))?;
+            let (a, b) = args
+                .split_once(',')
+                .ok_or_else(|| format!("`{label}` takes two values"))?;
+            let number = |s: &str| {
+                s.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| *v >= 0.0)
+                    .ok_or_else(|| format!("`{label}` has a bad value `{s}`"))
+            };
+            let (a, b) = (number(a)?, number(b)?);
+            let chroma = |v: f64| {
+                let scaled = (v * CHROMA_SCALE).round();
+                (scaled <= u16::MAX as f64)
+                    .then_some(scaled as u16)
+                    .ok_or_else(|| format!("`{label}` chromaticity {v} is above 1"))
+            };
+            let slot = match label.trim().to_ascii_uppercase().as_str() {
+                "G" => {
+                    md.g = [chroma(a)?, chroma(b)?];
+                    0
+                }
+                "B" => {
+                    md.b = [chroma(a)?, chroma(b)?];
+                    1
+                }
+                "R" => {
+                    md.r = [chroma(a)?, chroma(b)?];
+                    2
+                }
+                "WP" => {
+                    md.white_point = [chroma(a)?, chroma(b)?];
+                    3
+                }
+                "L" => {
+                    md.max_luma = (a * LUMA_SCALE).round().min(u32::MAX as f64) as u32;
+                    md.min_luma = (b * LUMA_SCALE).round().min(u32::MAX as f64) as u32;
+                    4
+                }
+                other => return Err(format!("unknown component `{other}`")),
+            };
+            seen[slot] = true;
+            rest = tail.trim_start();
+        }
+        if seen.contains(&false) {
+            return Err("expected G, B, R, WP and L components".into());
+        }
+        Ok(md)
+    }
+
+    fn format(self) -> String {
+        let c = |v: u16| v as f64 / CHROMA_SCALE;
+        format!(
+            "G({},{})B({},{})R({},{})WP({},{})L({},{})",
+            c(self.g[0]),
+            c(self.g[1]),
+            c(self.b[0]),
+            c(self.b[1]),
+            c(self.r[0]),
+            c(self.r[1]),
+            c(self.white_point[0]),
+            c(self.white_point[1]),
+            self.max_luma as f64 / LUMA_SCALE,
+            self.min_luma as f64 / LUMA_SCALE
+        )
+    }
+}
+
+/// `max_cll,max_fall`.
+impl Param for ContentLightLevel {
+    fn parse(value: &str) -> std::result::Result<Self, String> {
+        let (cll, fall) = value
+            .split_once(',')
+            .ok_or_else(|| format!("expected `max_cll,max_fall`, got `{value}`"))?;
+        Ok(Self {
+            max_cll: u16::parse(cll.trim())?,
+            max_fall: u16::parse(fall.trim())?,
+        })
+    }
+
+    fn format(self) -> String {
+        format!("{},{}", self.max_cll, self.max_fall)
+    }
+}
+
+integer_param!(u16);
+
+/// Fields whose parameter means the same as the C field.
+mod same {
+    use super::{Configuration, Param};
+
+    pub fn from_cli<T: Param>(_: &mut Configuration, value: &str) -> Result<T, String> {
+        T::parse(value)
+    }
+
+    pub fn to_cli<T: Param>(_: &Configuration, value: T) -> String {
+        value.format()
+    }
+}
+
+/// Bit rates: kbps on the command line, bps in the configuration. Rates
+/// that are not whole kbps show rounded down.
+mod kbps {
+    use super::{Configuration, Param};
+
+    pub fn from_cli(_: &mut Configuration, value: &str) -> Result<u32, String> {
+        let kbps = u32::parse(value)?;
+        kbps.checked_mul(1000)
+            .ok_or_else(|| format!("{kbps} kbps does not fit in bits per second"))
+    }
+
+    pub fn to_cli(_: &Configuration, bps: u32) -> String {
+        (bps / 1000).to_string()
+    }
+}
+
+/// `--keyint` counts frames per GOP; `intra_period_length` is one less.
+/// Negative values (-1 no periodic keyframes, -2 automatic) pass through.
+/// With an `s` suffix the length is in seconds: the field keeps the count
+/// of seconds and `multiply_keyint` is set, so the library scales it by
+/// the frame rate.
+mod keyint {
+    use super::{Configuration, Param};
+
+    pub fn from_cli(cfg: &mut Configuration, value: &str) -> Result<i32, String> {
+        if let Some(seconds) = value.strip_suffix('s') {
+            let seconds = i32::parse(seconds)?;
+            if seconds < 1 {
+                return Err(format!("{value} is not a GOP length"));
+            }
+            cfg.multiply_keyint = 1;
+            return Ok(seconds);
+        }
+        let keyint = i32::parse(value)?;
+        let period = match keyint {
+            1.. => keyint - 1,
+            -2 | -1 => keyint,
+            _ => return Err(format!("{keyint} is not a GOP length, -1 or -2")),
+        };
+        cfg.multiply_keyint = 0;
+        Ok(period)
+    }
+
+    pub fn to_cli(cfg: &Configuration, period: i32) -> String {
+        match period {
+            _ if cfg.multiply_keyint != 0 => format!("{period}s"),
+            0.. => (period + 1).to_string(),
+            _ => period.to_string(),
+        }
+    }
+}
+
+macro_rules! cli_name {
+    () => {
+        None
+    };
+    ($cli:literal) => {
+        Some($cli)
+    };
+}
+
+macro_rules! conversion {
+    ($f:ident;) => {
+        same::$f
+    };
+    ($f:ident; $conv:ident) => {
+        $conv::$f
+    };
+}
+
+/// One field of [`Configuration`] and how parameter strings spell it.
+pub struct Field {
+    name: &'static str,
+    cli: Option<&'static str>,
+    section: &'static str,
+    raw: fn(&Configuration) -> String,
+    format: fn(&Configuration) -> String,
+    parse: fn(&mut Configuration, &str) -> std::result::Result<(), String>,
+}
+
+impl Field {
+    /// Name of the C struct field.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// SvtAv1EncApp option, without the leading `--`, or `None` if the
+    /// field has no command-line option.
+    pub fn cli_name(&self) -> Option<&'static str> {
+        self.cli
+    }
+
+    /// The option name if the field has one, else the C field name.
+    pub fn key(&self) -> &'static str {
+        self.cli.unwrap_or(self.name)
+    }
+
+    /// Title of the group of related fields this one belongs to.
+    pub fn section(&self) -> &'static str {
+        self.section
+    }
+
+    /// The field's value in `cfg`, in the option's units and syntax.
+    pub fn value(&self, cfg: &Configuration) -> String {
+        (self.format)(cfg)
+    }
+
+    /// Parses `value` in the option's units and syntax into `cfg`.
+    pub fn set(&self, cfg: &mut Configuration, value: &str) -> Result<()> {
+        (self.parse)(cfg, value).map_err(|reason| Error::Parameter {
+            key: self.key().into(),
+            reason,
+        })
+    }
+}
+
+impl std::fmt::Debug for Field {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Field")
+            .field("name", &self.name)
+            .field("cli", &self.cli)
+            .field("section", &self.section)
+            .finish()
+    }
+}
+
+/// Defines [`EncoderSettings`] and the field table from the fields of
+/// [`Configuration`], grouped into titled sections, each with its
+/// SvtAv1EncApp option if it has one and the conversion between the
+/// option's units and the field's.
+///
+/// The table holds every value field of the struct. It leaves out
+/// `frame_scale_evts` and `fgs_table`, which point at caller-owned memory
+/// and have no value to store, copy or print, and the reserved `padding`.
+/// `settings_cover_every_field` destructures the struct with the table's
+/// fields and those three exclusions, so a field added by a library update
+/// fails to compile until it is listed here.
+macro_rules! encoder_settings {
+    ($($section:literal {
+        $($field:ident: $ty:ty $(= $cli:literal $(via $conv:ident)?)?,)*
+    })*) => {
+        /// A Rust-native mirror of the value fields of [`Configuration`], each
+        /// optional.
+        ///
+        /// Fields carry the names and raw values of the C struct. Only the
+        /// fields that are set override a configuration, so a profile lists
+        /// just what it changes from the library defaults. With the `serde`
+        /// feature the settings (de)serialize as a flat table, which loads
+        /// from TOML or JSON; unset fields are omitted and unknown ones are
+        /// rejected.
+        #[derive(Clone, Debug, Default, PartialEq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[cfg_attr(feature = "serde", serde(default, deny_unknown_fields))]
+        pub struct EncoderSettings {
+            $($(
+                #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+                pub $field: Option<$ty>,
+            )*)*
+        }
+
+        #[allow(clippy::useless_conversion)]
+        impl EncoderSettings {
+            /// Every field of `cfg` in the table.
+            pub fn from_configuration(cfg: &Configuration) -> Self {
+                Self {
+                    $($($field: Some(cfg.$field.into()),)*)*
+                }
+            }
+
+            /// The fields of `cfg` that differ from `base`, e.g. the library
+            /// defaults, as a sparse profile.
+            pub fn changes(base: &Configuration, cfg: &Configuration) -> Self {
+                Self {
+                    $($($field: {
+                        let value = <$ty>::from(cfg.$field);
+                        (value != <$ty>::from(base.$field)).then_some(value)
+                    },)*)*
+                }
+            }
+
+            /// Writes the fields that are set into `cfg`.
+            pub fn apply_to(&self, cfg: &mut Configuration) {
+                $($(
+                    if let Some(value) = self.$field {
+                        cfg.$field = value.into();
+                    }
+                )*)*
+            }
+
+            /// Whether no field is set.
+            pub fn is_empty(&self) -> bool {
+                *self == Self::default()
+            }
+        }
+
+        #[allow(clippy::useless_conversion)]
+        static FIELDS: &[Field] = &[$($(
+            Field {
+                name: stringify!($field),
+                cli: cli_name!($($cli)?),
+                section: $section,
+                raw: |cfg| Param::format(<$ty>::from(cfg.$field)),
+                format: |cfg| conversion!(to_cli; $($($conv)?)?)(cfg, <$ty>::from(cfg.$field)),
+                parse: |cfg, value| {
+                    let value: $ty = conversion!(from_cli; $($($conv)?)?)(cfg, value)?;
+                    cfg.$field = value.into();
+                    Ok(())
+                },
+            },
+        )*)*];
+
+        #[allow(dead_code)]
+        fn settings_cover_every_field(cfg: Configuration) {
+            let Configuration {
+                $($($field: _,)*)*
+                frame_scale_evts: _,
+                fgs_table: _,
+                padding: _,
+            } = cfg;
+        }
+    };
+}
+
+encoder_settings! {
+    "Preset and GOP structure" {
+        enc_mode: i8 = "preset",
+        intra_period_length: i32 = "keyint" via keyint,
+        intra_refresh_type: u32 = "irefresh-type",
+        hierarchical_levels: u32 = "hierarchical-levels",
+        pred_structure: u8 = "pred-struct",
+    }
+    "Picture format" {
+        source_width: u32 = "width",
+        source_height: u32 = "height",
+        forced_max_frame_width: u32 = "forced-max-frame-width",
+        forced_max_frame_height: u32 = "forced-max-frame-height",
+        frame_rate_numerator: u32 = "fps-num",
+        frame_rate_denominator: u32 = "fps-denom",
+        encoder_bit_depth: u32 = "input-depth",
+        encoder_color_format: u32 = "color-format",
+        profile: u32 = "profile",
+        tier: u32 = "tier",
+        level: u32 = "level",
+    }
+    "Color description" {
+        color_primaries: u32 = "color-primaries",
+        transfer_characteristics: u32 = "transfer-characteristics",
+        matrix_coefficients: u32 = "matrix-coefficients",
+        color_range: u32 = "color-range",
+        mastering_display: MasteringDisplay = "mastering-display",
+        content_light_level: ContentLightLevel = "content-light",
+        chroma_sample_position: u32 = "chroma-sample-position",
+    }
+    "Quantization" {
+        screen_content_mode: u32 = "scm",
+        enable_adaptive_quantization: u8 = "aq-mode",
+        qp: u32 = "qp",
+        use_qp_file: u8 = "use-q-file",
+        use_fixed_qindex_offsets: u8 = "use-fixed-qindex-offsets",
+        key_frame_chroma_qindex_offset: i32 = "key-frame-chroma-qindex-offset",
+        key_frame_qindex_offset: i32 = "key-frame-qindex-offset",
+        luma_y_dc_qindex_offset: i32 = "luma-y-dc-qindex-offset",
+        chroma_u_dc_qindex_offset: i32 = "chroma-u-dc-qindex-offset",
+        chroma_u_ac_qindex_offset: i32 = "chroma-u-ac-qindex-offset",
+        chroma_v_dc_qindex_offset: i32 = "chroma-v-dc-qindex-offset",
+        chroma_v_ac_qindex_offset: i32 = "chroma-v-ac-qindex-offset",
+    }
+    "In-loop filters" {
+        enable_dlf_flag: u8 = "enable-dlf",
+        cdef_level: i32 = "enable-cdef",
+        enable_restoration_filtering: i32 = "enable-restoration",
+        enable_mfmv: i32 = "enable-mfmv",
+    }
+    "Rate control" {
+        scene_change_detection: u32 = "scd",
+        rate_control_mode: u32 = "rc",
+        look_ahead_distance: u32 = "lookahead",
+        target_bit_rate: u32 = "tbr" via kbps,
+        max_bit_rate: u32 = "mbr" via kbps,
+        max_qp_allowed: u32 = "max-qp",
+        min_qp_allowed: u32 = "min-qp",
+        vbr_min_section_pct: u32 = "minsection-pct",
+        vbr_max_section_pct: u32 = "maxsection-pct",
+        under_shoot_pct: u32 = "undershoot-pct",
+        over_shoot_pct: u32 = "overshoot-pct",
+        mbr_over_shoot_pct: u32 = "mbr-overshoot-pct",
+        starting_buffer_level_ms: i64 = "buf-initial-sz",
+        optimal_buffer_level_ms: i64 = "buf-optimal-sz",
+        maximum_buffer_size_ms: i64 = "buf-sz",
+        recode_loop: u32 = "recode-loop",
+        gop_constraint_rc: u8 = "gop-constraint-rc",
+    }
+    "Tiles and threading" {
+        tile_columns: i32 = "tile-columns",
+        tile_rows: i32 = "tile-rows",
+        restricted_motion_vector: u8 = "restricted-motion-vector",
+        level_of_parallelism: u32 = "lp",
+        pin_threads: u32 = "pin",
+        target_socket: i32 = "ss",
+        use_cpu_flags: u64,
+        channel_id: u32,
+        active_channel_count: u32,
+    }
+    "Tools" {
+        recon_enabled: u8,
+        enable_tf: u8 = "enable-tf",
+        enable_overlays: u8 = "enable-overlays",
+        tune: u8 = "tune",
+        superres_mode: u8 = "superres-mode",
+        superres_denom: u8 = "superres-denom",
+        superres_kf_denom: u8 = "superres-kf-denom",
+        superres_qthres: u8 = "superres-qthres",
+        superres_kf_qthres: u8 = "superres-kf-qthres",
+        superres_auto_search_type: u8,
+        resize_mode: u8 = "resize-mode",
+        resize_denom: u8 = "resize-denom",
+        resize_kf_denom: u8 = "resize-kf-denom",
+        film_grain_denoise_strength: u32 = "film-grain",
+        film_grain_denoise_apply: u8 = "film-grain-denoise",
+        fast_decode: u8 = "fast-decode",
+        enable_qm: u8 = "enable-qm",
+        min_qm_level: u8 = "qm-min",
+        max_qm_level: u8 = "qm-max",
+        min_chroma_qm_level: u8 = "chroma-qm-min",
+        max_chroma_qm_level: u8 = "chroma-qm-max",
+        force_key_frames: u8,
+        enable_dg: u8 = "enable-dg",
+        startup_mg_size: u8 = "startup-mg-size",
+        sharpness: i8 = "sharpness",
+        enable_variance_boost: u8 = "enable-variance-boost",
+        variance_boost_strength: u8 = "variance-boost-strength",
+        variance_octile: u8 = "variance-octile",
+        avif: u8 = "avif",
+        rtc: u8 = "rtc",
+        lossless: u8 = "lossless",
+        enable_roi_map: u8,
+        stat_report: u32 = "enable-stat-report",
+        multiply_keyint: u8,
+        qp_scale_compress_strength: u8 = "qp-scale-compress-strength",
+        adaptive_film_grain: u8 = "adaptive-film-grain",
+        tf_strength: u8 = "tf-strength",
+    }
+}
+
+impl EncoderSettings {
+    /// The library defaults with these settings applied on top.
+    pub fn to_configuration(&self) -> Result<Configuration> {
+        let mut cfg = *Encoder::builder()?.config();
+        self.apply_to(&mut cfg);
+        Ok(cfg)
+    }
+}
+
+impl From<&Configuration> for EncoderSettings {
+    fn from(cfg: &Configuration) -> Self {
+        Self::from_configuration(cfg)
+    }
+}
+
+/// Every field in the table, by section. The pointer fields and padding of
+/// [`Configuration`] are not included.
+pub fn fields() -> &'static [Field] {
+    FIELDS
+}
+
+/// The field set by the SvtAv1EncApp option `name` (without `--`).
+pub fn field(name: &str) -> Option<&'static Field> {
+    FIELDS.iter().find(|f| f.cli == Some(name))
+}
+
+/// Strips whitespace and one pair of surrounding double quotes.
+fn unquote(value: &str) -> &str {
+    let value = value.trim();
+    value
+        .strip_prefix('"')
+        .and_then(|v| v.strip_suffix('"'))
+        .unwrap_or(value)
+}
+
+/// Splits `--key value` / `--key=value` options, or `key=value:key=value`
+/// pairs, into keys and values.
+fn split_params(params: &str) -> Result<Vec<(&str, &str)>> {
+    let params = params.trim();
+    let mut pairs = Vec::new();
+    if !params.starts_with('-') {
+        for pair in params.split(':').filter(|p| !p.trim().is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or_else(|| Error::Parameter {
+                key: pair.trim().into(),
+                reason: "expected `key=value`".into(),
+            })?;
+            pairs.push((key.trim(), unquote(value)));
+        }
+        return Ok(pairs);
+    }
+
+    let mut tokens = params.split_whitespace().peekable();
+    while let Some(token) = tokens.next() {
+        let Some(option) = token.strip_prefix("--") else {
+            return Err(Error::Parameter {
+                key: token.into(),
+                reason: "expected an option starting with `--`".into(),
+            });
+        };
+        let (key, value) = match option.split_once('=') {
+            Some((key, value)) => (key, value),
+            None => match tokens.next_if(|t| !t.starts_with("--")) {
+                Some(value) => (option, value),
+                None => {
+                    return Err(Error::Parameter {
+                        key: option.into(),
+                        reason: "missing value".into(),
+                    })
+                }
+            },
+        };
+        pairs.push((key, unquote(value)));
+    }
+    Ok(pairs)
+}
+
+/// Applies one SvtAv1EncApp option.
+fn apply_param(cfg: &mut Configuration, key: &str, value: &str) -> Result<()> {
+    match key {
+        // Constant rate factor: the QP under the CRF rate control mode.
+        "crf" => {
+            field("qp").expect("qp is in the table").set(cfg, value)?;
+            cfg.rate_control_mode = RcMode::CqpOrCrf as u32;
+            Ok(())
+        }
+        _ => field(key)
+            .ok_or_else(|| Error::Parameter {
+                key: key.into(),
+                reason: "unknown parameter".into(),
+            })?
+            .set(cfg, value),
+    }
+}
+
+/// See [`ConfigExt::apply_params`](crate::config::ConfigExt::apply_params).
+pub fn apply_params(cfg: &mut Configuration, params: &str) -> Result<()> {
+    let mut updated = *cfg;
+    for (key, value) in split_params(params)? {
+        apply_param(&mut updated, key, value)?;
+    }
+    *cfg = updated;
+    Ok(())
+}
+
+/// See [`ConfigExt::to_params_string`](crate::config::ConfigExt::to_params_string).
+pub fn to_params_string(cfg: &Configuration) -> Result<String> {
+    let params = FIELDS
+        .iter()
+        .filter_map(|f| Some(format!("--{} {}", f.cli?, f.value(cfg))))
+        .collect::<Vec<_>>()
+        .join(" ");
+    // Read the options back over the defaults: a field that comes back
+    // different has no option or a value its option cannot spell.
+    let mut rebuilt = *Encoder::builder()?.config();
+    apply_params(&mut rebuilt, &params)?;
+    match FIELDS.iter().find(|f| (f.raw)(&rebuilt) != (f.raw)(cfg)) {
+        None => Ok(params),
+        Some(field) => Err(Error::Parameter {
+            key: field.key().into(),
+            reason: match field.cli {
+                Some(cli) => format!(
+                    "{} cannot be written as `--{cli} {}`",
+                    (field.raw)(cfg),
+                    field.value(cfg)
+                ),
+                None => format!(
+                    "{} differs from the library default {} and has no option",
+                    (field.raw)(cfg),
+                    (field.raw)(&rebuilt)
+                ),
+            },
+        }),
+    }
+}
+
+/// One field that differs between two configurations.
+#[derive(Clone, Debug)]
+pub struct FieldChange {
+    pub field: &'static Field,
+    /// Option name, or the C field name when the field has no option or
+    /// the option's units cannot show the difference (e.g. `tbr` values
+    /// that round to the same kbps).
+    pub key: &'static str,
+    pub old: String,
+    pub new: String,
+}
+
+impl std::fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} -> {}", self.key, self.old, self.new)
+    }
+}
+
+/// The fields in the table that differ between two configurations, in
+/// table order.
+///
+/// Displays as one `key: old -> new` line per change.
+#[derive(Clone, Debug, Default)]
+pub struct ConfigDiff {
+    changes: Vec<FieldChange>,
+}
+
+impl ConfigDiff {
+    /// Compares every field in the table of `old` and `new`.
+    pub fn between(old: &Configuration, new: &Configuration) -> Self {
+        let changes = FIELDS
+            .iter()
+            .filter_map(|field| {
+                let (raw_old, raw_new) = ((field.raw)(old), (field.raw)(new));
+                if raw_old == raw_new {
+                    return None;
+                }
+                let (cli_old, cli_new) = (field.value(old), field.value(new));
+                Some(match field.cli {
+                    Some(cli) if cli_old != cli_new => FieldChange {
+                        field,
+                        key: cli,
+                        old: cli_old,
+                        new: cli_new,
+                    },
+                    _ => FieldChange {
+                        field,
+                        key: field.name,
+                        old: raw_old,
+                        new: raw_new,
+                    },
+                })
+            })
+            .collect();
+        Self { changes }
+    }
+
+    pub fn changes(&self) -> &[FieldChange] {
+        &self.changes
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl std::fmt::Display for ConfigDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{change}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Readable dump of every field in the table of a configuration, from
+/// [`ConfigExt::summary`](crate::config::ConfigExt::summary).
+///
+/// Fields are grouped under section titles and listed by option name in
+/// the option's units; fields without an option use the C field name.
+#[derive(Clone, Copy, Debug)]
+pub struct Summary<'a>(pub &'a Configuration);
+
+impl std::fmt::Display for Summary<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let width = FIELDS
+            .iter()
+            .map(|field| field.key().len())
+            .max()
+            .unwrap_or(0);
+        let mut section = None;
+        for field in FIELDS {
+            if section != Some(field.section) {
+                if section.is_some() {
+                    writeln!(f)?;
+                }
+                writeln!(f, "{}:", field.section)?;
+                section = Some(field.section);
+            }
+            writeln!(f, "  {:width$}  {}", field.key(), field.value(self.0))?;
+        }
+        Ok(())
+    }
+}
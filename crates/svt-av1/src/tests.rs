@@ -7,8 +7,3414 @@ mod tests {
     #[test]
     #[cfg(feature = "decoder")]
     fn test_decoder_init() {
-        let (mut dec, cfg) = Decoder::init_default().expect("Failed to init decoder");
+        let (dec, _cfg) = Decoder::init_default().expect("Failed to init decoder");
 
         dec.init().expect("Failed to init decoder instance");
     }
+
+    #[cfg(feature = "decoder")]
+    mod decoder {
+        use super::fixtures;
+        use crate::config::ColorFormat;
+        use crate::decoder::{DecodedFrame, Decoder};
+        use crate::mock::dec::{MockDecoder, MockPicture};
+        use crate::Error;
+
+        fn picture(width: u32, height: u32, color_format: ColorFormat) -> MockPicture {
+            MockPicture {
+                width,
+                height,
+                bit_depth: 8,
+                color_format,
+            }
+        }
+
+        fn assert_planes(frame: &DecodedFrame, picture: &MockPicture, n: i64) {
+            let (ss_x, ss_y) = picture.color_format.subsampling();
+            for p in 0..picture.color_format.num_planes() {
+                let frame = frame.frame();
+                let (w, h) = if p == 0 {
+                    (picture.width, picture.height)
+                } else {
+                    (
+                        (picture.width + ss_x) >> ss_x,
+                        (picture.height + ss_y) >> ss_y,
+                    )
+                };
+                for y in 0..h as usize {
+                    let row = frame.row(p, y);
+                    assert_eq!(row.len(), w as usize);
+                    let expected: Vec<u8> = (0..w as usize)
+                        .map(|x| picture.sample(n, p, x, y) as u8)
+                        .collect();
+                    // No padding byte leaks in from past the picture width.
+                    assert_eq!(row, expected.as_slice(), "plane {p} row {y}");
+                }
+            }
+        }
+
+        fn lifecycle(mock: &MockDecoder) -> [usize; 4] {
+            ["init_handle", "init", "deinit", "deinit_handle"].map(|f| mock.calls.get(f))
+        }
+
+        #[test]
+        fn deinit_runs_once_and_only_after_init() {
+            let pic = picture(16, 16, ColorFormat::Yuv420);
+
+            let mock = MockDecoder::new(pic).arm();
+            drop(Decoder::init_default().unwrap());
+            assert_eq!(lifecycle(&mock), [1, 0, 0, 1]);
+
+            let mock = MockDecoder::new(pic).arm();
+            let (dec, _cfg) = Decoder::init_default().unwrap();
+            let dec = dec.init().unwrap();
+            assert_eq!(lifecycle(&mock), [1, 1, 0, 0]);
+            drop(dec);
+            assert_eq!(lifecycle(&mock), [1, 1, 1, 1]);
+
+            let mut failing = MockDecoder::new(pic);
+            failing.init_result = 0x80001005u32 as i32;
+            let mock = failing.arm();
+            let (dec, _cfg) = Decoder::init_default().unwrap();
+            assert!(matches!(dec.init(), Err(Error::Code(_))));
+            assert_eq!(lifecycle(&mock), [1, 1, 0, 1]);
+        }
+
+        /// Creates and drops thousands of decoders, alternating between
+        /// handles that were never started and running decoders, and checks
+        /// that every handle is torn down exactly once.
+        #[test]
+        fn lifecycle_stress_leaves_no_live_handles() {
+            let mock = MockDecoder::new(picture(16, 16, ColorFormat::Yuv420)).arm();
+            for i in 0..4000 {
+                MockDecoder::rearm(&mock);
+                let (dec, _cfg) = Decoder::init_default().unwrap();
+                assert_eq!(MockDecoder::live(&mock), 1);
+                if i % 2 == 1 {
+                    drop(dec.init().unwrap());
+                } else {
+                    drop(dec);
+                }
+                assert_eq!(MockDecoder::live(&mock), 0);
+            }
+            assert_eq!(lifecycle(&mock), [4000, 2000, 2000, 4000]);
+        }
+
+        #[test]
+        fn output_size_comes_from_the_sequence_header() {
+            let pic = picture(40, 30, ColorFormat::Yuv420);
+            let mock = MockDecoder::new(pic).arm();
+            let (dec, _cfg) = Decoder::init_default().unwrap();
+            let mut dec = dec.init().unwrap();
+
+            // The sequence header signals 64x48: output rows are 64 samples
+            // apart and the mock pads the 24 past the picture width.
+            let frames: Vec<_> = dec
+                .decode(&fixtures::temporal_unit(true, 1, 8))
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(frames.len(), 1);
+            let frame = &frames[0];
+            assert_eq!((frame.width(), frame.height()), (40, 30));
+            assert_eq!(frame.color_format(), ColorFormat::Yuv420);
+            assert_planes(frame, &pic, 0);
+
+            // Later temporal units without a sequence header reuse the size.
+            let frames: Vec<_> = dec
+                .decode(&fixtures::temporal_unit(false, 2, 8))
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_planes(&frames[0], &pic, 1);
+            assert_eq!(mock.calls.get("get_picture"), 4);
+        }
+
+        #[test]
+        fn strided_planes_of_every_format_are_copied() {
+            for format in [
+                ColorFormat::Yuv400,
+                ColorFormat::Yuv420,
+                ColorFormat::Yuv422,
+                ColorFormat::Yuv444,
+            ] {
+                let pic = picture(33, 17, format);
+                MockDecoder::new(pic).arm();
+                let (dec, mut cfg) = Decoder::init_default().unwrap();
+                cfg.max_picture_width = 48;
+                cfg.max_picture_height = 24;
+                let mut dec = dec;
+                dec.set_parameter(&cfg).unwrap();
+                let mut dec = dec.init().unwrap();
+                let frames: Vec<_> = dec
+                    .decode(&fixtures::temporal_unit(false, 1, 8))
+                    .collect::<Result<_, _>>()
+                    .unwrap();
+                assert_eq!((frames[0].width(), frames[0].height()), (33, 17));
+                assert_eq!(frames[0].stride(0), 33);
+                assert_planes(&frames[0], &pic, 0);
+            }
+        }
+
+        #[test]
+        fn unknown_maximum_size_is_rejected_before_the_library_writes() {
+            let mock = MockDecoder::new(picture(64, 48, ColorFormat::Yuv420)).arm();
+            let (dec, _cfg) = Decoder::init_default().unwrap();
+            let mut dec = dec.init().unwrap();
+            let result: Vec<_> = dec.decode(&fixtures::temporal_unit(false, 1, 8)).collect();
+            assert!(
+                matches!(result.as_slice(), [Err(Error::InvalidArgument(_))]),
+                "{result:?}"
+            );
+            assert_eq!(mock.calls.get("get_picture"), 0);
+        }
+    }
+
+    mod raw_yuv {
+        use crate::config::{BitDepth, ColorFormat};
+        use crate::yuv::{PixelLayout, RawYuvFormat, RawYuvReader};
+        use std::io::Cursor;
+
+        const ALL_FORMATS: [ColorFormat; 4] = [
+            ColorFormat::Yuv400,
+            ColorFormat::Yuv420,
+            ColorFormat::Yuv422,
+            ColorFormat::Yuv444,
+        ];
+
+        fn le16(v: &[u16]) -> Vec<u8> {
+            v.iter().flat_map(|s| s.to_le_bytes()).collect()
+        }
+
+        #[test]
+        fn i420_with_row_padding() {
+            // 4x2 luma with 2 bytes of padding, 2x1 chroma with 1 byte of padding.
+            #[rustfmt::skip]
+            let data = [
+                1, 2, 3, 4, 0xEE, 0xEE,
+                5, 6, 7, 8, 0xEE, 0xEE,
+                10, 11, 0xEE,
+                20, 21, 0xEE,
+            ];
+            let fmt =
+                RawYuvFormat::new(4, 2, PixelLayout::I420, BitDepth::Eight).with_strides(6, 3);
+            assert_eq!(fmt.frame_size(), data.len());
+            let mut frame = fmt.new_frame();
+            fmt.unpack(&data, &mut frame).unwrap();
+            assert_eq!(frame.plane(0), &[1, 2, 3, 4, 5, 6, 7, 8]);
+            assert_eq!(frame.plane(1), &[10, 11]);
+            assert_eq!(frame.plane(2), &[20, 21]);
+        }
+
+        #[test]
+        fn nv12_deinterleaves_chroma() {
+            #[rustfmt::skip]
+            let data = [
+                1, 2, 3, 4,
+                5, 6, 7, 8,
+                10, 20, 11, 21,
+            ];
+            let fmt = RawYuvFormat::new(4, 2, PixelLayout::Nv12, BitDepth::Eight);
+            let mut frame = fmt.new_frame();
+            fmt.unpack(&data, &mut frame).unwrap();
+            assert_eq!(frame.plane(0), &[1, 2, 3, 4, 5, 6, 7, 8]);
+            assert_eq!(frame.plane(1), &[10, 11]);
+            assert_eq!(frame.plane(2), &[20, 21]);
+        }
+
+        #[test]
+        fn p010_shifts_msb_aligned_samples() {
+            let luma = [0x3FFu16, 0, 512, 1, 2, 3, 4, 5];
+            let chroma = [100u16, 200, 300, 400];
+            let mut words: Vec<u16> = luma.iter().map(|v| v << 6).collect();
+            words.extend(chroma.iter().map(|v| v << 6));
+            let mut data = le16(&words);
+            // Low bits of MSB-aligned samples are ignored.
+            data[0] |= 0x3F;
+
+            let fmt = RawYuvFormat::new(4, 2, PixelLayout::P010, BitDepth::Ten);
+            let mut frame = fmt.new_frame();
+            fmt.unpack(&data, &mut frame).unwrap();
+            assert_eq!(frame.plane(0), le16(&luma).as_slice());
+            assert_eq!(frame.plane(1), le16(&[100, 300]).as_slice());
+            assert_eq!(frame.plane(2), le16(&[200, 400]).as_slice());
+        }
+
+        #[test]
+        fn i422_ten_bit_little_endian() {
+            let luma = [1u16, 2, 3, 4, 1020, 1021, 1022, 1023];
+            let u = [7u16, 8, 9, 10];
+            let v = [11u16, 12, 13, 14];
+            let mut data = le16(&luma);
+            data.extend(le16(&u));
+            data.extend(le16(&v));
+            let fmt = RawYuvFormat::new(4, 2, PixelLayout::I422, BitDepth::Ten);
+            let mut frame = fmt.new_frame();
+            fmt.unpack(&data, &mut frame).unwrap();
+            assert_eq!(frame.plane(0), le16(&luma).as_slice());
+            assert_eq!(frame.plane(1), le16(&u).as_slice());
+            assert_eq!(frame.plane(2), le16(&v).as_slice());
+        }
+
+        #[test]
+        fn odd_dimensions_round_chroma_up() {
+            let fmt = RawYuvFormat::new(5, 3, PixelLayout::I420, BitDepth::Eight);
+            assert_eq!(fmt.frame_size(), 15 + 2 * 6);
+            let frame = fmt.new_frame();
+            assert_eq!((frame.plane_width(1), frame.plane_height(1)), (3, 2));
+        }
+
+        // Builds a raw buffer for `layout` from known per-plane sample values and
+        // checks that unpacking reproduces them exactly.
+        fn check_layout(layout: PixelLayout, depth: BitDepth, padding: usize) {
+            let (w, h) = (6u32, 4u32);
+            let fmt = RawYuvFormat::new(w, h, layout, depth);
+            let fmt = fmt.with_strides(fmt.luma_stride() + padding, fmt.chroma_stride() + padding);
+            let mut expected = fmt.new_frame();
+            let max = (1u32 << depth.bits()) - 1;
+            let sample =
+                |p: usize, x: usize, y: usize| ((p * 97 + y * 31 + x * 7) as u32 % max) as u16;
+            let shift = if layout.is_msb_aligned() {
+                16 - depth.bits()
+            } else {
+                0
+            };
+            let in_bps = fmt.bytes_per_sample();
+            let push = |buf: &mut Vec<u8>, v: u16| {
+                if in_bps == 1 {
+                    buf.push(v as u8);
+                } else {
+                    buf.extend((v << shift).to_le_bytes());
+                }
+            };
+
+            let mut data = Vec::new();
+            for y in 0..h as usize {
+                let row_start = data.len();
+                for x in 0..w as usize {
+                    push(&mut data, sample(0, x, y));
+                }
+                data.resize(row_start + fmt.luma_stride(), 0xAB);
+            }
+            let (cw, ch) = (
+                expected.plane_width(1) as usize,
+                expected.plane_height(1) as usize,
+            );
+            if layout.is_semi_planar() {
+                for y in 0..ch {
+                    let row_start = data.len();
+                    for x in 0..cw {
+                        push(&mut data, sample(1, x, y));
+                        push(&mut data, sample(2, x, y));
+                    }
+                    data.resize(row_start + fmt.chroma_stride(), 0xAB);
+                }
+            } else if layout != PixelLayout::Yuv400 {
+                for p in 1..3 {
+                    for y in 0..ch {
+                        let row_start = data.len();
+                        for x in 0..cw {
+                            push(&mut data, sample(p, x, y));
+                        }
+                        data.resize(row_start + fmt.chroma_stride(), 0xAB);
+                    }
+                }
+            }
+            assert_eq!(data.len(), fmt.frame_size(), "{layout:?}");
+
+            for p in 0..expected.num_planes() {
+                for y in 0..expected.plane_height(p) as usize {
+                    let bps = expected.bytes_per_sample();
+                    let row = expected.row_mut(p, y);
+                    for (x, out) in row.chunks_exact_mut(bps).enumerate() {
+                        let v = sample(p, x, y);
+                        if bps == 1 {
+                            out[0] = v as u8;
+                        } else {
+                            out.copy_from_slice(&v.to_le_bytes());
+                        }
+                    }
+                }
+            }
+
+            let mut reader = RawYuvReader::new(Cursor::new(data), fmt).unwrap();
+            let frame = reader.read_frame().unwrap().expect("one frame");
+            assert_eq!(frame, expected, "{layout:?} {depth:?}");
+            assert!(reader.read_frame().unwrap().is_none());
+        }
+
+        #[test]
+        fn every_color_format_is_bit_exact() {
+            for fmt in ALL_FORMATS {
+                for padding in [0, 3] {
+                    check_layout(PixelLayout::planar(fmt), BitDepth::Eight, padding);
+                    check_layout(PixelLayout::planar(fmt), BitDepth::Ten, padding);
+                    check_layout(PixelLayout::planar(fmt), BitDepth::Twelve, padding);
+                    check_layout(PixelLayout::semi_planar(fmt), BitDepth::Eight, padding);
+                    check_layout(PixelLayout::semi_planar_msb(fmt), BitDepth::Ten, padding);
+                    check_layout(PixelLayout::semi_planar_msb(fmt), BitDepth::Twelve, padding);
+                }
+            }
+        }
+
+        #[test]
+        fn reader_assigns_pts_and_rejects_truncation() {
+            let fmt = RawYuvFormat::new(2, 2, PixelLayout::I420, BitDepth::Eight);
+            let mut data = vec![0u8; fmt.frame_size() * 2];
+            data.push(1);
+            let mut reader = RawYuvReader::new(Cursor::new(data), fmt).unwrap();
+            assert_eq!(reader.next().unwrap().unwrap().pts, 0);
+            assert_eq!(reader.next().unwrap().unwrap().pts, 1);
+            assert!(matches!(reader.next(), Some(Err(crate::Error::Io(_)))));
+        }
+
+        #[test]
+        fn reader_fills_pooled_frames_in_place() {
+            use crate::frame::{FramePool, PLANE_ALIGN};
+
+            let fmt = RawYuvFormat::new(5, 3, PixelLayout::I420, BitDepth::Ten);
+            // One and a half pictures: the second read hits a truncated luma plane.
+            let data: Vec<u16> = (0..fmt.frame_size() as u16 * 3 / 4).collect();
+            let data = le16(&data);
+            let mut expected = fmt.new_frame();
+            fmt.unpack(&data[..fmt.frame_size()], &mut expected)
+                .unwrap();
+
+            let pool = FramePool::new(5, 3, BitDepth::Ten, ColorFormat::Yuv420);
+            let mut reader = RawYuvReader::new(Cursor::new(data), fmt).unwrap();
+            let mut frame = pool.get();
+            assert!(reader.read_into(&mut frame).unwrap());
+            for p in 0..3 {
+                assert_eq!(frame.plane(p), expected.plane(p));
+                assert_eq!(frame.plane(p).as_ptr() as usize % PLANE_ALIGN, 0);
+            }
+            assert!(matches!(
+                reader.read_into(&mut frame),
+                Err(crate::Error::Io(_))
+            ));
+        }
+
+        #[test]
+        fn rejects_short_stride() {
+            let fmt =
+                RawYuvFormat::new(4, 2, PixelLayout::Nv12, BitDepth::Eight).with_strides(4, 2);
+            assert!(RawYuvReader::new(Cursor::new(Vec::new()), fmt).is_err());
+        }
+    }
+
+    mod convert {
+        use crate::config::{
+            BitDepth, ChromaSamplePosition, ColorFormat, ColorRange, MatrixCoefficients,
+        };
+        use crate::convert::{ColorConversion, RgbFormat, RgbLayout};
+        use crate::frame::Frame;
+
+        fn conv(matrix: MatrixCoefficients, range: ColorRange) -> ColorConversion {
+            ColorConversion::new(matrix, range, ChromaSamplePosition::Vertical)
+        }
+
+        fn sample(frame: &Frame, p: usize, x: usize, y: usize) -> u16 {
+            let row = frame.row(p, y);
+            match frame.bytes_per_sample() {
+                1 => row[x] as u16,
+                _ => u16::from_le_bytes([row[2 * x], row[2 * x + 1]]),
+            }
+        }
+
+        fn yuv_of(c: &ColorConversion, rgb: [u8; 3], depth: BitDepth) -> [u16; 3] {
+            let fmt = RgbFormat::new(1, 1, RgbLayout::Rgb24);
+            let frame = c
+                .convert_new(&fmt, &rgb, depth, ColorFormat::Yuv444)
+                .unwrap();
+            [0, 1, 2].map(|p| sample(&frame, p, 0, 0))
+        }
+
+        #[test]
+        fn reference_colors() {
+            let studio = conv(MatrixCoefficients::Bt709, ColorRange::Studio);
+            assert_eq!(yuv_of(&studio, [0, 0, 0], BitDepth::Eight), [16, 128, 128]);
+            assert_eq!(yuv_of(&studio, [255; 3], BitDepth::Eight), [235, 128, 128]);
+            assert_eq!(
+                yuv_of(&studio, [255, 0, 0], BitDepth::Eight),
+                [63, 102, 240]
+            );
+            assert_eq!(yuv_of(&studio, [255; 3], BitDepth::Ten), [940, 512, 512]);
+
+            let full = conv(MatrixCoefficients::Bt601, ColorRange::Full);
+            assert_eq!(yuv_of(&full, [255, 0, 0], BitDepth::Eight), [76, 85, 255]);
+            assert_eq!(
+                yuv_of(&full, [255; 3], BitDepth::Twelve),
+                [4095, 2048, 2048]
+            );
+
+            let bt2020 = conv(MatrixCoefficients::Bt2020Ncl, ColorRange::Studio);
+            assert_eq!(yuv_of(&bt2020, [0, 0, 255], BitDepth::Ten), [116, 960, 476]);
+        }
+
+        #[test]
+        fn layouts_agree() {
+            let (w, h, pad) = (5, 3, 3);
+            let rgb: Vec<[u8; 3]> = (0..w * h)
+                .map(|i| [(i * 37) as u8, (i * 91 + 13) as u8, (255 - i * 17) as u8])
+                .collect();
+            let pack = |layout: RgbLayout| {
+                let mut out = Vec::new();
+                for y in 0..h {
+                    for &[r, g, b] in &rgb[y * w..][..w] {
+                        match layout {
+                            RgbLayout::Rgb24 => out.extend([r, g, b]),
+                            RgbLayout::Rgba => out.extend([r, g, b, 0x80]),
+                            RgbLayout::Bgra => out.extend([b, g, r, 0xff]),
+                            RgbLayout::Rgb48 => {
+                                for v in [r, g, b] {
+                                    out.extend((v as u16 * 257).to_le_bytes());
+                                }
+                            }
+                        }
+                    }
+                    out.extend([0u8; 3][..pad].iter());
+                }
+                out
+            };
+
+            let c = ColorConversion::default();
+            let layouts = [
+                RgbLayout::Rgb24,
+                RgbLayout::Rgba,
+                RgbLayout::Bgra,
+                RgbLayout::Rgb48,
+            ];
+            let frames: Vec<Frame> = layouts
+                .into_iter()
+                .map(|layout| {
+                    let fmt = RgbFormat::new(w as u32, h as u32, layout)
+                        .with_stride(w * layout.bytes_per_pixel() + pad);
+                    c.convert_new(&fmt, &pack(layout), BitDepth::Ten, ColorFormat::Yuv420)
+                        .unwrap()
+                })
+                .collect();
+            for frame in &frames[1..] {
+                assert_eq!(frame, &frames[0]);
+            }
+        }
+
+        #[test]
+        fn every_color_format_and_odd_size() {
+            let c = ColorConversion::default();
+            let fill = [200, 30, 90];
+            let rgb = RgbFormat::new(7, 5, RgbLayout::Rgb24);
+            let data = fill.repeat(35);
+            for depth in [BitDepth::Eight, BitDepth::Ten, BitDepth::Twelve] {
+                let expected = yuv_of(&c, fill, depth);
+                for fmt in [
+                    ColorFormat::Yuv400,
+                    ColorFormat::Yuv420,
+                    ColorFormat::Yuv422,
+                    ColorFormat::Yuv444,
+                ] {
+                    let frame = c.convert_new(&rgb, &data, depth, fmt).unwrap();
+                    for (p, &want) in expected.iter().enumerate().take(fmt.num_planes()) {
+                        for y in 0..frame.plane_height(p) as usize {
+                            for x in 0..frame.plane_width(p) as usize {
+                                assert_eq!(sample(&frame, p, x, y), want, "{fmt:?} {depth:?}");
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn chroma_siting() {
+            // Red at even rows and columns, blue elsewhere: the chroma sample
+            // at (0, 0) weighs the red pixel according to its siting.
+            let (w, h) = (4, 4);
+            let mut data = Vec::new();
+            for y in 0..h {
+                for x in 0..w {
+                    let red = x % 2 == 0 && y % 2 == 0;
+                    data.extend(if red { [255, 0, 0] } else { [0, 0, 255] });
+                }
+            }
+            let rgb = RgbFormat::new(w, h, RgbLayout::Rgb24);
+            let cr = |pos| {
+                let c = ColorConversion::new(MatrixCoefficients::Bt709, ColorRange::Full, pos);
+                let frame = c
+                    .convert_new(&rgb, &data, BitDepth::Eight, ColorFormat::Yuv420)
+                    .unwrap();
+                sample(&frame, 2, 0, 0) as f32
+            };
+            let full = conv(MatrixCoefficients::Bt709, ColorRange::Full);
+            let cr_red = yuv_of(&full, [255, 0, 0], BitDepth::Eight)[2] as f32;
+            let cr_blue = yuv_of(&full, [0, 0, 255], BitDepth::Eight)[2] as f32;
+            let mix = |red_weight: f32| cr_blue + (cr_red - cr_blue) * red_weight;
+
+            // [1, 2, 1] on both axes, with the left and top edges clamped.
+            assert!((cr(ChromaSamplePosition::Colocated) - mix(9.0 / 16.0)).abs() <= 1.0);
+            // [1, 2, 1] horizontally, between rows vertically.
+            assert!((cr(ChromaSamplePosition::Vertical) - mix(3.0 / 8.0)).abs() <= 1.0);
+            // Centre of the 2x2 block.
+            assert!((cr(ChromaSamplePosition::Unknown) - mix(1.0 / 4.0)).abs() <= 1.0);
+        }
+
+        #[test]
+        fn rejects_mismatched_input() {
+            let c = ColorConversion::default();
+            let rgb = RgbFormat::new(4, 2, RgbLayout::Bgra);
+            let mut frame = Frame::new(4, 2, BitDepth::Eight, ColorFormat::Yuv420);
+            assert!(c.convert(&rgb, &[0; 31], &mut frame).is_err());
+            assert!(c
+                .convert(&rgb.with_stride(8), &[0; 32], &mut frame)
+                .is_err());
+            let mut small = Frame::new(2, 2, BitDepth::Eight, ColorFormat::Yuv420);
+            assert!(c.convert(&rgb, &[0; 32], &mut small).is_err());
+            c.convert(&rgb, &[0; 32], &mut frame).unwrap();
+        }
+
+        #[cfg(feature = "encoder")]
+        #[test]
+        fn signals_conversion_in_configuration() {
+            let mut cfg: crate::encoder::Configuration = unsafe { std::mem::zeroed() };
+            ColorConversion::new(
+                MatrixCoefficients::Bt2020Ncl,
+                ColorRange::Full,
+                ChromaSamplePosition::Colocated,
+            )
+            .apply_to(&mut cfg);
+            assert_eq!(cfg.matrix_coefficients, 9);
+            assert_eq!(cfg.color_range, 1);
+            assert_eq!(cfg.chroma_sample_position, 2);
+        }
+    }
+
+    mod scale {
+        use crate::config::{BitDepth, ChromaSamplePosition, ColorFormat};
+        use crate::frame::Frame;
+        use crate::scale::{Filter, Scaler};
+
+        const FILTERS: [Filter; 3] = [Filter::Bilinear, Filter::Bicubic, Filter::Lanczos3];
+
+        fn get(frame: &Frame, p: usize, x: usize, y: usize) -> u16 {
+            let row = frame.row(p, y);
+            match frame.bytes_per_sample() {
+                1 => row[x] as u16,
+                _ => u16::from_le_bytes([row[2 * x], row[2 * x + 1]]),
+            }
+        }
+
+        fn set(frame: &mut Frame, p: usize, x: usize, y: usize, v: u16) {
+            let bps = frame.bytes_per_sample();
+            let row = frame.row_mut(p, y);
+            match bps {
+                1 => row[x] = v as u8,
+                _ => row[2 * x..2 * x + 2].copy_from_slice(&v.to_le_bytes()),
+            }
+        }
+
+        fn filled(
+            width: u32,
+            height: u32,
+            depth: BitDepth,
+            fmt: ColorFormat,
+            f: impl Fn(usize, usize, usize) -> u16,
+        ) -> Frame {
+            let mut frame = Frame::new(width, height, depth, fmt);
+            for p in 0..fmt.num_planes() {
+                for y in 0..frame.plane_height(p) as usize {
+                    for x in 0..frame.plane_width(p) as usize {
+                        set(&mut frame, p, x, y, f(p, x, y));
+                    }
+                }
+            }
+            frame
+        }
+
+        #[test]
+        fn odd_sizes_in_every_format() {
+            let sizes = [
+                (191, 107, 85, 47),
+                (7, 5, 3, 9),
+                (1, 1, 2, 3),
+                (64, 36, 1, 1),
+            ];
+            for fmt in [
+                ColorFormat::Yuv400,
+                ColorFormat::Yuv420,
+                ColorFormat::Yuv422,
+                ColorFormat::Yuv444,
+            ] {
+                for depth in [BitDepth::Eight, BitDepth::Ten] {
+                    let level = |p: usize| [700u16, 300, 900][p] >> (10 - depth.bits());
+                    for (sw, sh, dw, dh) in sizes {
+                        let mut src = filled(sw, sh, depth, fmt, |p, _, _| level(p));
+                        src.pts = 42;
+                        for filter in FILTERS {
+                            let dst = Scaler::new(filter).scale_to(&src, dw, dh).unwrap();
+                            assert_eq!((dst.width(), dst.height(), dst.pts), (dw, dh, 42));
+                            assert_eq!(dst.color_format(), fmt);
+                            assert_eq!(dst.bit_depth(), depth);
+                            for p in 0..fmt.num_planes() {
+                                let (cw, ch) = (dst.plane_width(p), dst.plane_height(p));
+                                let (ssx, ssy) = if p == 0 { (0, 0) } else { fmt.subsampling() };
+                                assert_eq!((cw, ch), ((dw + ssx) >> ssx, (dh + ssy) >> ssy));
+                                for y in 0..ch as usize {
+                                    for x in 0..cw as usize {
+                                        assert_eq!(
+                                            get(&dst, p, x, y),
+                                            level(p),
+                                            "{fmt:?} {filter:?}"
+                                        );
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn same_size_is_identity() {
+            let src = filled(9, 7, BitDepth::Ten, ColorFormat::Yuv420, |p, x, y| {
+                ((x * 131 + y * 71 + p * 17) % 1024) as u16
+            });
+            for filter in FILTERS {
+                for pos in [
+                    ChromaSamplePosition::Unknown,
+                    ChromaSamplePosition::Vertical,
+                    ChromaSamplePosition::Colocated,
+                ] {
+                    let scaler = Scaler::new(filter).with_chroma_position(pos);
+                    assert_eq!(scaler.scale_to(&src, 9, 7).unwrap(), src);
+                }
+            }
+        }
+
+        #[test]
+        fn chroma_follows_siting() {
+            // A horizontal ramp in the chroma plane, halved in width. Co-sited
+            // chroma sample `i` lands on source chroma position `2i + 0.25`;
+            // centred chroma lands on `2i + 0.5`.
+            let src = filled(64, 8, BitDepth::Ten, ColorFormat::Yuv420, |p, x, _| {
+                if p == 0 {
+                    512
+                } else {
+                    8 * x as u16
+                }
+            });
+            let at = |pos| {
+                let scaler = Scaler::new(Filter::Bilinear).with_chroma_position(pos);
+                let dst = scaler.scale_to(&src, 32, 8).unwrap();
+                get(&dst, 1, 4, 1)
+            };
+            assert_eq!(at(ChromaSamplePosition::Vertical), 8 * 8 + 2);
+            assert_eq!(at(ChromaSamplePosition::Colocated), 8 * 8 + 2);
+            assert_eq!(at(ChromaSamplePosition::Unknown), 8 * 8 + 4);
+
+            // Luma keeps its ramp under every kernel away from the edges.
+            let ramp = filled(64, 4, BitDepth::Eight, ColorFormat::Yuv400, |_, x, _| {
+                (x * 4) as u16
+            });
+            for filter in FILTERS {
+                let dst = Scaler::new(filter).scale_to(&ramp, 32, 4).unwrap();
+                // Output sample 10 is centred on source position 20.5.
+                assert_eq!(get(&dst, 0, 10, 0), 82, "{filter:?}");
+            }
+        }
+
+        #[test]
+        fn rejects_format_changes_and_empty_frames() {
+            let src = Frame::new(8, 8, BitDepth::Eight, ColorFormat::Yuv420);
+            let scaler = Scaler::new(Filter::Lanczos3);
+            let mut dst = Frame::new(4, 4, BitDepth::Ten, ColorFormat::Yuv420);
+            assert!(scaler.scale(&src, &mut dst).is_err());
+            let mut dst = Frame::new(4, 4, BitDepth::Eight, ColorFormat::Yuv444);
+            assert!(scaler.scale(&src, &mut dst).is_err());
+            assert!(scaler.scale_to(&src, 0, 4).is_err());
+        }
+    }
+
+    /// Hand-built AV1 bitstream pieces shared by the container and OBU tests,
+    /// and the encoder configuration shared by the encoding tests.
+    pub(crate) mod fixtures {
+        #[derive(Default)]
+        pub struct BitWriter {
+            pub bytes: Vec<u8>,
+            bits: usize,
+        }
+
+        impl BitWriter {
+            pub fn put(&mut self, value: u64, n: u32) {
+                for i in (0..n).rev() {
+                    if self.bits.is_multiple_of(8) {
+                        self.bytes.push(0);
+                    }
+                    let bit = ((value >> i) & 1) as u8;
+                    *self.bytes.last_mut().unwrap() |= bit << (7 - self.bits % 8);
+                    self.bits += 1;
+                }
+            }
+
+            /// Appends `trailing_bits()` and returns the payload.
+            pub fn finish(mut self) -> Vec<u8> {
+                self.put(1, 1);
+                while !self.bits.is_multiple_of(8) {
+                    self.put(0, 1);
+                }
+                self.bytes
+            }
+        }
+
+        pub fn obu(obu_type: u8, payload: &[u8]) -> Vec<u8> {
+            let mut out = vec![(obu_type << 3) | 0x02];
+            crate::obu::write_leb128(&mut out, payload.len() as u64);
+            out.extend_from_slice(payload);
+            out
+        }
+
+        /// Sequence header payload as emitted for a main-profile 8-bit 4:2:0
+        /// stream with order hints (SVT-AV1 defaults), level 4.0.
+        pub fn sequence_header_payload(width: u32, height: u32) -> Vec<u8> {
+            let mut w = BitWriter::default();
+            w.put(0, 3); // seq_profile
+            w.put(0, 1); // still_picture
+            w.put(0, 1); // reduced_still_picture_header
+            w.put(0, 1); // timing_info_present_flag
+            w.put(0, 1); // initial_display_delay_present_flag
+            w.put(0, 5); // operating_points_cnt_minus_1
+            w.put(0, 12); // operating_point_idc[0]
+            w.put(8, 5); // seq_level_idx[0] = 4.0
+            w.put(0, 1); // seq_tier[0]
+            w.put(15, 4); // frame_width_bits_minus_1
+            w.put(15, 4); // frame_height_bits_minus_1
+            w.put(width as u64 - 1, 16);
+            w.put(height as u64 - 1, 16);
+            w.put(0, 1); // frame_id_numbers_present_flag
+            w.put(0, 1); // use_128x128_superblock
+            w.put(1, 1); // enable_filter_intra
+            w.put(1, 1); // enable_intra_edge_filter
+            w.put(0, 1); // enable_interintra_compound
+            w.put(0, 1); // enable_masked_compound
+            w.put(0, 1); // enable_warped_motion
+            w.put(0, 1); // enable_dual_filter
+            w.put(1, 1); // enable_order_hint
+            w.put(0, 1); // enable_jnt_comp
+            w.put(1, 1); // enable_ref_frame_mvs
+            w.put(1, 1); // seq_choose_screen_content_tools
+            w.put(1, 1); // seq_choose_integer_mv
+            w.put(6, 3); // order_hint_bits_minus_1
+            w.put(0, 1); // enable_superres
+            w.put(1, 1); // enable_cdef
+            w.put(1, 1); // enable_restoration
+            w.put(0, 1); // high_bitdepth
+            w.put(0, 1); // mono_chrome
+            w.put(0, 1); // color_description_present_flag
+            w.put(0, 1); // color_range
+            w.put(0, 2); // chroma_sample_position
+            w.put(0, 1); // separate_uv_delta_q
+            w.put(0, 1); // film_grain_params_present
+            w.finish()
+        }
+
+        pub fn sequence_header_obu(width: u32, height: u32) -> Vec<u8> {
+            obu(1, &sequence_header_payload(width, height))
+        }
+
+        /// A temporal unit: temporal delimiter, optional sequence header and an
+        /// opaque frame OBU whose payload is `tag` repeated.
+        pub fn temporal_unit(with_sequence_header: bool, tag: u8, len: usize) -> Vec<u8> {
+            let mut out = obu(2, &[]);
+            if with_sequence_header {
+                out.extend(sequence_header_obu(64, 48));
+            }
+            out.extend(obu(6, &vec![tag; len]));
+            out
+        }
+
+        /// Library defaults for an 8-bit 4:2:0 `width`x`height` picture at
+        /// 30 fps.
+        #[cfg(feature = "encoder")]
+        pub fn encoder_config(width: u32, height: u32) -> crate::encoder::Configuration {
+            use crate::config::{BitDepth, ColorFormat, ConfigExt};
+
+            let mut cfg = *crate::encoder::Encoder::builder().unwrap().config();
+            cfg.set_resolution(width, height)
+                .set_frame_rate(30, 1)
+                .set_bit_depth(BitDepth::Eight)
+                .set_color_format(ColorFormat::Yuv420);
+            cfg
+        }
+    }
+
+    mod scenecut {
+        use crate::config::{BitDepth, ColorFormat};
+        use crate::frame::Frame;
+        use crate::scenecut::{compare, SceneDetector};
+        use crate::Result;
+
+        /// A ramp scrolling by 3 samples per frame on top of a per-scene
+        /// brightness, so every frame moves but only scene changes shift the
+        /// histogram.
+        fn frame(base: u16, t: usize, depth: BitDepth) -> Frame {
+            let mut frame = Frame::new(96, 64, depth, ColorFormat::Yuv420);
+            let shift = depth.bits() - 8;
+            for y in 0..64 {
+                let bps = frame.bytes_per_sample();
+                let row = frame.row_mut(0, y);
+                for x in 0..96 {
+                    let v = (base + ((x + 3 * t) % 48) as u16) << shift;
+                    match bps {
+                        1 => row[x] = v as u8,
+                        _ => row[2 * x..2 * x + 2].copy_from_slice(&v.to_le_bytes()),
+                    }
+                }
+            }
+            frame
+        }
+
+        /// Scenes of the given lengths and brightnesses, back to back.
+        fn sequence(scenes: &[(usize, u16)], depth: BitDepth) -> Vec<Result<Frame>> {
+            let mut frames = Vec::new();
+            for &(len, base) in scenes {
+                for _ in 0..len {
+                    frames.push(Ok(frame(base, frames.len(), depth)));
+                }
+            }
+            frames
+        }
+
+        const SCENES: [(usize, u16); 4] = [(10, 20), (15, 120), (8, 200), (12, 60)];
+
+        #[test]
+        fn finds_known_cuts_through_motion() {
+            let mut detector = SceneDetector::new();
+            let cuts = detector.detect(sequence(&SCENES, BitDepth::Eight)).unwrap();
+            assert_eq!(cuts, [10, 25, 33]);
+            assert_eq!(detector.frames(), 45);
+
+            // Bit depth does not change the outcome.
+            let mut detector = SceneDetector::new();
+            let cuts = detector.detect(sequence(&SCENES, BitDepth::Ten)).unwrap();
+            assert_eq!(cuts, [10, 25, 33]);
+        }
+
+        #[test]
+        fn scene_length_bounds() {
+            // A 8-frame scene is too short for a minimum of 9.
+            let mut detector = SceneDetector::new().with_scene_len(9, usize::MAX);
+            assert_eq!(
+                detector.detect(sequence(&SCENES, BitDepth::Eight)).unwrap(),
+                [10, 25]
+            );
+
+            let mut detector = SceneDetector::new().with_scene_len(2, 6);
+            let cuts = detector.detect(sequence(&SCENES, BitDepth::Eight)).unwrap();
+            assert_eq!(cuts, [6, 10, 16, 22, 25, 31, 33, 39]);
+        }
+
+        #[test]
+        fn both_metrics_must_agree() {
+            // Dark and bright halves swapping sides keep the histogram but
+            // change every sample.
+            let mut a = Frame::new(96, 64, BitDepth::Eight, ColorFormat::Yuv420);
+            for y in 0..64 {
+                a.row_mut(0, y)[..48].fill(20);
+                a.row_mut(0, y)[48..].fill(220);
+            }
+            let mut b = a.clone();
+            for y in 0..64 {
+                b.row_mut(0, y).reverse();
+            }
+            let score = compare(&a, &b).unwrap();
+            assert!(score.histogram < 0.05 && score.sad > 0.1, "{score:?}");
+
+            let frames = [a.clone(), a, b.clone(), b];
+            let detector = SceneDetector::new().with_scene_len(1, usize::MAX);
+            assert!(detector
+                .clone()
+                .detect(frames.clone().map(Ok))
+                .unwrap()
+                .is_empty());
+            let mut detector = detector.with_thresholds(0.0, 0.1);
+            assert_eq!(detector.detect(frames.map(Ok)).unwrap(), [2]);
+        }
+
+        #[test]
+        fn rejects_size_changes_and_empty_bounds() {
+            let mut detector = SceneDetector::new();
+            detector.push(&frame(0, 0, BitDepth::Eight)).unwrap();
+            let other = Frame::new(48, 64, BitDepth::Eight, ColorFormat::Yuv420);
+            assert!(detector.push(&other).is_err());
+            let mut detector = SceneDetector::new().with_scene_len(5, 4);
+            assert!(detector.push(&other).is_err());
+            assert!(compare(&other, &frame(0, 0, BitDepth::Eight)).is_err());
+        }
+
+        #[cfg(all(feature = "encoder", not(loom)))]
+        #[test]
+        fn drives_chunks_and_keyframes() {
+            use super::fixtures;
+            use crate::chunked::{ChunkSplit, ChunkedEncoder};
+            use crate::encoder::KeyframePolicy;
+
+            let mut detector = SceneDetector::new().with_scene_len(2, 12);
+            detector.detect(sequence(&SCENES, BitDepth::Eight)).unwrap();
+            assert_eq!(
+                detector.chunk_split(),
+                ChunkSplit::SceneCuts {
+                    cuts: vec![10, 22, 25, 33],
+                    max_len: 12
+                }
+            );
+            assert_eq!(
+                detector.keyframe_policy(),
+                KeyframePolicy::Pts(vec![10, 22, 25, 33])
+            );
+
+            let cfg = fixtures::encoder_config(96, 64);
+            let mut ranges = Vec::new();
+            let mut keys = Vec::new();
+            ChunkedEncoder::new(cfg, detector.chunk_split())
+                .jobs(2)
+                .encode(sequence(&SCENES, BitDepth::Eight), |packet| {
+                    if packet.keyframe {
+                        keys.push(packet.pts);
+                    }
+                    Ok(())
+                })
+                .map(|r| ranges = r)
+                .unwrap();
+            assert_eq!(ranges, [0..10, 10..22, 22..25, 25..33, 33..45]);
+            assert_eq!(keys, [0, 10, 22, 25, 33]);
+        }
+    }
+
+    mod tq {
+        use crate::config::{BitDepth, ColorFormat};
+        use crate::frame::Frame;
+        use crate::tq::{psnr, ssim, Target, MAX_PSNR};
+
+        /// A textured luma plane, so quantization error shows in both metrics.
+        fn textured(width: u32, height: u32, depth: BitDepth, seed: usize) -> Frame {
+            let mut frame = Frame::new(width, height, depth, ColorFormat::Yuv420);
+            let max = (1usize << depth.bits()) - 1;
+            for y in 0..height as usize {
+                let bps = frame.bytes_per_sample();
+                let row = frame.row_mut(0, y);
+                for x in 0..width as usize {
+                    let v = (((x * 37 + y * 91 + seed * 13) * 2654435761) >> 7) % (max + 1);
+                    match bps {
+                        1 => row[x] = v as u8,
+                        _ => row[2 * x..2 * x + 2].copy_from_slice(&(v as u16).to_le_bytes()),
+                    }
+                }
+            }
+            frame
+        }
+
+        #[test]
+        fn metrics_of_known_distortions() {
+            let frame = textured(64, 48, BitDepth::Eight, 0);
+            assert_eq!(psnr(&frame, &frame).unwrap(), MAX_PSNR);
+            assert!((ssim(&frame, &frame).unwrap() - 1.0).abs() < 1e-9);
+
+            // An error of one code value everywhere: MSE 1, PSNR 20*log10(255).
+            let black = Frame::new(16, 16, BitDepth::Eight, ColorFormat::Yuv420);
+            let mut ones = black.clone();
+            for y in 0..16 {
+                ones.row_mut(0, y).fill(1);
+            }
+            let expected = 20.0 * 255f64.log10();
+            assert!((psnr(&black, &ones).unwrap() - expected).abs() < 1e-9);
+
+            // More distortion scores lower in both metrics, at any bit depth.
+            for depth in [BitDepth::Eight, BitDepth::Ten] {
+                let a = textured(40, 30, depth, 0);
+                let slight = shifted(&a, 2);
+                let heavy = shifted(&a, 40);
+                assert!(psnr(&a, &slight).unwrap() > psnr(&a, &heavy).unwrap());
+                assert!(ssim(&a, &slight).unwrap() > ssim(&a, &heavy).unwrap());
+                assert!(ssim(&a, &heavy).unwrap() < 1.0);
+            }
+            assert!(Target::Ssim(0.97).is_met(0.97));
+            assert!(!Target::Psnr(42.0).is_met(41.9));
+        }
+
+        /// Adds `delta` (saturating) to every other luma sample.
+        fn shifted(frame: &Frame, delta: u16) -> Frame {
+            let mut out = frame.clone();
+            let max = (1u16 << frame.bit_depth().bits()) - 1;
+            let bps = frame.bytes_per_sample();
+            for y in 0..frame.height() as usize {
+                let row = out.row_mut(0, y);
+                for x in (0..frame.width() as usize).step_by(2) {
+                    if bps == 1 {
+                        row[x] = (row[x] as u16 + delta).min(max) as u8;
+                    } else {
+                        let v = u16::from_le_bytes([row[2 * x], row[2 * x + 1]]);
+                        row[2 * x..2 * x + 2].copy_from_slice(&(v + delta).min(max).to_le_bytes());
+                    }
+                }
+            }
+            out
+        }
+
+        #[test]
+        fn tiny_and_mismatched_frames() {
+            let a = textured(3, 2, BitDepth::Eight, 1);
+            assert!((ssim(&a, &a).unwrap() - 1.0).abs() < 1e-9);
+            let b = textured(4, 2, BitDepth::Eight, 1);
+            assert!(psnr(&a, &b).is_err());
+            let c = textured(3, 2, BitDepth::Ten, 1);
+            assert!(ssim(&a, &c).is_err());
+        }
+
+        #[cfg(all(feature = "encoder", not(loom)))]
+        mod search {
+            use super::super::fixtures;
+            use super::textured;
+            use crate::config::BitDepth;
+            use crate::encoder::Configuration;
+            use crate::frame::Frame;
+            use crate::tq::{find_crf, CrfSearch, Target};
+
+            fn clip() -> (Vec<Frame>, Configuration) {
+                let frames: Vec<Frame> = (0..4)
+                    .map(|i| {
+                        let mut frame = textured(64, 48, BitDepth::Eight, i);
+                        frame.pts = i as i64;
+                        frame
+                    })
+                    .collect();
+                (frames, fixtures::encoder_config(64, 48))
+            }
+
+            #[test]
+            fn finds_highest_passing_qp() {
+                let (frames, cfg) = clip();
+                for target in [Target::Psnr(40.0), Target::Ssim(0.9995)] {
+                    let result = find_crf(&frames, &cfg, target).unwrap();
+                    assert!(target.is_met(result.best.score), "{result:?}");
+                    assert!(result.probes.len() <= 8);
+                    assert!(result.probes.contains(&result.best));
+                    // Every probe above the chosen QP misses the target, and
+                    // the QP just above it was tried unless the chosen QP is
+                    // the top of the range or the probe budget ran out.
+                    for probe in &result.probes {
+                        if probe.qp > result.qp() {
+                            assert!(!target.is_met(probe.score), "{result:?}");
+                        }
+                    }
+                    assert!(
+                        result.qp() == 63
+                            || result.probes.len() == 8
+                            || result.probes.iter().any(|p| p.qp == result.qp() + 1),
+                        "{result:?}"
+                    );
+                }
+            }
+
+            #[test]
+            fn easy_targets_stop_at_max_qp() {
+                let (frames, cfg) = clip();
+                let result = CrfSearch::new(Target::Psnr(10.0))
+                    .with_qp_range(20, 50)
+                    .run(&frames, &cfg)
+                    .unwrap();
+                assert_eq!(result.qp(), 50);
+                assert_eq!(result.probes.len(), 1);
+            }
+
+            #[test]
+            fn rejects_unreachable_targets_and_bad_input() {
+                let (mut frames, cfg) = clip();
+                assert!(find_crf(&frames, &cfg, Target::Psnr(200.0)).is_err());
+                assert!(find_crf(&[], &cfg, Target::Psnr(40.0)).is_err());
+                let search = CrfSearch::new(Target::Psnr(40.0));
+                assert!(search.with_qp_range(30, 20).run(&frames, &cfg).is_err());
+                assert!(search.with_max_probes(1).run(&frames, &cfg).is_err());
+                frames[1].pts = 0;
+                assert!(search.run(&frames, &cfg).is_err());
+            }
+        }
+    }
+
+    mod webm {
+        use super::fixtures;
+        use crate::container::av1c::Av1CodecConfig;
+        use crate::container::ebml::{ids, EbmlReader};
+        use crate::container::webm::{WebmConfig, WebmWriter};
+        use std::io::Cursor;
+
+        #[test]
+        fn av1c_from_stream_header() {
+            let mut header = fixtures::obu(2, &[]);
+            header.extend(fixtures::sequence_header_obu(64, 48));
+            let av1c = Av1CodecConfig::from_sequence_header(&header).unwrap();
+            assert_eq!(av1c.seq_profile, 0);
+            assert_eq!(av1c.seq_level_idx_0, 8);
+            assert_eq!(av1c.bit_depth(), 8);
+            assert!(av1c.chroma_subsampling_x && av1c.chroma_subsampling_y);
+            assert!(!av1c.monochrome);
+            assert_eq!(av1c.config_obus, fixtures::sequence_header_obu(64, 48));
+            let bytes = av1c.to_bytes();
+            assert_eq!(&bytes[..4], &[0x81, 0x08, 0x0c, 0x00]);
+            assert_eq!(&bytes[4..], av1c.config_obus.as_slice());
+        }
+
+        #[test]
+        fn writes_parseable_webm() {
+            let sequence_header = fixtures::sequence_header_obu(64, 48);
+            let config = WebmConfig {
+                width: 64,
+                height: 48,
+                frame_rate_num: 30,
+                frame_rate_den: 1,
+                sequence_header: sequence_header.clone(),
+            };
+            let mut writer = WebmWriter::new(Cursor::new(Vec::new()), config).unwrap();
+            for pts in 0..5u8 {
+                let key = pts == 0 || pts == 3;
+                let tu = fixtures::temporal_unit(key, pts, 10);
+                writer.write_frame(&tu, pts as i64, key).unwrap();
+            }
+            let file = writer.finish().unwrap().into_inner();
+
+            let top: Vec<_> = EbmlReader::new(&file).collect::<Result<_, _>>().unwrap();
+            assert_eq!(top.len(), 2);
+            assert_eq!(top[0].id, ids::EBML);
+            let doc_type = top[0].child(ids::DOC_TYPE).unwrap().unwrap();
+            assert_eq!(doc_type.as_str().unwrap(), "webm");
+
+            let segment = top[1];
+            assert_eq!(segment.id, ids::SEGMENT);
+            // Size was patched: the segment ends exactly at the end of the file.
+            assert_eq!(
+                segment.offset + segment.header_len + segment.data.len(),
+                file.len()
+            );
+            assert_eq!(segment.header_len, 4 + 8);
+
+            let children: Vec<_> = segment.children().collect::<Result<_, _>>().unwrap();
+            let find = |id| children.iter().find(|el| el.id == id).copied().unwrap();
+
+            let seek_head = find(ids::SEEK_HEAD);
+            for seek in seek_head.children_with_id(ids::SEEK).unwrap() {
+                let target = seek.child(ids::SEEK_ID).unwrap().unwrap().as_uint() as u32;
+                let pos = seek.child(ids::SEEK_POSITION).unwrap().unwrap().as_uint() as usize;
+                let el = EbmlReader::new(&segment.data[pos..])
+                    .next()
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(el.id, target);
+            }
+            assert_eq!(seek_head.children_with_id(ids::SEEK).unwrap().len(), 3);
+
+            let info = find(ids::INFO);
+            let duration = info
+                .child(ids::DURATION)
+                .unwrap()
+                .unwrap()
+                .as_float()
+                .unwrap();
+            assert_eq!(duration, 133.0 + 33.0);
+
+            let entry = find(ids::TRACKS).child(ids::TRACK_ENTRY).unwrap().unwrap();
+            assert_eq!(
+                entry
+                    .child(ids::CODEC_ID)
+                    .unwrap()
+                    .unwrap()
+                    .as_str()
+                    .unwrap(),
+                "V_AV1"
+            );
+            let private = entry.child(ids::CODEC_PRIVATE).unwrap().unwrap();
+            let expected = Av1CodecConfig::from_sequence_header(&sequence_header).unwrap();
+            assert_eq!(private.data, expected.to_bytes().as_slice());
+
+            let clusters: Vec<_> = children.iter().filter(|el| el.id == ids::CLUSTER).collect();
+            assert_eq!(clusters.len(), 2);
+            let mut blocks = Vec::new();
+            for cluster in &clusters {
+                let base = cluster.child(ids::TIMESTAMP).unwrap().unwrap().as_uint() as i64;
+                for block in cluster.children_with_id(ids::SIMPLE_BLOCK).unwrap() {
+                    assert_eq!(block.data[0], 0x81);
+                    let rel = i16::from_be_bytes([block.data[1], block.data[2]]) as i64;
+                    let key = block.data[3] & 0x80 != 0;
+                    blocks.push((base + rel, key, block.data[4..].to_vec()));
+                }
+            }
+            let times: Vec<_> = blocks.iter().map(|b| (b.0, b.1)).collect();
+            assert_eq!(
+                times,
+                [
+                    (0, true),
+                    (33, false),
+                    (67, false),
+                    (100, true),
+                    (133, false)
+                ]
+            );
+            // Temporal delimiters are stripped from the stored samples.
+            let mut expected_first = fixtures::sequence_header_obu(64, 48);
+            expected_first.extend(fixtures::obu(6, &[0; 10]));
+            assert_eq!(blocks[0].2, expected_first);
+            assert_eq!(blocks[1].2, fixtures::obu(6, &[1; 10]));
+
+            let cues = find(ids::CUES);
+            let points = cues.children_with_id(ids::CUE_POINT).unwrap();
+            assert_eq!(points.len(), 2);
+            for (point, cluster) in points.iter().zip(&clusters) {
+                let positions = point.child(ids::CUE_TRACK_POSITIONS).unwrap().unwrap();
+                let pos = positions
+                    .child(ids::CUE_CLUSTER_POSITION)
+                    .unwrap()
+                    .unwrap()
+                    .as_uint() as usize;
+                assert_eq!(pos, cluster.offset);
+            }
+        }
+
+        #[test]
+        fn rejects_stream_header_without_sequence_header() {
+            let config = WebmConfig {
+                width: 64,
+                height: 48,
+                frame_rate_num: 30,
+                frame_rate_den: 1,
+                sequence_header: fixtures::obu(2, &[]),
+            };
+            assert!(WebmWriter::new(Cursor::new(Vec::new()), config).is_err());
+        }
+    }
+
+    mod mp4 {
+        use super::fixtures;
+        use crate::container::av1c::Av1CodecConfig;
+        use crate::container::bmff::{BmffBox, BoxReader};
+        use crate::container::mp4::{Mp4Config, Mp4Writer};
+        use std::io::Cursor;
+
+        fn config(fragmented: bool) -> Mp4Config {
+            Mp4Config {
+                width: 64,
+                height: 48,
+                frame_rate_num: 30,
+                frame_rate_den: 1,
+                sequence_header: fixtures::sequence_header_obu(64, 48),
+                fragmented,
+            }
+        }
+
+        fn write(fragmented: bool) -> Vec<u8> {
+            let mut writer = Mp4Writer::new(Cursor::new(Vec::new()), config(fragmented)).unwrap();
+            for pts in 0..5u8 {
+                let key = pts == 0 || pts == 3;
+                let tu = fixtures::temporal_unit(key, pts, 10);
+                writer
+                    .write_frame(&tu, pts as i64, pts as i64, key)
+                    .unwrap();
+            }
+            writer.finish().unwrap().into_inner()
+        }
+
+        fn u32_at(data: &[u8], at: usize) -> u32 {
+            u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+        }
+
+        fn stbl<'a>(moov: &BmffBox<'a>) -> BmffBox<'a> {
+            moov.find(&[b"trak", b"mdia", b"minf", b"stbl"])
+                .unwrap()
+                .unwrap()
+        }
+
+        fn check_av1c(stbl: &BmffBox) {
+            let stsd = stbl.child(b"stsd").unwrap().unwrap();
+            let av01 = stsd.children(8).next().unwrap().unwrap();
+            assert_eq!(&av01.fourcc, b"av01");
+            assert_eq!(u32_at(av01.data, 24), (64 << 16) | 48);
+            // VisualSampleEntry fields take 78 bytes before the child boxes.
+            let av1c = av01.children(78).next().unwrap().unwrap();
+            assert_eq!(&av1c.fourcc, b"av1C");
+            let expected =
+                Av1CodecConfig::from_sequence_header(&fixtures::sequence_header_obu(64, 48))
+                    .unwrap();
+            assert_eq!(av1c.data, expected.to_bytes().as_slice());
+        }
+
+        #[test]
+        fn writes_progressive_mp4() {
+            let file = write(false);
+            let top: Vec<_> = BoxReader::new(&file).collect::<Result<_, _>>().unwrap();
+            let fourccs: Vec<_> = top.iter().map(|b| &b.fourcc).collect();
+            assert_eq!(fourccs, [b"ftyp", b"mdat", b"moov"]);
+            assert_eq!(&top[0].data[..4], b"isom");
+            assert_eq!(top[1].header_len, 16);
+
+            let stbl = stbl(&top[2]);
+            check_av1c(&stbl);
+
+            let stts = stbl.child(b"stts").unwrap().unwrap();
+            assert_eq!(u32_at(stts.data, 4), 1);
+            assert_eq!((u32_at(stts.data, 8), u32_at(stts.data, 12)), (5, 1));
+            assert!(stbl.child(b"ctts").unwrap().is_none());
+
+            let stss = stbl.child(b"stss").unwrap().unwrap();
+            assert_eq!(u32_at(stss.data, 4), 2);
+            assert_eq!((u32_at(stss.data, 8), u32_at(stss.data, 12)), (1, 4));
+
+            // Samples are stored back to back in the mdat, without temporal delimiters.
+            let stsz = stbl.child(b"stsz").unwrap().unwrap();
+            let stco = stbl.child(b"stco").unwrap().unwrap();
+            let mut offset = u32_at(stco.data, 8) as usize;
+            assert_eq!(offset, top[1].offset + 16);
+            assert_eq!(u32_at(stsz.data, 8), 5);
+            let mut samples = Vec::new();
+            for i in 0..5 {
+                let size = u32_at(stsz.data, 12 + 4 * i) as usize;
+                samples.push(&file[offset..offset + size]);
+                offset += size;
+            }
+            assert_eq!(offset, top[2].offset);
+            let mut expected_first = fixtures::sequence_header_obu(64, 48);
+            expected_first.extend(fixtures::obu(6, &[0; 10]));
+            assert_eq!(samples[0], expected_first.as_slice());
+            assert_eq!(samples[1], fixtures::obu(6, &[1; 10]).as_slice());
+
+            let mvhd = top[2].child(b"mvhd").unwrap().unwrap();
+            assert_eq!((u32_at(mvhd.data, 12), u32_at(mvhd.data, 16)), (30, 5));
+        }
+
+        #[test]
+        fn writes_fragment_per_gop() {
+            let file = write(true);
+            let top: Vec<_> = BoxReader::new(&file).collect::<Result<_, _>>().unwrap();
+            let fourccs: Vec<_> = top.iter().map(|b| &b.fourcc).collect();
+            assert_eq!(
+                fourccs,
+                [b"ftyp", b"moov", b"moof", b"mdat", b"moof", b"mdat"]
+            );
+            assert!(top[1].child(b"mvex").unwrap().is_some());
+            let stbl = stbl(&top[1]);
+            check_av1c(&stbl);
+            let stsz = stbl.child(b"stsz").unwrap().unwrap();
+            assert_eq!(u32_at(stsz.data, 8), 0);
+
+            let mut counts = Vec::new();
+            for (i, (moof, mdat)) in [(top[2], top[3]), (top[4], top[5])].iter().enumerate() {
+                let mfhd = moof.child(b"mfhd").unwrap().unwrap();
+                assert_eq!(u32_at(mfhd.data, 4), i as u32 + 1);
+                let traf = moof.child(b"traf").unwrap().unwrap();
+                let tfdt = traf.child(b"tfdt").unwrap().unwrap();
+                assert_eq!(tfdt.data[11], [0, 3][i]);
+                let trun = traf.child(b"trun").unwrap().unwrap();
+                let count = u32_at(trun.data, 4) as usize;
+                let data_offset = u32_at(trun.data, 8) as usize;
+                assert_eq!(moof.offset + data_offset, mdat.offset + 8);
+                let mut total = 0;
+                for s in 0..count {
+                    let entry = 12 + 16 * s;
+                    assert_eq!(u32_at(trun.data, entry), 1);
+                    total += u32_at(trun.data, entry + 4) as usize;
+                    let sync = u32_at(trun.data, entry + 8) & 0x0001_0000 == 0;
+                    assert_eq!(sync, s == 0);
+                }
+                assert_eq!(total, mdat.data.len());
+                counts.push(count);
+            }
+            assert_eq!(counts, [3, 2]);
+        }
+
+        #[test]
+        fn rejects_decreasing_dts() {
+            let mut writer = Mp4Writer::new(Cursor::new(Vec::new()), config(false)).unwrap();
+            let tu = fixtures::temporal_unit(true, 0, 4);
+            writer.write_frame(&tu, 1, 1, true).unwrap();
+            assert!(writer.write_frame(&tu, 0, 1, false).is_err());
+        }
+    }
+
+    mod ivf {
+        use crate::container::ivf::{IvfFile, IvfWriter, HEADER_LEN};
+        use std::io::Cursor;
+
+        #[test]
+        fn round_trips_frames_and_count() {
+            let mut writer = IvfWriter::new(Cursor::new(Vec::new()), 64, 48, 30, 1).unwrap();
+            writer.write_frame(&[1, 2, 3], 0).unwrap();
+            writer.write_frame(&[4], 7).unwrap();
+            assert_eq!(writer.frame_count(), 2);
+            let out = writer.finish().unwrap().into_inner();
+            assert_eq!(&out[..4], b"DKIF");
+            assert_eq!(out.len(), HEADER_LEN + 2 * 12 + 4);
+
+            let file = IvfFile::parse(&out).unwrap();
+            assert_eq!((file.width, file.height), (64, 48));
+            assert_eq!((file.frame_rate_num, file.frame_rate_den), (30, 1));
+            assert_eq!(file.frame_count, 2);
+            assert_eq!(file.frames.len(), 2);
+            assert_eq!(
+                (file.frames[0].pts, file.frames[0].data),
+                (0, &[1, 2, 3][..])
+            );
+            assert_eq!((file.frames[1].pts, file.frames[1].data), (7, &[4][..]));
+        }
+
+        #[test]
+        fn drop_patches_frame_count() {
+            let mut out = Cursor::new(Vec::new());
+            {
+                let mut writer = IvfWriter::new(&mut out, 16, 16, 25, 1).unwrap();
+                writer.write_frame(&[9], 0).unwrap();
+            }
+            assert_eq!(IvfFile::parse(out.get_ref()).unwrap().frame_count, 1);
+        }
+
+        #[test]
+        fn rejects_bad_input() {
+            assert!(IvfWriter::new(Cursor::new(Vec::new()), 70_000, 48, 30, 1).is_err());
+            assert!(IvfFile::parse(b"DKIF").is_err());
+
+            let mut writer = IvfWriter::new(Cursor::new(Vec::new()), 8, 8, 30, 1).unwrap();
+            writer.write_frame(&[1, 2, 3], 0).unwrap();
+            let out = writer.finish().unwrap().into_inner();
+            assert!(IvfFile::parse(&out[..out.len() - 1]).is_err());
+        }
+    }
+
+    mod avif {
+        use super::fixtures;
+        use crate::avif::{write_avif, AvifItem, Nclx};
+        use crate::container::bmff::{BmffBox, BoxReader};
+
+        fn item(tag: u8) -> AvifItem {
+            let header = fixtures::sequence_header_obu(64, 48);
+            let frame = fixtures::temporal_unit(false, tag, 12);
+            AvifItem::new(64, 48, &header, &frame).unwrap()
+        }
+
+        fn u16_at(data: &[u8], at: usize) -> u16 {
+            u16::from_be_bytes(data[at..at + 2].try_into().unwrap())
+        }
+
+        fn u32_at(data: &[u8], at: usize) -> u32 {
+            u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+        }
+
+        fn meta_child<'a>(meta: &BmffBox<'a>, fourcc: &[u8; 4]) -> BmffBox<'a> {
+            meta.children(4)
+                .map(Result::unwrap)
+                .find(|b| &b.fourcc == fourcc)
+                .unwrap()
+        }
+
+        #[test]
+        fn item_gets_sequence_header_without_temporal_delimiter() {
+            let item = item(7);
+            let mut expected = fixtures::sequence_header_obu(64, 48);
+            expected.extend(fixtures::obu(6, &[7; 12]));
+            assert_eq!(item.data, expected);
+        }
+
+        #[test]
+        fn writes_color_and_alpha_items() {
+            let color = item(1);
+            let alpha = item(2);
+            let file = write_avif(&color, Some(&alpha), Nclx::default()).unwrap();
+            let top: Vec<_> = BoxReader::new(&file).collect::<Result<_, _>>().unwrap();
+            let fourccs: Vec<_> = top.iter().map(|b| &b.fourcc).collect();
+            assert_eq!(fourccs, [b"ftyp", b"meta", b"mdat"]);
+            assert_eq!(&top[0].data[..4], b"avif");
+
+            let meta = top[1];
+            let hdlr = meta_child(&meta, b"hdlr");
+            assert_eq!(&hdlr.data[8..12], b"pict");
+            assert_eq!(u16_at(meta_child(&meta, b"pitm").data, 4), 1);
+
+            // Each iloc extent points at the item's bytes inside the mdat.
+            let iloc = meta_child(&meta, b"iloc");
+            assert_eq!(u16_at(iloc.data, 6), 2);
+            for (i, expected) in [&color, &alpha].iter().enumerate() {
+                let entry = 8 + 14 * i;
+                assert_eq!(u16_at(iloc.data, entry), i as u16 + 1);
+                let offset = u32_at(iloc.data, entry + 6) as usize;
+                let len = u32_at(iloc.data, entry + 10) as usize;
+                assert_eq!(&file[offset..offset + len], expected.data.as_slice());
+            }
+
+            let iinf = meta_child(&meta, b"iinf");
+            let infes: Vec<_> = iinf.children(6).map(Result::unwrap).collect();
+            assert_eq!(infes.len(), 2);
+            assert!(infes.iter().all(|infe| &infe.data[8..12] == b"av01"));
+
+            let iref = meta_child(&meta, b"iref");
+            let auxl = iref.children(4).next().unwrap().unwrap();
+            assert_eq!(&auxl.fourcc, b"auxl");
+            assert_eq!(auxl.data, [0, 2, 0, 1, 0, 1]);
+
+            let iprp = meta_child(&meta, b"iprp");
+            let properties: Vec<_> = iprp
+                .child(b"ipco")
+                .unwrap()
+                .unwrap()
+                .children(0)
+                .map(Result::unwrap)
+                .collect();
+            let ipma = iprp.child(b"ipma").unwrap().unwrap();
+            assert_eq!(u32_at(ipma.data, 4), 2);
+            let mut at = 8;
+            let mut associated = Vec::new();
+            for _ in 0..2 {
+                let id = u16_at(ipma.data, at);
+                let count = ipma.data[at + 2] as usize;
+                let mut names = Vec::new();
+                for &index in &ipma.data[at + 3..at + 3 + count] {
+                    let property = properties[(index & 0x7f) as usize - 1];
+                    names.push((property.fourcc, index & 0x80 != 0));
+                }
+                associated.push((id, names));
+                at += 3 + count;
+            }
+            assert_eq!(
+                associated[0],
+                (
+                    1,
+                    vec![
+                        (*b"ispe", false),
+                        (*b"pixi", false),
+                        (*b"av1C", true),
+                        (*b"colr", false)
+                    ]
+                )
+            );
+            assert_eq!(
+                associated[1],
+                (
+                    2,
+                    vec![
+                        (*b"ispe", false),
+                        (*b"pixi", false),
+                        (*b"av1C", true),
+                        (*b"auxC", true)
+                    ]
+                )
+            );
+
+            let find = |fourcc: &[u8; 4]| properties.iter().find(|p| &p.fourcc == fourcc).unwrap();
+            assert_eq!(
+                (u32_at(find(b"ispe").data, 4), u32_at(find(b"ispe").data, 8)),
+                (64, 48)
+            );
+            assert_eq!(&find(b"pixi").data[4..], &[3, 8, 8, 8]);
+            assert_eq!(find(b"av1C").data, color.config.to_bytes().as_slice());
+            assert_eq!(find(b"colr").data, b"nclx\x00\x01\x00\x0d\x00\x06\x80");
+            assert!(
+                find(b"auxC").data[4..].starts_with(b"urn:mpeg:mpegB:cicp:systems:auxiliary:alpha")
+            );
+        }
+
+        #[test]
+        fn alpha_pixi_has_one_channel_of_a_420_bitstream() {
+            let color = item(1);
+            let alpha = item(2);
+            assert!(!alpha.config.monochrome);
+            let file = write_avif(&color, Some(&alpha), Nclx::default()).unwrap();
+            let meta = BoxReader::new(&file).nth(1).unwrap().unwrap();
+            let iprp = meta_child(&meta, b"iprp");
+            let ipco = iprp.child(b"ipco").unwrap().unwrap();
+            let properties: Vec<_> = ipco.children(0).map(Result::unwrap).collect();
+            let ipma = iprp.child(b"ipma").unwrap().unwrap();
+
+            // The alpha item is the second ipma entry; follow its associations.
+            let at = 8 + 3 + ipma.data[10] as usize;
+            assert_eq!(u16_at(ipma.data, at), 2);
+            let count = ipma.data[at + 2] as usize;
+            let associated: Vec<_> = ipma.data[at + 3..at + 3 + count]
+                .iter()
+                .map(|&index| properties[(index & 0x7f) as usize - 1])
+                .collect();
+            let pixi = associated.iter().find(|p| &p.fourcc == b"pixi").unwrap();
+            let av1c = associated.iter().find(|p| &p.fourcc == b"av1C").unwrap();
+            assert_eq!(av1c.data, alpha.config.to_bytes().as_slice());
+            // One channel, at the bit depth the bitstream is coded with.
+            assert_eq!(&pixi.data[4..], &[1, alpha.config.bit_depth()]);
+        }
+
+        #[test]
+        fn color_only_has_no_iref() {
+            let file = write_avif(&item(1), None, Nclx::default()).unwrap();
+            let meta = BoxReader::new(&file).nth(1).unwrap().unwrap();
+            assert!(meta
+                .children(4)
+                .map(Result::unwrap)
+                .all(|b| &b.fourcc != b"iref"));
+        }
+    }
+
+    mod obu {
+        use super::fixtures;
+        use crate::obu::{
+            parse_obus, read_leb128, write_leb128, MetadataType, ObuExtension, ObuHeader,
+            ObuReader, ObuType,
+        };
+
+        /// A key frame packet laid out the way SVT-AV1 emits it: temporal
+        /// delimiter, sequence header, HDR metadata, then one frame OBU.
+        fn key_frame_packet() -> Vec<u8> {
+            let mut packet = vec![0x12, 0x00];
+            packet.extend(fixtures::sequence_header_obu(64, 48));
+            // metadata_type = HDR_CLL, max_cll = 1000, max_fall = 400
+            packet.extend([0x2a, 0x06, 0x01, 0x03, 0xe8, 0x01, 0x90, 0x80]);
+            packet.extend(fixtures::obu(6, &[0x10; 200]));
+            packet
+        }
+
+        #[test]
+        fn leb128_round_trip() {
+            for value in [0u64, 1, 127, 128, 200, 16_383, 16_384, u32::MAX as u64] {
+                let mut buf = Vec::new();
+                write_leb128(&mut buf, value);
+                assert_eq!(read_leb128(&buf).unwrap(), (value, buf.len()));
+            }
+            assert_eq!(read_leb128(&[0xc8, 0x01]).unwrap(), (200, 2));
+            // Non-minimal encodings are valid.
+            assert_eq!(read_leb128(&[0x85, 0x80, 0x00]).unwrap(), (5, 3));
+            assert!(read_leb128(&[0x80, 0x80]).is_err());
+        }
+
+        #[test]
+        fn splits_key_frame_packet() {
+            let packet = key_frame_packet();
+            let obus = parse_obus(&packet).unwrap();
+            let types: Vec<_> = obus.iter().map(|o| o.obu_type()).collect();
+            assert_eq!(
+                types,
+                [
+                    ObuType::TemporalDelimiter,
+                    ObuType::SequenceHeader,
+                    ObuType::Metadata,
+                    ObuType::Frame
+                ]
+            );
+            assert!(obus.iter().all(|o| o.header.has_size_field));
+            assert!(obus[0].payload.is_empty());
+            assert_eq!(
+                obus[1].payload,
+                fixtures::sequence_header_payload(64, 48).as_slice()
+            );
+            assert_eq!(obus[2].metadata_type().unwrap(), Some(MetadataType::HdrCll));
+            assert_eq!(obus[3].metadata_type().unwrap(), None);
+            // Frame payload of 200 bytes needs a two-byte size field.
+            assert_eq!(obus[3].data.len(), 1 + 2 + 200);
+            let mut end = 0;
+            for obu in &obus {
+                assert_eq!(obu.offset, end);
+                assert_eq!(&packet[obu.offset..obu.offset + obu.data.len()], obu.data);
+                end += obu.data.len();
+            }
+            assert_eq!(end, packet.len());
+        }
+
+        #[test]
+        fn decodes_extension_and_sizeless_last_obu() {
+            // Tile group in temporal layer 2, spatial layer 1, followed by
+            // padding without a size field that runs to the end of the buffer.
+            let data = [0x26, 0x50, 0x02, 0xaa, 0xbb, 0x78, 0x00, 0x00, 0x00];
+            let obus: Vec<_> = ObuReader::new(&data).collect::<Result<_, _>>().unwrap();
+            assert_eq!(obus.len(), 2);
+            assert_eq!(
+                obus[0].header,
+                ObuHeader {
+                    obu_type: ObuType::TileGroup,
+                    extension: Some(ObuExtension {
+                        temporal_id: 2,
+                        spatial_id: 2,
+                    }),
+                    has_size_field: true,
+                }
+            );
+            assert_eq!(obus[0].payload, [0xaa, 0xbb]);
+            assert_eq!(obus[1].obu_type(), ObuType::Padding);
+            assert!(!obus[1].header.has_size_field);
+            assert_eq!(obus[1].payload, [0; 3]);
+            assert_eq!(obus[0].header.to_bytes(), [0x26, 0x50]);
+        }
+
+        #[test]
+        fn rejects_malformed_obus() {
+            assert!(parse_obus(&[0x92, 0x00]).is_err());
+            assert!(parse_obus(&[0x32, 0x05, 0x00]).is_err());
+            assert!(parse_obus(&[0x36]).is_err());
+            let mut reader = ObuReader::new(&[0x12, 0x00, 0x32, 0x7f]);
+            assert!(reader.next().unwrap().is_ok());
+            assert!(reader.next().unwrap().is_err());
+            assert!(reader.next().is_none());
+        }
+
+        #[test]
+        fn obu_type_round_trip() {
+            for value in 0..16 {
+                assert_eq!(ObuType::from_u8(value).as_u8(), value);
+            }
+            assert_eq!(ObuType::from_u8(9), ObuType::Reserved(9));
+        }
+    }
+
+    mod sequence_header {
+        use super::fixtures::{self, BitWriter};
+        use crate::config::{
+            BitDepth, ChromaSamplePosition, ColorFormat, ColorRange, Profile, Tier,
+        };
+        use crate::obu::sequence_header::{DecoderModelInfo, TimingInfo};
+        use crate::obu::SequenceHeader;
+
+        #[test]
+        fn decodes_svt_defaults() {
+            let mut stream_header = fixtures::obu(2, &[]);
+            stream_header.extend(fixtures::sequence_header_obu(1920, 1080));
+            let seq = SequenceHeader::from_bytes(&stream_header).unwrap();
+            assert_eq!(seq.bytes, fixtures::sequence_header_obu(1920, 1080));
+            assert_eq!(seq.profile, Profile::Main);
+            assert_eq!(seq.seq_level_idx(), 8);
+            assert_eq!(seq.level_code(), Some(40));
+            assert_eq!(seq.tier(), Tier::Main);
+            assert_eq!((seq.max_frame_width, seq.max_frame_height), (1920, 1080));
+            assert_eq!(seq.timing_info, None);
+            assert_eq!(seq.bit_depth(), BitDepth::Eight);
+            assert_eq!(seq.color_format(), ColorFormat::Yuv420);
+            assert_eq!(seq.color.color_range, ColorRange::Studio);
+            assert_eq!(
+                (
+                    seq.color.color_primaries,
+                    seq.color.transfer_characteristics,
+                    seq.color.matrix_coefficients
+                ),
+                (2, 2, 2)
+            );
+            let tools = seq.tools;
+            assert!(tools.enable_filter_intra && tools.enable_intra_edge_filter);
+            assert!(tools.enable_order_hint && tools.enable_ref_frame_mvs);
+            assert!(!tools.enable_jnt_comp && !tools.use_128x128_superblock);
+            assert_eq!(tools.order_hint_bits, 7);
+            assert_eq!(
+                (
+                    tools.seq_force_screen_content_tools,
+                    tools.seq_force_integer_mv
+                ),
+                (2, 2)
+            );
+            assert!(tools.enable_cdef && tools.enable_restoration && !tools.enable_superres);
+            assert!(!seq.film_grain_params_present);
+        }
+
+        #[test]
+        fn decodes_timing_and_color_description() {
+            let mut w = BitWriter::default();
+            w.put(2, 3); // seq_profile
+            w.put(0, 1); // still_picture
+            w.put(0, 1); // reduced_still_picture_header
+            w.put(1, 1); // timing_info_present_flag
+            w.put(1001, 32); // num_units_in_display_tick
+            w.put(60000, 32); // time_scale
+            w.put(1, 1); // equal_picture_interval
+            w.put(0b010, 3); // num_ticks_per_picture_minus_1 = 1 (uvlc)
+            w.put(1, 1); // decoder_model_info_present_flag
+            w.put(9, 5); // buffer_delay_length_minus_1
+            w.put(1001, 32); // num_units_in_decoding_tick
+            w.put(4, 5); // buffer_removal_time_length_minus_1
+            w.put(5, 5); // frame_presentation_time_length_minus_1
+            w.put(1, 1); // initial_display_delay_present_flag
+            w.put(1, 5); // operating_points_cnt_minus_1
+            for (idc, level, tier) in [(0x101u64, 13u64, 1u64), (0x103, 5, 0)] {
+                w.put(idc, 12);
+                w.put(level, 5);
+                if level > 7 {
+                    w.put(tier, 1);
+                }
+                w.put(1, 1); // decoder_model_present_for_this_op
+                w.put(100, 10); // decoder_buffer_delay
+                w.put(200, 10); // encoder_buffer_delay
+                w.put(0, 1); // low_delay_mode_flag
+                w.put(1, 1); // initial_display_delay_present_for_this_op
+                w.put(9, 4); // initial_display_delay_minus_1
+            }
+            w.put(11, 4);
+            w.put(11, 4);
+            w.put(3839, 12);
+            w.put(2159, 12);
+            w.put(1, 1); // frame_id_numbers_present_flag
+            w.put(5, 4);
+            w.put(2, 3);
+            w.put(0b111, 3); // 128x128 superblocks, filter intra, intra edge
+            w.put(0b1111, 4); // interintra, masked compound, warped motion, dual filter
+            w.put(1, 1); // enable_order_hint
+            w.put(0b11, 2); // jnt_comp, ref_frame_mvs
+            w.put(0, 1); // seq_choose_screen_content_tools
+            w.put(1, 1); // seq_force_screen_content_tools
+            w.put(0, 1); // seq_choose_integer_mv
+            w.put(0, 1); // seq_force_integer_mv
+            w.put(4, 3); // order_hint_bits_minus_1
+            w.put(0b101, 3); // superres, cdef, restoration
+            w.put(1, 1); // high_bitdepth
+            w.put(1, 1); // twelve_bit
+            w.put(0, 1); // mono_chrome
+            w.put(1, 1); // color_description_present_flag
+            w.put(9, 8);
+            w.put(16, 8);
+            w.put(9, 8);
+            w.put(1, 1); // color_range
+            w.put(1, 1); // subsampling_x
+            w.put(0, 1); // subsampling_y
+            w.put(1, 1); // separate_uv_delta_q
+            w.put(1, 1); // film_grain_params_present
+            let seq = SequenceHeader::parse_payload(&w.finish()).unwrap();
+
+            assert_eq!(seq.profile, Profile::Professional);
+            assert_eq!(
+                seq.timing_info,
+                Some(TimingInfo {
+                    num_units_in_display_tick: 1001,
+                    time_scale: 60000,
+                    num_ticks_per_picture: Some(2),
+                })
+            );
+            assert_eq!(
+                seq.decoder_model_info,
+                Some(DecoderModelInfo {
+                    buffer_delay_length: 10,
+                    num_units_in_decoding_tick: 1001,
+                    buffer_removal_time_length: 5,
+                    frame_presentation_time_length: 6,
+                })
+            );
+            assert_eq!(seq.operating_points.len(), 2);
+            assert_eq!(seq.operating_points[0].idc, 0x101);
+            assert_eq!(seq.level_code(), Some(51));
+            assert_eq!(seq.tier(), Tier::High);
+            assert_eq!(seq.operating_points[1].tier, Tier::Main);
+            assert_eq!(seq.operating_points[1].initial_display_delay, Some(10));
+            assert_eq!((seq.max_frame_width, seq.max_frame_height), (3840, 2160));
+            assert!(seq.frame_id_numbers_present);
+            let tools = seq.tools;
+            assert!(tools.use_128x128_superblock && tools.enable_warped_motion);
+            assert!(tools.enable_jnt_comp);
+            assert_eq!(
+                (
+                    tools.seq_force_screen_content_tools,
+                    tools.seq_force_integer_mv
+                ),
+                (1, 0)
+            );
+            assert_eq!(tools.order_hint_bits, 5);
+            assert!(tools.enable_superres && !tools.enable_cdef && tools.enable_restoration);
+            assert_eq!(seq.bit_depth(), BitDepth::Twelve);
+            assert_eq!(seq.color_format(), ColorFormat::Yuv422);
+            assert_eq!(
+                (
+                    seq.color.color_primaries,
+                    seq.color.transfer_characteristics,
+                    seq.color.matrix_coefficients
+                ),
+                (9, 16, 9)
+            );
+            assert_eq!(seq.color.color_range, ColorRange::Full);
+            assert_eq!(
+                seq.color.chroma_sample_position,
+                ChromaSamplePosition::Unknown
+            );
+            assert!(seq.color.separate_uv_delta_q);
+            assert!(seq.film_grain_params_present);
+        }
+
+        #[test]
+        fn decodes_reduced_still_picture_header() {
+            let mut w = BitWriter::default();
+            w.put(0, 3); // seq_profile
+            w.put(1, 1); // still_picture
+            w.put(1, 1); // reduced_still_picture_header
+            w.put(12, 5); // seq_level_idx[0] = 5.0
+            w.put(9, 4);
+            w.put(9, 4);
+            w.put(639, 10);
+            w.put(479, 10);
+            w.put(0b011, 3); // filter intra, intra edge
+            w.put(0b010, 3); // cdef only
+            w.put(1, 1); // high_bitdepth
+            w.put(1, 1); // mono_chrome
+            w.put(0, 1); // color_description_present_flag
+            w.put(1, 1); // color_range
+            w.put(0, 1); // film_grain_params_present
+            let seq = SequenceHeader::parse_payload(&w.finish()).unwrap();
+            assert!(seq.still_picture && seq.reduced_still_picture_header);
+            assert_eq!(seq.level_code(), Some(50));
+            assert_eq!((seq.max_frame_width, seq.max_frame_height), (640, 480));
+            assert!(!seq.tools.enable_order_hint);
+            assert_eq!(seq.tools.seq_force_screen_content_tools, 2);
+            assert_eq!(seq.bit_depth(), BitDepth::Ten);
+            assert_eq!(seq.color_format(), ColorFormat::Yuv400);
+            assert_eq!(seq.color.color_range, ColorRange::Full);
+        }
+
+        #[test]
+        fn rejects_truncated_payload() {
+            let payload = fixtures::sequence_header_payload(64, 48);
+            assert!(SequenceHeader::parse_payload(&payload[..6]).is_err());
+            assert!(SequenceHeader::from_bytes(&fixtures::obu(2, &[])).is_err());
+        }
+    }
+
+    mod bitstream {
+        use super::fixtures;
+        use crate::bitstream::{convert, temporal_units, BitstreamWriter, Framing};
+        use crate::obu::{parse_obus, ObuType};
+
+        /// Three temporal units as the encoder emits them. The second carries a
+        /// shown frame split into frame header and tile group followed by a
+        /// second frame, so Annex B needs two frame units for it; the third has
+        /// an OBU with an extension header.
+        fn section5_stream() -> Vec<u8> {
+            let mut stream = fixtures::temporal_unit(true, 0, 40);
+            stream.extend(fixtures::obu(2, &[]));
+            stream.extend(fixtures::obu(3, &[0x11; 5]));
+            stream.extend(fixtures::obu(4, &[0x22; 150]));
+            stream.extend(fixtures::obu(5, &[0x04, 0xb5, 0x00]));
+            stream.extend(fixtures::obu(6, &[0x33; 7]));
+            stream.extend(fixtures::obu(2, &[]));
+            stream.extend([0x36, 0x28, 0x03, 0x44, 0x44, 0x44]);
+            stream
+        }
+
+        #[test]
+        fn splits_section5_at_temporal_delimiters() {
+            let units = temporal_units(&section5_stream(), Framing::Section5).unwrap();
+            let types: Vec<Vec<_>> = units
+                .iter()
+                .map(|u| u.obus.iter().map(|o| o.obu_type()).collect())
+                .collect();
+            assert_eq!(
+                types,
+                [
+                    vec![
+                        ObuType::TemporalDelimiter,
+                        ObuType::SequenceHeader,
+                        ObuType::Frame
+                    ],
+                    vec![
+                        ObuType::TemporalDelimiter,
+                        ObuType::FrameHeader,
+                        ObuType::TileGroup,
+                        ObuType::Metadata,
+                        ObuType::Frame
+                    ],
+                    vec![ObuType::TemporalDelimiter, ObuType::Frame],
+                ]
+            );
+            assert!(units[2].obus[1].header.extension.is_some());
+        }
+
+        #[test]
+        fn annexb_layout() {
+            let tu = fixtures::temporal_unit(false, 9, 2);
+            let annexb = convert(&tu, Framing::Section5, Framing::AnnexB).unwrap();
+            // temporal_unit_size, frame_unit_size, then obu_length-prefixed OBUs
+            // without size fields.
+            assert_eq!(annexb, [7, 6, 1, 0x10, 3, 0x30, 9, 9]);
+
+            let annexb = convert(&section5_stream(), Framing::Section5, Framing::AnnexB).unwrap();
+            let mut rest = annexb.as_slice();
+            let mut frame_units = Vec::new();
+            while !rest.is_empty() {
+                let (size, len) = crate::obu::read_leb128(rest).unwrap();
+                let mut tu = &rest[len..len + size as usize];
+                rest = &rest[len + size as usize..];
+                let mut count = 0;
+                while !tu.is_empty() {
+                    let (size, len) = crate::obu::read_leb128(tu).unwrap();
+                    tu = &tu[len + size as usize..];
+                    count += 1;
+                }
+                frame_units.push(count);
+            }
+            assert_eq!(frame_units, [1, 2, 1]);
+        }
+
+        #[test]
+        fn round_trips_every_framing() {
+            let section5 = section5_stream();
+            let annexb = convert(&section5, Framing::Section5, Framing::AnnexB).unwrap();
+            assert_eq!(
+                convert(&annexb, Framing::AnnexB, Framing::Section5).unwrap(),
+                section5
+            );
+            assert_eq!(
+                convert(&annexb, Framing::AnnexB, Framing::AnnexB).unwrap(),
+                annexb
+            );
+            assert_eq!(
+                convert(&section5, Framing::Section5, Framing::Section5).unwrap(),
+                section5
+            );
+
+            let units = temporal_units(&section5, Framing::Section5).unwrap();
+            let mut rebuilt_section5 = Vec::new();
+            let mut rebuilt_annexb = Vec::new();
+            for unit in &units {
+                let sample = unit.to_bytes(Framing::Sample);
+                assert!(parse_obus(&sample)
+                    .unwrap()
+                    .iter()
+                    .all(|o| o.obu_type() != ObuType::TemporalDelimiter));
+                assert_eq!(
+                    convert(&sample, Framing::Sample, Framing::Sample).unwrap(),
+                    sample
+                );
+                rebuilt_section5
+                    .extend(convert(&sample, Framing::Sample, Framing::Section5).unwrap());
+                rebuilt_annexb.extend(convert(&sample, Framing::Sample, Framing::AnnexB).unwrap());
+
+                let one = unit.to_bytes(Framing::AnnexB);
+                assert_eq!(
+                    convert(&one, Framing::AnnexB, Framing::Sample).unwrap(),
+                    sample
+                );
+                let one = unit.to_bytes(Framing::Section5);
+                assert_eq!(
+                    convert(&one, Framing::Section5, Framing::Sample).unwrap(),
+                    sample
+                );
+            }
+            assert_eq!(rebuilt_section5, section5);
+            assert_eq!(rebuilt_annexb, annexb);
+        }
+
+        #[test]
+        fn sample_output_needs_single_temporal_unit() {
+            assert!(convert(&section5_stream(), Framing::Section5, Framing::Sample).is_err());
+        }
+
+        #[test]
+        fn annexb_accepts_obus_with_size_fields() {
+            // obu_length 4: temporal delimiter with has_size_field and size 0,
+            // then a padding OBU carrying its own size.
+            let annexb = [9, 8, 2, 0x12, 0x00, 4, 0x7a, 0x02, 0xee, 0xff];
+            assert_eq!(
+                convert(&annexb, Framing::AnnexB, Framing::Section5).unwrap(),
+                [0x12, 0x00, 0x7a, 0x02, 0xee, 0xff]
+            );
+            assert!(convert(&[9, 7, 2, 0x12, 0x00], Framing::AnnexB, Framing::Section5).is_err());
+        }
+
+        #[test]
+        fn writer_records_unit_sizes() {
+            let section5 = section5_stream();
+            for framing in [Framing::Section5, Framing::AnnexB, Framing::Sample] {
+                let mut writer = BitstreamWriter::new(Vec::new(), framing);
+                for unit in temporal_units(&section5, Framing::Section5).unwrap() {
+                    writer
+                        .write_temporal_unit(&unit.to_bytes(Framing::Section5))
+                        .unwrap();
+                }
+                let sizes = writer.unit_sizes().to_vec();
+                let out = writer.into_inner();
+                assert_eq!(sizes.len(), 3);
+                assert_eq!(sizes.iter().sum::<usize>(), out.len());
+                let mut offset = 0;
+                for (unit, size) in temporal_units(&section5, Framing::Section5)
+                    .unwrap()
+                    .iter()
+                    .zip(sizes)
+                {
+                    assert_eq!(&out[offset..offset + size], unit.to_bytes(framing));
+                    offset += size;
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "encoder")]
+    mod encoder {
+        use crate::config::ConfigExt;
+        use crate::encoder::{
+            validate, Configuration, Encoder, FrameSender, Handle, InputGate, InputHandle,
+            KeyframePolicy, Packet, PacketReceiver, QueueStats,
+        };
+        use crate::mock::enc::MockEncoder;
+        use crate::Error;
+
+        #[test]
+        fn build_rejects_incomplete_configuration() {
+            let mut cfg: Configuration = unsafe { std::mem::zeroed() };
+            assert!(matches!(validate(&cfg), Err(Error::InvalidArgument(_))));
+            cfg.set_resolution(64, 64);
+            assert!(matches!(validate(&cfg), Err(Error::InvalidArgument(_))));
+            cfg.set_frame_rate(30, 0);
+            assert!(matches!(validate(&cfg), Err(Error::InvalidArgument(_))));
+            cfg.set_frame_rate(30, 1);
+            validate(&cfg).unwrap();
+        }
+
+        #[test]
+        fn failed_init_frees_the_handle_without_deinit() {
+            let mut failing = MockEncoder::new();
+            failing.init_result = 0x80001000u32 as i32;
+            let mock = failing.arm();
+            let mut builder = Encoder::builder().unwrap();
+            builder.config_mut().set_resolution(64, 64);
+            builder.config_mut().set_frame_rate(30, 1);
+            assert!(matches!(builder.build(), Err(Error::Code(_))));
+            assert_eq!(mock.calls.get("init"), 1);
+            assert_eq!(mock.calls.get("deinit"), 0);
+            assert_eq!(mock.calls.get("deinit_handle"), 1);
+        }
+
+        // Compiles only while `T` is not `Sync`: with a `Sync` impl the
+        // inferred parameter becomes ambiguous.
+        trait AmbiguousIfSync<A> {
+            fn check() {}
+        }
+        impl<T: ?Sized> AmbiguousIfSync<()> for T {}
+        struct IsSync;
+        impl<T: ?Sized + Sync> AmbiguousIfSync<IsSync> for T {}
+
+        #[test]
+        fn threading_bounds() {
+            fn send<T: Send>() {}
+            fn send_sync_clone<T: Send + Sync + Clone>() {}
+            send::<Encoder>();
+            send::<Packet>();
+            send::<Handle>();
+            send::<FrameSender>();
+            send::<PacketReceiver>();
+            send_sync_clone::<InputHandle>();
+            <Encoder as AmbiguousIfSync<_>>::check();
+            <Handle as AmbiguousIfSync<_>>::check();
+            <PacketReceiver as AmbiguousIfSync<_>>::check();
+        }
+
+        // These exercise only the gate, so they also run under Miri.
+        #[test]
+        fn input_gate_rejects_sends_after_finish() {
+            let gate = InputGate::new(8);
+            gate.send(|| Ok(())).unwrap();
+            gate.finish(|| Ok(())).unwrap();
+            assert!(matches!(
+                gate.send(|| Ok(())),
+                Err(Error::InvalidArgument(_))
+            ));
+            assert!(matches!(
+                gate.finish(|| Ok(())),
+                Err(Error::InvalidArgument(_))
+            ));
+        }
+
+        #[test]
+        fn input_gate_bounds_pictures_in_flight() {
+            let gate = InputGate::new(2);
+            assert_eq!(gate.try_send(|| Ok(())).unwrap(), Some(()));
+            assert_eq!(gate.try_send(|| Ok(())).unwrap(), Some(()));
+            assert_eq!(gate.try_send(|| Ok(())).unwrap(), None);
+            gate.received();
+            assert_eq!(gate.try_send(|| Ok(())).unwrap(), Some(()));
+            // Blocking sends are counted but not refused.
+            gate.send(|| Ok(())).unwrap();
+            assert_eq!(
+                gate.stats(),
+                QueueStats {
+                    capacity: 2,
+                    depth: 3,
+                    peak_depth: 3,
+                    sent: 4,
+                    received: 1,
+                    would_block: 1,
+                }
+            );
+            // A failed send leaves the counters alone.
+            assert!(gate.send(|| Err::<(), _>(Error::Null)).is_err());
+            assert_eq!(gate.stats().sent, 4);
+        }
+
+        #[test]
+        #[cfg(not(loom))]
+        fn try_send_frame_hands_back_frame_when_full() {
+            use crate::config::{BitDepth, ColorFormat};
+            use crate::encoder::TrySendError;
+            use crate::frame::Frame;
+
+            let mut builder = Encoder::builder().unwrap().input_capacity(3);
+            builder
+                .config_mut()
+                .set_resolution(64, 64)
+                .set_frame_rate(30, 1)
+                .set_bit_depth(BitDepth::Eight)
+                .set_color_format(ColorFormat::Yuv420);
+            let mut enc = builder.build().unwrap();
+            assert_eq!(enc.input_capacity(), 3);
+
+            let frame = |pts| {
+                let mut frame = Frame::new(64, 64, BitDepth::Eight, ColorFormat::Yuv420);
+                frame.pts = pts;
+                frame
+            };
+            for pts in 0..3 {
+                enc.try_send_frame(frame(pts)).unwrap();
+            }
+            match enc.try_send_frame(frame(3)) {
+                Err(TrySendError::WouldBlock(rejected)) => assert_eq!(rejected.pts, 3),
+                other => panic!("expected WouldBlock, got {other:?}"),
+            }
+            assert_eq!(enc.queue_stats().depth, 3);
+            assert_eq!(enc.queue_stats().would_block, 1);
+
+            let drained = enc.packets().collect::<crate::Result<Vec<_>>>().unwrap();
+            assert_eq!(drained.len(), 3);
+            assert_eq!(enc.queue_stats().depth, 0);
+            enc.try_send_frame(frame(4)).unwrap();
+
+            let stats = enc.queue_stats();
+            assert_eq!((stats.sent, stats.received, stats.peak_depth), (4, 3, 3));
+            let rest = enc.finish().unwrap();
+            assert!(rest.last().unwrap().is_eos());
+        }
+
+        #[test]
+        #[cfg(not(loom))]
+        fn frame_pool_allocates_nothing_in_steady_state() {
+            use crate::config::{BitDepth, ColorFormat};
+            use crate::frame::FramePool;
+
+            let mut builder = Encoder::builder().unwrap();
+            builder
+                .config_mut()
+                .set_resolution(64, 48)
+                .set_frame_rate(30, 1)
+                .set_bit_depth(BitDepth::Ten)
+                .set_color_format(ColorFormat::Yuv422);
+            let pool = FramePool::for_config(builder.config()).unwrap();
+            let mut enc = builder.build().unwrap();
+
+            for pts in 0..100 {
+                let mut frame = pool.get();
+                assert_eq!((frame.width(), frame.height()), (64, 48));
+                assert_eq!(frame.bit_depth(), BitDepth::Ten);
+                assert_eq!(frame.color_format(), ColorFormat::Yuv422);
+                frame.pts = pts;
+                enc.send_frame(&frame).unwrap();
+                drop(frame);
+                enc.packets().for_each(drop);
+            }
+            assert_eq!(pool.allocations(), 1);
+            assert_eq!(pool.idle(), 1);
+
+            let kept = pool.get().detach();
+            assert_eq!(pool.idle(), 0);
+            drop(kept);
+            assert_eq!(pool.idle(), 0);
+            enc.finish().unwrap();
+        }
+
+        #[test]
+        fn frame_pool_rejects_unknown_formats() {
+            use crate::frame::FramePool;
+
+            let mut cfg: Configuration = unsafe { std::mem::zeroed() };
+            cfg.set_resolution(64, 64);
+            cfg.encoder_bit_depth = 9;
+            assert!(matches!(
+                FramePool::for_config(&cfg),
+                Err(Error::InvalidArgument(_))
+            ));
+            cfg.encoder_bit_depth = 8;
+            cfg.encoder_color_format = 7;
+            assert!(FramePool::for_config(&cfg).is_err());
+        }
+
+        #[test]
+        fn keyframe_policy_decides_by_pts() {
+            let interval = KeyframePolicy::Interval(4);
+            let keys: Vec<i64> = (-4..10).filter(|&p| interval.is_keyframe(p)).collect();
+            assert_eq!(keys, [-4, 0, 4, 8]);
+
+            let mut list = KeyframePolicy::Pts(vec![9, 3, 3, 0]);
+            list.normalize().unwrap();
+            assert_eq!(list, KeyframePolicy::Pts(vec![0, 3, 9]));
+            let keys: Vec<i64> = (0..12).filter(|&p| list.is_keyframe(p)).collect();
+            assert_eq!(keys, [0, 3, 9]);
+
+            let mut cuts = KeyframePolicy::SceneCut {
+                cuts: vec![13, 5],
+                max_interval: 6,
+            };
+            cuts.normalize().unwrap();
+            let keys: Vec<i64> = (0..30).filter(|&p| cuts.is_keyframe(p)).collect();
+            assert_eq!(keys, [0, 5, 11, 13, 19, 25]);
+
+            assert!(!KeyframePolicy::Auto.is_keyframe(0));
+            assert!(KeyframePolicy::Interval(0).normalize().is_err());
+            assert!(KeyframePolicy::SceneCut {
+                cuts: vec![],
+                max_interval: 0
+            }
+            .normalize()
+            .is_err());
+        }
+
+        #[test]
+        fn keyframe_policy_disables_library_keyframes() {
+            let mut cfg: Configuration = unsafe { std::mem::zeroed() };
+            cfg.intra_period_length = 60;
+            cfg.scene_change_detection = 1;
+            KeyframePolicy::Auto.configure(&mut cfg);
+            assert_eq!(
+                (cfg.intra_period_length, cfg.scene_change_detection),
+                (60, 1)
+            );
+            KeyframePolicy::Interval(48).configure(&mut cfg);
+            assert_eq!(cfg.intra_period_length, -1);
+            assert_eq!(cfg.scene_change_detection, 0);
+            assert_eq!(cfg.force_key_frames, 1);
+        }
+
+        #[test]
+        #[cfg(not(loom))]
+        fn keyframes_align_across_resolutions() {
+            use crate::config::{BitDepth, ColorFormat};
+            use crate::frame::Frame;
+
+            let encode = |width: u32, height: u32| {
+                let mut builder = Encoder::builder()
+                    .unwrap()
+                    .keyframe_policy(KeyframePolicy::Interval(5));
+                builder
+                    .config_mut()
+                    .set_resolution(width, height)
+                    .set_frame_rate(30, 1)
+                    .set_bit_depth(BitDepth::Eight)
+                    .set_color_format(ColorFormat::Yuv420);
+                let mut enc = builder.build().unwrap();
+                assert_eq!(enc.keyframe_policy(), &KeyframePolicy::Interval(5));
+                let mut frame = Frame::new(width, height, BitDepth::Eight, ColorFormat::Yuv420);
+                let mut packets = Vec::new();
+                for pts in 0..17 {
+                    frame.pts = pts;
+                    enc.send_frame(&frame).unwrap();
+                    packets.extend(enc.packets().map(Result::unwrap));
+                }
+                packets.extend(enc.finish().unwrap());
+                packets
+                    .iter()
+                    .filter(|p| p.is_keyframe())
+                    .map(|p| p.pts())
+                    .collect::<Vec<_>>()
+            };
+            let full = encode(64, 48);
+            assert_eq!(full, [0, 5, 10, 15]);
+            assert_eq!(encode(32, 24), full);
+        }
+
+        #[test]
+        #[cfg(not(loom))]
+        fn try_send_frame_blocks_when_the_pool_is_below_capacity() {
+            use crate::config::{BitDepth, ColorFormat};
+            use crate::frame::Frame;
+            use std::sync::mpsc;
+            use std::time::Duration;
+
+            let mut pooled = MockEncoder::new();
+            pooled.pool = Some(3);
+            let mock = pooled.arm();
+            let mut builder = Encoder::builder().unwrap();
+            builder
+                .config_mut()
+                .set_resolution(64, 64)
+                .set_frame_rate(30, 1)
+                .set_bit_depth(BitDepth::Eight)
+                .set_color_format(ColorFormat::Yuv420);
+            builder.config_mut().look_ahead_distance = 4;
+            builder.config_mut().hierarchical_levels = 2;
+            let enc = builder.build().unwrap();
+            assert_eq!(enc.input_capacity(), 8);
+
+            let (mut sender, mut receiver) = enc.split();
+            let (done, result) = mpsc::channel();
+            std::thread::spawn(move || {
+                let frame = Frame::new(64, 64, BitDepth::Eight, ColorFormat::Yuv420);
+                for _ in 0..4 {
+                    sender.try_send_frame(frame.clone()).unwrap();
+                }
+                done.send(sender.queue_stats()).unwrap();
+            });
+            while mock.calls.get("send_picture") < 4 {
+                std::thread::yield_now();
+            }
+            // The fourth picture is under the capacity but waits for the pool.
+            assert!(result.recv_timeout(Duration::from_millis(50)).is_err());
+            drop(receiver.try_recv().unwrap().unwrap());
+            let stats = result
+                .recv_timeout(Duration::from_secs(10))
+                .expect("send still blocked after a packet was released");
+            assert_eq!((stats.sent, stats.would_block), (4, 0));
+        }
+
+        #[test]
+        fn send_frame_rejects_a_frame_of_another_format() {
+            use crate::config::{BitDepth, ColorFormat};
+            use crate::encoder::TrySendError;
+            use crate::frame::Frame;
+
+            let mock = MockEncoder::new().arm();
+            let mut builder = Encoder::builder().unwrap();
+            builder
+                .config_mut()
+                .set_resolution(64, 48)
+                .set_frame_rate(30, 1)
+                .set_bit_depth(BitDepth::Eight)
+                .set_color_format(ColorFormat::Yuv420);
+            let mut enc = builder.build().unwrap();
+            let input = enc.input();
+            for frame in [
+                Frame::new(32, 48, BitDepth::Eight, ColorFormat::Yuv420),
+                Frame::new(64, 64, BitDepth::Eight, ColorFormat::Yuv420),
+                Frame::new(64, 48, BitDepth::Ten, ColorFormat::Yuv420),
+                Frame::new(64, 48, BitDepth::Eight, ColorFormat::Yuv444),
+            ] {
+                assert!(matches!(
+                    enc.send_frame(&frame),
+                    Err(Error::InvalidArgument(_))
+                ));
+                assert!(matches!(
+                    input.send_frame(&frame),
+                    Err(Error::InvalidArgument(_))
+                ));
+                assert!(matches!(
+                    enc.try_send_frame(frame),
+                    Err(TrySendError::Failed(Error::InvalidArgument(_)))
+                ));
+            }
+            assert_eq!(mock.calls.get("send_picture"), 0);
+            assert_eq!(enc.queue_stats().sent, 0);
+
+            let frame = Frame::new(64, 48, BitDepth::Eight, ColorFormat::Yuv420);
+            enc.send_frame(&frame).unwrap();
+            assert_eq!(mock.calls.get("send_picture"), 1);
+        }
+
+        #[test]
+        fn recv_recon_rejects_a_frame_of_another_format() {
+            use crate::config::{BitDepth, ColorFormat};
+            use crate::frame::Frame;
+
+            let mock = MockEncoder::new().arm();
+            let mut builder = Encoder::builder().unwrap();
+            builder
+                .config_mut()
+                .set_resolution(64, 48)
+                .set_frame_rate(30, 1)
+                .set_bit_depth(BitDepth::Eight)
+                .set_color_format(ColorFormat::Yuv420);
+            let mut enc = builder.build().unwrap();
+            for mut frame in [
+                Frame::new(32, 48, BitDepth::Eight, ColorFormat::Yuv420),
+                Frame::new(64, 64, BitDepth::Eight, ColorFormat::Yuv420),
+                Frame::new(64, 48, BitDepth::Ten, ColorFormat::Yuv420),
+                Frame::new(64, 48, BitDepth::Eight, ColorFormat::Yuv444),
+            ] {
+                assert!(matches!(
+                    enc.recv_recon(&mut frame),
+                    Err(Error::InvalidArgument(_))
+                ));
+            }
+            assert_eq!(mock.calls.get("get_recon"), 0);
+
+            let mut frame = Frame::new(64, 48, BitDepth::Eight, ColorFormat::Yuv420);
+            assert!(!enc.recv_recon(&mut frame).unwrap());
+            assert_eq!(mock.calls.get("get_recon"), 1);
+        }
+
+        #[test]
+        fn build_rejects_zero_input_capacity() {
+            let mut builder = Encoder::builder().unwrap().input_capacity(0);
+            builder
+                .config_mut()
+                .set_resolution(64, 64)
+                .set_frame_rate(30, 1);
+            assert!(matches!(builder.build(), Err(Error::InvalidArgument(_))));
+        }
+
+        #[test]
+        #[cfg(not(loom))]
+        fn input_gate_orders_eos_last() {
+            use std::sync::{Arc, Mutex};
+
+            let gate = Arc::new(InputGate::new(8));
+            let log = Arc::new(Mutex::new(Vec::new()));
+            let senders: Vec<_> = (0..4)
+                .map(|id| {
+                    let (gate, log) = (Arc::clone(&gate), Arc::clone(&log));
+                    std::thread::spawn(move || {
+                        for _ in 0..50 {
+                            let _ = gate.send(|| {
+                                log.lock().unwrap().push(id);
+                                Ok(())
+                            });
+                        }
+                    })
+                })
+                .collect();
+            gate.finish(|| {
+                log.lock().unwrap().push(u32::MAX);
+                Ok(())
+            })
+            .unwrap();
+            for sender in senders {
+                sender.join().unwrap();
+            }
+            let log = log.lock().unwrap();
+            assert_eq!(log.last(), Some(&u32::MAX));
+            assert_eq!(log.iter().filter(|&&id| id == u32::MAX).count(), 1);
+        }
+
+        #[test]
+        #[cfg(not(loom))]
+        fn input_gate_wakes_waiter_on_finish() {
+            use std::sync::Arc;
+            use std::time::Duration;
+
+            let gate = Arc::new(InputGate::new(8));
+            assert!(!gate.wait_finished(Duration::from_millis(1)));
+            let waiter = {
+                let gate = Arc::clone(&gate);
+                std::thread::spawn(move || {
+                    while !gate.wait_finished(Duration::from_secs(5)) {}
+                    gate.is_finished()
+                })
+            };
+            gate.finish(|| Ok(())).unwrap();
+            assert!(waiter.join().unwrap());
+        }
+
+        #[test]
+        #[cfg(not(loom))]
+        fn input_gate_stays_unfinished_when_eos_fails() {
+            use std::time::Duration;
+
+            let gate = InputGate::new(8);
+            assert!(gate.finish(|| Err::<(), _>(Error::Code(-1))).is_err());
+            assert!(!gate.is_finished());
+            assert!(!gate.wait_finished(Duration::from_millis(1)));
+            assert!(matches!(gate.failed(), Err(Error::InvalidArgument(_))));
+            assert!(gate.send(|| Ok(())).is_err());
+        }
+
+        #[test]
+        #[cfg(not(loom))]
+        fn recv_fails_instead_of_waiting_when_eos_fails() {
+            use crate::config::{BitDepth, ColorFormat};
+            use crate::frame::Frame;
+            use std::sync::mpsc;
+            use std::time::Duration;
+
+            let mut failing = MockEncoder::new();
+            failing.eos_result = -1;
+            failing.arm();
+            let mut builder = Encoder::builder().unwrap();
+            builder
+                .config_mut()
+                .set_resolution(64, 64)
+                .set_frame_rate(30, 1)
+                .set_bit_depth(BitDepth::Eight)
+                .set_color_format(ColorFormat::Yuv420);
+            let (mut sender, mut receiver) = builder.build().unwrap().split();
+            sender
+                .send_frame(&Frame::new(64, 64, BitDepth::Eight, ColorFormat::Yuv420))
+                .unwrap();
+            assert!(matches!(sender.finish(), Err(Error::Code(-1))));
+
+            let (done, result) = mpsc::channel();
+            std::thread::spawn(move || {
+                let packet = receiver.recv().unwrap().unwrap();
+                let failed = receiver.recv().map(|packet| packet.is_some());
+                done.send((packet.pts(), failed)).unwrap();
+            });
+            let (pts, failed) = result
+                .recv_timeout(Duration::from_secs(10))
+                .expect("recv kept waiting for an end of stream that never came");
+            assert_eq!(pts, 0);
+            assert!(matches!(failed, Err(Error::InvalidArgument(_))));
+        }
+
+        #[test]
+        #[cfg(not(loom))]
+        fn receiver_runs_while_a_send_waits_for_the_pool() {
+            use crate::config::{BitDepth, ColorFormat};
+            use crate::frame::Frame;
+            use std::sync::{mpsc, Arc};
+            use std::time::Duration;
+
+            let mut pooled = MockEncoder::new();
+            pooled.pool = Some(1);
+            let mock = pooled.arm();
+            let mut builder = Encoder::builder().unwrap();
+            builder
+                .config_mut()
+                .set_resolution(64, 64)
+                .set_frame_rate(30, 1)
+                .set_bit_depth(BitDepth::Eight)
+                .set_color_format(ColorFormat::Yuv420);
+            let (mut sender, mut receiver) = builder.build().unwrap().split();
+            let input = std::thread::spawn(move || {
+                let mut frame = Frame::new(64, 64, BitDepth::Eight, ColorFormat::Yuv420);
+                for pts in 0..3 {
+                    frame.pts = pts;
+                    sender.send_frame(&frame).unwrap();
+                }
+                sender.finish().unwrap();
+            });
+
+            // The second send holds the gate until the first packet is
+            // released; the receiver must get to it regardless.
+            let (done, result) = mpsc::channel();
+            let watched = Arc::clone(&mock);
+            std::thread::spawn(move || {
+                while watched.calls.get("send_picture") < 2 {
+                    std::thread::yield_now();
+                }
+                let stats = receiver.queue_stats();
+                let mut pts = Vec::new();
+                for packet in &mut receiver {
+                    let packet = packet.unwrap();
+                    if !packet.is_eos() {
+                        pts.push(packet.pts());
+                    }
+                }
+                done.send((stats.sent, pts)).unwrap();
+            });
+            let (sent, pts) = result
+                .recv_timeout(Duration::from_secs(10))
+                .expect("receiver blocked behind a waiting sender");
+            assert_eq!(sent, 1);
+            assert_eq!(pts, [0, 1, 2]);
+            input.join().unwrap();
+            assert_eq!(mock.calls.get("release_out_buffer"), 4);
+        }
+
+        #[test]
+        #[cfg(loom)]
+        fn loom_receiver_sees_eos_after_wait() {
+            use loom::sync::atomic::{AtomicBool, Ordering};
+            use loom::sync::Arc;
+            use std::time::Duration;
+
+            loom::model(|| {
+                let gate = Arc::new(InputGate::new(8));
+                let eos_sent = Arc::new(AtomicBool::new(false));
+                let receiver = {
+                    let (gate, eos_sent) = (Arc::clone(&gate), Arc::clone(&eos_sent));
+                    loom::thread::spawn(move || {
+                        while !gate.wait_finished(Duration::from_millis(1)) {}
+                        assert!(eos_sent.load(Ordering::Relaxed));
+                    })
+                };
+                gate.finish(|| {
+                    eos_sent.store(true, Ordering::Relaxed);
+                    Ok(())
+                })
+                .unwrap();
+                receiver.join().unwrap();
+            });
+        }
+
+        #[test]
+        #[cfg(loom)]
+        fn loom_receiver_counts_while_sender_waits() {
+            use loom::sync::atomic::{AtomicBool, Ordering};
+            use loom::sync::Arc;
+
+            // A send blocked on the pipeline holds the gate until the receiver
+            // has taken a packet; nothing the receiver does may need the gate.
+            loom::model(|| {
+                let gate = Arc::new(InputGate::new(1));
+                let released = Arc::new(AtomicBool::new(false));
+                let receiver = {
+                    let (gate, released) = (Arc::clone(&gate), Arc::clone(&released));
+                    loom::thread::spawn(move || {
+                        assert!(!gate.is_finished());
+                        gate.stats();
+                        gate.received();
+                        released.store(true, Ordering::Release);
+                    })
+                };
+                gate.send(|| {
+                    while !released.load(Ordering::Acquire) {
+                        loom::thread::yield_now();
+                    }
+                    Ok(())
+                })
+                .unwrap();
+                receiver.join().unwrap();
+                assert_eq!(gate.stats().received, 1);
+            });
+        }
+
+        #[test]
+        #[cfg(loom)]
+        fn loom_input_gate_orders_eos_last() {
+            use loom::sync::{Arc, Mutex};
+
+            loom::model(|| {
+                let gate = Arc::new(InputGate::new(8));
+                let log = Arc::new(Mutex::new(Vec::new()));
+                let sender = {
+                    let (gate, log) = (Arc::clone(&gate), Arc::clone(&log));
+                    loom::thread::spawn(move || {
+                        let _ = gate.send(|| {
+                            log.lock().unwrap().push("picture");
+                            Ok(())
+                        });
+                    })
+                };
+                gate.finish(|| {
+                    log.lock().unwrap().push("eos");
+                    Ok(())
+                })
+                .unwrap();
+                sender.join().unwrap();
+                assert_eq!(log.lock().unwrap().last(), Some(&"eos"));
+            });
+        }
+    }
+
+    #[cfg(feature = "encoder")]
+    mod settings {
+        use crate::encoder::{Configuration, Encoder};
+        use crate::settings::{ContentLightLevel, EncoderSettings};
+
+        /// A configuration whose bytes all differ, so every field holds a
+        /// distinct, non-zero value.
+        fn patterned() -> Configuration {
+            let mut cfg: Configuration = unsafe { std::mem::zeroed() };
+            // SAFETY: every field of the struct is an integer or a raw
+            // pointer, so any byte pattern is a valid value. The pointers are
+            // never dereferenced.
+            let bytes = unsafe {
+                std::slice::from_raw_parts_mut(
+                    &mut cfg as *mut Configuration as *mut u8,
+                    std::mem::size_of::<Configuration>(),
+                )
+            };
+            for (i, b) in bytes.iter_mut().enumerate() {
+                *b = (i % 251) as u8 + 1;
+            }
+            cfg
+        }
+
+        #[test]
+        fn every_field_round_trips() {
+            let cfg = patterned();
+            let settings = EncoderSettings::from_configuration(&cfg);
+            let zeroed: Configuration = unsafe { std::mem::zeroed() };
+            // Every field differs from zero, so all of them are compared below.
+            assert_eq!(EncoderSettings::changes(&zeroed, &cfg), settings);
+
+            let mut copy = zeroed;
+            settings.apply_to(&mut copy);
+            assert_eq!(EncoderSettings::from(&copy), settings);
+        }
+
+        #[test]
+        fn sparse_settings_override_only_what_they_set() {
+            let defaults = *Encoder::builder().unwrap().config();
+            let mut cfg = defaults;
+            cfg.enc_mode = 6;
+            cfg.qp = 30;
+            cfg.content_light_level.max_cll = 1000;
+            let changes = EncoderSettings::changes(&defaults, &cfg);
+            assert_eq!(
+                changes,
+                EncoderSettings {
+                    enc_mode: Some(6),
+                    qp: Some(30),
+                    content_light_level: Some(ContentLightLevel {
+                        max_cll: 1000,
+                        max_fall: defaults.content_light_level.max_fall,
+                    }),
+                    ..Default::default()
+                }
+            );
+            let rebuilt = changes.to_configuration().unwrap();
+            assert_eq!(
+                EncoderSettings::changes(&cfg, &rebuilt),
+                EncoderSettings::default()
+            );
+            assert!(EncoderSettings::changes(&defaults, &defaults).is_empty());
+
+            let builder = Encoder::builder().unwrap().with_settings(&changes);
+            assert_eq!((builder.config().enc_mode, builder.config().qp), (6, 30));
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn loads_toml_and_json_profiles() {
+            use crate::settings::MasteringDisplay;
+
+            let profile = r#"
+                enc_mode = 6
+                qp = 30
+                tune = 0
+                film_grain_denoise_strength = 8
+
+                [mastering_display]
+                r = [34000, 16000]
+                g = [13250, 34500]
+                b = [7500, 3000]
+                white_point = [15635, 16450]
+                max_luma = 10000000
+                min_luma = 50
+            "#;
+            let settings: EncoderSettings = toml::from_str(profile).unwrap();
+            assert_eq!(settings.enc_mode, Some(6));
+            assert_eq!(settings.film_grain_denoise_strength, Some(8));
+            assert_eq!(settings.source_width, None);
+            assert_eq!(
+                settings.mastering_display,
+                Some(MasteringDisplay {
+                    r: [34000, 16000],
+                    g: [13250, 34500],
+                    b: [7500, 3000],
+                    white_point: [15635, 16450],
+                    max_luma: 10_000_000,
+                    min_luma: 50,
+                })
+            );
+            let json = r#"{"enc_mode":6,"qp":30,"tune":0,"film_grain_denoise_strength":8}"#;
+            let from_json: EncoderSettings = serde_json::from_str(json).unwrap();
+            assert_eq!(
+                from_json,
+                EncoderSettings {
+                    mastering_display: None,
+                    ..settings.clone()
+                }
+            );
+            // Unset fields are not written.
+            assert_eq!(serde_json::to_string(&from_json).unwrap(), json);
+
+            assert!(toml::from_str::<EncoderSettings>("preset = 6").is_err());
+            assert!(serde_json::from_str::<EncoderSettings>(r#"{"qp":-1}"#).is_err());
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn every_field_survives_toml_and_json() {
+            let settings = EncoderSettings::from_configuration(&patterned());
+            let json = serde_json::to_string(&settings).unwrap();
+            assert_eq!(
+                serde_json::from_str::<EncoderSettings>(&json).unwrap(),
+                settings
+            );
+            let toml = toml::to_string(&settings).unwrap();
+            assert_eq!(toml::from_str::<EncoderSettings>(&toml).unwrap(), settings);
+        }
+    }
+
+    #[cfg(feature = "encoder")]
+    mod params {
+        use super::fixtures;
+        use crate::config::ConfigExt;
+        use crate::encoder::{Configuration, Encoder};
+        use crate::settings::{EncoderSettings, MasteringDisplay};
+        use crate::Error;
+
+        fn defaults() -> Configuration {
+            fixtures::encoder_config(64, 48)
+        }
+
+        fn failing_key(cfg: &mut Configuration, params: &str) -> (String, String) {
+            match cfg.apply_params(params) {
+                Err(Error::Parameter { key, reason }) => (key, reason),
+                other => panic!("expected a parameter error, got {other:?}"),
+            }
+        }
+
+        #[test]
+        fn applies_command_line_options() {
+            let mut cfg = defaults();
+            cfg.apply_params("--preset 6 --crf 30 --tune 0 --film-grain 8")
+                .unwrap();
+            assert_eq!(cfg.enc_mode, 6);
+            assert_eq!((cfg.qp, cfg.rate_control_mode), (30, 0));
+            assert_eq!(cfg.tune, 0);
+            assert_eq!(cfg.film_grain_denoise_strength, 8);
+
+            cfg.apply_params("--keyint=120 --rc 1 --tbr 2500 --sharpness -2")
+                .unwrap();
+            assert_eq!(cfg.intra_period_length, 119);
+            assert_eq!(cfg.rate_control_mode, 1);
+            assert_eq!(cfg.target_bit_rate, 2_500_000);
+            assert_eq!(cfg.sharpness, -2);
+        }
+
+        #[test]
+        fn applies_svtav1_params() {
+            let mut cfg = defaults();
+            cfg.apply_params("preset=4:crf=28:keyint=-1:content-light=1000,400")
+                .unwrap();
+            assert_eq!((cfg.enc_mode, cfg.qp), (4, 28));
+            assert_eq!(cfg.intra_period_length, -1);
+            assert_eq!(
+                (
+                    cfg.content_light_level.max_cll,
+                    cfg.content_light_level.max_fall
+                ),
+                (1000, 400)
+            );
+
+            cfg.apply_params(
+                "mastering-display=G(0.265,0.69)B(0.15,0.06)R(0.68,0.32)WP(0.3127,0.329)L(1000,0.005)",
+            )
+            .unwrap();
+            assert_eq!(
+                MasteringDisplay::from(cfg.mastering_display),
+                MasteringDisplay {
+                    r: [34000, 16000],
+                    g: [13250, 34500],
+                    b: [7500, 3000],
+                    white_point: [15635, 16450],
+                    max_luma: 10_000_000,
+                    min_luma: 50,
+                }
+            );
+        }
+
+        #[test]
+        fn reports_the_failing_key() {
+            let mut cfg = defaults();
+            let before = EncoderSettings::from(&cfg);
+            let cases = [
+                ("--preset 6 --bogus 1", "bogus", "unknown parameter"),
+                ("--preset 200", "preset", "200 is outside -128..=127"),
+                ("preset=6:qp=high", "qp", "expected an integer, got `high`"),
+                ("--tune", "tune", "missing value"),
+                ("preset=6:tune", "tune", "expected `key=value`"),
+                ("--keyint 0", "keyint", "0 is not a GOP length, -1 or -2"),
+                (
+                    "content-light=1000",
+                    "content-light",
+                    "expected `max_cll,max_fall`",
+                ),
+                (
+                    "mastering-display=G(0.2,0.7)",
+                    "mastering-display",
+                    "expected G, B",
+                ),
+            ];
+            for (params, key, reason) in cases {
+                let (k, r) = failing_key(&mut cfg, params);
+                assert_eq!(k, key, "{params}");
+                assert!(r.starts_with(reason), "{params}: {r}");
+            }
+            // A failed string leaves the configuration untouched.
+            assert_eq!(EncoderSettings::from(&cfg), before);
+
+            let err = Encoder::parse_parameter_str(&mut cfg, "preset", "6\0").unwrap_err();
+            assert!(
+                matches!(&err, Error::Parameter { key, reason }
+                    if key == "preset" && reason == "value contains a NUL byte"),
+                "{err:?}"
+            );
+        }
+
+        #[test]
+        fn params_string_reproduces_the_configuration() {
+            let mut cfg = defaults();
+            cfg.apply_params(
+                "--preset 5 --crf 32 --keyint 240 --tbr 3000 --sharpness -1 \
+                 --mastering-display G(0.265,0.69)B(0.15,0.06)R(0.68,0.32)WP(0.3127,0.329)L(1000,0.005)",
+            )
+            .unwrap();
+            let params = cfg.to_params_string().unwrap();
+            assert!(params.starts_with("--preset 5 --keyint 240 "), "{params}");
+            assert!(params.contains(" --tbr 3000 "), "{params}");
+
+            let mut copy = defaults();
+            copy.apply_params(&params).unwrap();
+            assert_eq!(
+                EncoderSettings::changes(&cfg, &copy),
+                EncoderSettings::default()
+            );
+        }
+
+        #[test]
+        fn keyint_in_seconds_round_trips() {
+            let mut cfg = defaults();
+            cfg.apply_params("--keyint 5s").unwrap();
+            assert_eq!((cfg.intra_period_length, cfg.multiply_keyint), (5, 1));
+            let params = cfg.to_params_string().unwrap();
+            assert!(params.contains("--keyint 5s "), "{params}");
+
+            cfg.apply_params("keyint=240").unwrap();
+            assert_eq!((cfg.intra_period_length, cfg.multiply_keyint), (239, 0));
+            let (key, reason) = failing_key(&mut cfg, "--keyint 0s");
+            assert_eq!(
+                (key.as_str(), reason.as_str()),
+                ("keyint", "0s is not a GOP length")
+            );
+        }
+
+        #[test]
+        fn params_string_refuses_what_options_cannot_express() {
+            let mut cfg = defaults();
+            cfg.target_bit_rate = 2_500_500;
+            let err = cfg.to_params_string().unwrap_err();
+            assert!(
+                matches!(&err, Error::Parameter { key, .. } if key == "tbr"),
+                "{err:?}"
+            );
+
+            let mut cfg = defaults();
+            cfg.recon_enabled = 1 - cfg.recon_enabled;
+            let err = cfg.to_params_string().unwrap_err();
+            assert!(
+                matches!(&err, Error::Parameter { key, .. } if key == "recon_enabled"),
+                "{err:?}"
+            );
+        }
+    }
+
+    #[cfg(feature = "encoder")]
+    mod diff {
+        use super::fixtures;
+        use crate::config::ConfigExt;
+        use crate::encoder::Configuration;
+        use crate::settings::{fields, ConfigDiff};
+
+        fn defaults() -> Configuration {
+            fixtures::encoder_config(64, 48)
+        }
+
+        #[test]
+        fn lists_changed_fields_by_option_name() {
+            let old = defaults();
+            let mut new = old;
+            new.apply_params("--preset 4 --keyint 241 --content-light 1000,400")
+                .unwrap();
+            new.recon_enabled = 1;
+            new.target_bit_rate = old.target_bit_rate + 1;
+
+            let diff = ConfigDiff::between(&old, &new);
+            let keys: Vec<_> = diff.changes().iter().map(|c| c.key).collect();
+            assert_eq!(
+                keys,
+                [
+                    "preset",
+                    "keyint",
+                    "content-light",
+                    "target_bit_rate",
+                    "recon_enabled"
+                ]
+            );
+            let preset = &diff.changes()[0];
+            assert_eq!(preset.field.name(), "enc_mode");
+            assert_eq!(preset.old, old.enc_mode.to_string());
+            assert_eq!(preset.new, "4");
+            // A sub-kbps change falls back to the raw field in bps.
+            assert_eq!(diff.changes()[3].new, (old.target_bit_rate + 1).to_string());
+
+            let text = diff.to_string();
+            assert_eq!(text.lines().count(), 5);
+            assert!(text.contains("keyint: "), "{text}");
+            assert!(text.contains(" -> 241\n"), "{text}");
+
+            assert!(ConfigDiff::between(&old, &old).is_empty());
+            assert_eq!(ConfigDiff::between(&new, &old).len(), 5);
+        }
+
+        #[test]
+        fn summary_lists_every_field_by_section() {
+            let mut cfg = defaults();
+            cfg.apply_params("--preset 6 --crf 30").unwrap();
+            let text = cfg.summary().to_string();
+
+            let headings: Vec<_> = text.lines().filter(|l| l.ends_with(':')).collect();
+            assert_eq!(headings[0], "Preset and GOP structure:");
+            assert_eq!(headings.len(), 8);
+            let rows = text.lines().filter(|l| l.starts_with("  ")).count();
+            assert_eq!(rows, fields().len());
+
+            let preset = text
+                .lines()
+                .find(|l| l.trim_start().starts_with("preset "))
+                .unwrap();
+            assert!(preset.ends_with(" 6"), "{preset}");
+            assert!(text.contains("\n  recon_enabled "), "{text}");
+        }
+    }
+
+    #[cfg(all(feature = "encoder", not(loom)))]
+    mod ladder {
+        use super::fixtures;
+        use crate::config::{BitDepth, ColorFormat};
+        use crate::encoder::{Configuration, KeyframePolicy};
+        use crate::frame::Frame;
+        use crate::ladder::{Ladder, RateControl, Rendition};
+        use crate::scale::Filter;
+
+        fn base() -> Configuration {
+            let mut cfg = fixtures::encoder_config(64, 48);
+            cfg.intra_period_length = 4;
+            cfg.scene_change_detection = 1;
+            cfg
+        }
+
+        #[test]
+        fn rendition_overrides_base_configuration() {
+            let mut cfg = base();
+            Rendition::new(32, 24)
+                .with_preset(8)
+                .with_rate_control(RateControl::Vbr(500_000))
+                .configure(&mut cfg);
+            assert_eq!((cfg.source_width, cfg.source_height), (32, 24));
+            assert_eq!(cfg.enc_mode, 8);
+            assert_eq!(cfg.rate_control_mode, 1);
+            assert_eq!(cfg.target_bit_rate, 500_000);
+            assert_eq!(cfg.scene_change_detection, 0);
+            assert_eq!(cfg.intra_period_length, 4);
+
+            let mut cfg = base();
+            Rendition::new(16, 12)
+                .with_rate_control(RateControl::Crf(35))
+                .configure(&mut cfg);
+            assert_eq!((cfg.rate_control_mode, cfg.qp), (0, 35));
+        }
+
+        #[test]
+        fn encodes_every_rendition() {
+            let renditions = [
+                Rendition::new(64, 48),
+                Rendition::new(32, 24).with_rate_control(RateControl::Crf(40)),
+                Rendition::new(15, 11),
+            ];
+            let (ladder, outputs) = Ladder::new(
+                &base(),
+                &renditions,
+                Filter::Bicubic,
+                KeyframePolicy::Interval(4),
+            )
+            .unwrap();
+            assert_eq!(ladder.renditions(), &renditions);
+            let collectors: Vec<_> = outputs
+                .into_iter()
+                .map(|receiver| {
+                    std::thread::spawn(move || {
+                        receiver
+                            .map(|packet| {
+                                let packet = packet.unwrap();
+                                (packet.pts(), packet.is_keyframe(), packet.is_eos())
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            for pts in 0..12 {
+                let mut frame = Frame::new(64, 48, BitDepth::Eight, ColorFormat::Yuv420);
+                frame.pts = pts;
+                ladder.send_frame(frame).unwrap();
+            }
+            ladder.finish().unwrap();
+
+            let streams: Vec<_> = collectors.into_iter().map(|c| c.join().unwrap()).collect();
+            for stream in &streams {
+                let (eos, packets) = stream.split_last().unwrap();
+                assert!(eos.2);
+                let pts: Vec<i64> = packets.iter().map(|p| p.0).collect();
+                assert_eq!(pts, (0..12).collect::<Vec<_>>());
+            }
+            let keys = |stream: &Vec<(i64, bool, bool)>| {
+                stream
+                    .iter()
+                    .filter(|p| p.1)
+                    .map(|p| p.0)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(keys(&streams[0]), [0, 4, 8]);
+            assert!(streams.iter().all(|s| keys(s) == keys(&streams[0])));
+        }
+
+        #[test]
+        fn rejects_empty_ladder_and_mismatched_frames() {
+            assert!(Ladder::new(&base(), &[], Filter::Bilinear, KeyframePolicy::Auto).is_err());
+
+            let (ladder, outputs) = Ladder::new(
+                &base(),
+                &[Rendition::new(32, 24)],
+                Filter::Bilinear,
+                KeyframePolicy::Auto,
+            )
+            .unwrap();
+            drop(outputs);
+            let frame = Frame::new(64, 48, BitDepth::Ten, ColorFormat::Yuv420);
+            ladder.send_frame(frame).unwrap();
+            assert!(ladder.finish().is_err());
+        }
+    }
+
+    #[cfg(all(feature = "encoder", not(loom)))]
+    mod chunked {
+        use super::fixtures;
+        use crate::bitstream::{self, Framing};
+        use crate::chunked::{ChunkPacket, ChunkSplit, ChunkedEncoder};
+        use crate::config::{BitDepth, ColorFormat};
+        use crate::container::ivf::IvfFile;
+        use crate::encoder::{Configuration, KeyframePolicy};
+        use crate::frame::Frame;
+        use crate::Result;
+        use std::io::Cursor;
+
+        fn base() -> Configuration {
+            fixtures::encoder_config(32, 24)
+        }
+
+        fn frames(count: usize) -> impl Iterator<Item = Result<Frame>> {
+            (0..count).map(|i| {
+                let mut frame = Frame::new(32, 24, BitDepth::Eight, ColorFormat::Yuv420);
+                // Input pts are ignored by the chunked encoder.
+                frame.pts = 1000 + i as i64;
+                Ok(frame)
+            })
+        }
+
+        fn encode(
+            encoder: &ChunkedEncoder,
+            count: usize,
+        ) -> (Vec<std::ops::Range<usize>>, Vec<ChunkPacket>) {
+            let mut packets = Vec::new();
+            let ranges = encoder
+                .encode(frames(count), |packet| {
+                    packets.push(packet);
+                    Ok(())
+                })
+                .unwrap();
+            (ranges, packets)
+        }
+
+        fn keys(packets: &[ChunkPacket]) -> Vec<i64> {
+            packets
+                .iter()
+                .filter(|p| p.keyframe)
+                .map(|p| p.pts)
+                .collect()
+        }
+
+        #[test]
+        fn concatenates_chunks_in_order() {
+            let encoder = ChunkedEncoder::new(base(), ChunkSplit::Interval(4)).jobs(3);
+            let (ranges, packets) = encode(&encoder, 10);
+            assert_eq!(ranges, [0..4, 4..8, 8..10]);
+            let pts: Vec<i64> = packets.iter().map(|p| p.pts).collect();
+            assert_eq!(pts, (0..10).collect::<Vec<_>>());
+            let chunks: Vec<usize> = packets.iter().map(|p| p.chunk).collect();
+            assert_eq!(chunks, [0, 0, 0, 0, 1, 1, 1, 1, 2, 2]);
+            assert_eq!(keys(&packets), [0, 4, 8]);
+        }
+
+        #[test]
+        fn splits_at_scene_cuts_and_max_len() {
+            let split = ChunkSplit::SceneCuts {
+                cuts: vec![7, 2],
+                max_len: 3,
+            };
+            let encoder = ChunkedEncoder::new(base(), split).jobs(2);
+            let (ranges, packets) = encode(&encoder, 11);
+            assert_eq!(ranges, [0..2, 2..5, 5..7, 7..10, 10..11]);
+            assert_eq!(keys(&packets), [0, 2, 5, 7, 10]);
+        }
+
+        #[test]
+        fn keyframe_policy_uses_global_frame_index() {
+            let encoder = ChunkedEncoder::new(base(), ChunkSplit::Interval(5))
+                .jobs(2)
+                .keyframe_policy(KeyframePolicy::Interval(3));
+            let (_, packets) = encode(&encoder, 10);
+            assert_eq!(keys(&packets), [0, 3, 5, 6, 9]);
+        }
+
+        #[test]
+        fn writes_ivf_and_obu_streams() {
+            let encoder = ChunkedEncoder::new(base(), ChunkSplit::Interval(4)).jobs(2);
+            let ivf = encoder
+                .encode_ivf(frames(10), Cursor::new(Vec::new()))
+                .unwrap()
+                .into_inner();
+            let file = IvfFile::parse(&ivf).unwrap();
+            assert_eq!((file.width, file.height, file.frame_count), (32, 24, 10));
+            let pts: Vec<i64> = file.frames.iter().map(|f| f.pts).collect();
+            assert_eq!(pts, (0..10).collect::<Vec<_>>());
+
+            let obu = encoder.encode_obu(frames(10), Vec::new()).unwrap();
+            let units = bitstream::temporal_units(&obu, Framing::Section5).unwrap();
+            assert_eq!(units.len(), 10);
+        }
+
+        #[test]
+        fn rejects_empty_chunks_and_no_jobs() {
+            let empty = ChunkedEncoder::new(base(), ChunkSplit::Interval(0));
+            assert!(empty.encode(frames(2), |_| Ok(())).is_err());
+            let no_jobs = ChunkedEncoder::new(base(), ChunkSplit::Interval(2)).jobs(0);
+            assert!(no_jobs.encode(frames(2), |_| Ok(())).is_err());
+        }
+    }
+
+    #[cfg(feature = "async")]
+    mod async_encoder {
+        use crate::async_encoder::AsyncEncoder;
+        use crate::config::{BitDepth, ColorFormat, ConfigExt};
+        use crate::encoder::Encoder;
+        use crate::frame::Frame;
+
+        #[test]
+        fn encodes_synthetic_frames_on_current_thread_runtime() {
+            const FRAMES: i64 = 12;
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            runtime.block_on(async {
+                let mut builder = Encoder::builder().unwrap();
+                builder
+                    .config_mut()
+                    .set_resolution(64, 64)
+                    .set_frame_rate(30, 1)
+                    .set_bit_depth(BitDepth::Eight)
+                    .set_color_format(ColorFormat::Yuv420);
+                let mut enc = AsyncEncoder::new(builder.build().unwrap()).unwrap();
+
+                let sender = enc.sender().unwrap();
+                let producer = tokio::spawn(async move {
+                    for pts in 0..FRAMES {
+                        let mut frame = Frame::new(64, 64, BitDepth::Eight, ColorFormat::Yuv420);
+                        frame.plane_mut(0).fill((pts * 16) as u8);
+                        frame.pts = pts;
+                        sender.send(frame).await.unwrap();
+                    }
+                });
+                enc.finish();
+
+                let mut pts = Vec::new();
+                let mut saw_eos = false;
+                while let Some(packet) = enc.next_packet().await {
+                    let packet = packet.unwrap();
+                    assert!(!saw_eos, "packet after end of stream");
+                    saw_eos = packet.is_eos();
+                    if !packet.data().is_empty() {
+                        pts.push(packet.pts());
+                    }
+                }
+                producer.await.unwrap();
+                assert!(saw_eos);
+                pts.sort_unstable();
+                assert_eq!(pts, (0..FRAMES).collect::<Vec<_>>());
+                assert!(enc
+                    .send(Frame::new(64, 64, BitDepth::Eight, ColorFormat::Yuv420))
+                    .await
+                    .is_err());
+            });
+        }
+    }
 }
@@ -0,0 +1,362 @@
+#[cfg(feature = "encoder")]
+use std::collections::HashMap;
+#[cfg(feature = "encoder")]
+use std::thread;
+
+#[cfg(feature = "encoder")]
+use crate::config::{ConfigExt, RcMode};
+#[cfg(feature = "encoder")]
+use crate::encoder::{Configuration, Encoder};
+use crate::frame::Frame;
+use crate::{Error, Result};
+
+/// PSNR reported for identical pictures, in dB.
+pub const MAX_PSNR: f64 = 100.0;
+
+/// Quality goal, scored on luma and averaged over the frames of a clip.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Target {
+    /// Mean SSIM of at least this value, from 0 to 1.
+    Ssim(f64),
+    /// Mean PSNR of at least this many dB.
+    Psnr(f64),
+}
+
+impl Target {
+    fn value(self) -> f64 {
+        match self {
+            Self::Ssim(v) | Self::Psnr(v) => v,
+        }
+    }
+
+    /// Scores `distorted` against `reference` in this target's metric.
+    pub fn score(self, reference: &Frame, distorted: &Frame) -> Result<f64> {
+        match self {
+            Self::Ssim(_) => ssim(reference, distorted),
+            Self::Psnr(_) => psnr(reference, distorted),
+        }
+    }
+
+    /// Whether a score meets the target.
+    pub fn is_met(self, score: f64) -> bool {
+        score >= self.value()
+    }
+}
+
+/// Luma samples of two comparable frames, row by row.
+fn luma_pairs<'a>(
+    reference: &'a Frame,
+    distorted: &'a Frame,
+) -> Result<impl Iterator<Item = (u32, u32)> + 'a> {
+    if (reference.width(), reference.height(), reference.bit_depth())
+        != (distorted.width(), distorted.height(), distorted.bit_depth())
+        || reference.width() == 0
+        || reference.height() == 0
+    {
+        return Err(Error::InvalidArgument(format!(
+            "cannot compare a {}x{} {:?} frame with a {}x{} {:?} frame",
+            reference.width(),
+            reference.height(),
+            reference.bit_depth(),
+            distorted.width(),
+            distorted.height(),
+            distorted.bit_depth()
+        )));
+    }
+    let bps = reference.bytes_per_sample();
+    let sample = move |row: &[u8], x: usize| -> u32 {
+        if bps == 1 {
+            row[x] as u32
+        } else {
+            u16::from_le_bytes([row[2 * x], row[2 * x + 1]]) as u32
+        }
+    };
+    let (width, height) = (reference.width() as usize, reference.height() as usize);
+    Ok((0..height).flat_map(move |y| {
+        let (a, b) = (reference.row(0, y), distorted.row(0, y));
+        (0..width).map(move |x| (sample(a, x), sample(b, x)))
+    }))
+}
+
+/// Luma PSNR in dB, capped at [`MAX_PSNR`].
+pub fn psnr(reference: &Frame, distorted: &Frame) -> Result<f64> {
+    let max = ((1u64 << reference.bit_depth().bits()) - 1) as f64;
+    let (mut sse, mut count) = (0u64, 0u64);
+    for (a, b) in luma_pairs(reference, distorted)? {
+        let d = a.abs_diff(b) as u64;
+        sse += d * d;
+        count += 1;
+    }
+    if sse == 0 {
+        return Ok(MAX_PSNR);
+    }
+    let mse = sse as f64 / count as f64;
+    Ok((10.0 * (max * max / mse).log10()).min(MAX_PSNR))
+}
+
+/// Side of the SSIM window; windows overlap by half.
+const SSIM_WINDOW: usize = 8;
+
+/// Mean luma SSIM over 8x8 windows spaced 4 samples apart.
+pub fn ssim(reference: &Frame, distorted: &Frame) -> Result<f64> {
+    let (width, height) = (reference.width() as usize, reference.height() as usize);
+    let mut samples = Vec::with_capacity(width * height);
+    samples.extend(luma_pairs(reference, distorted)?);
+
+    let max = ((1u64 << reference.bit_depth().bits()) - 1) as f64;
+    let c1 = (0.01 * max).powi(2);
+    let c2 = (0.03 * max).powi(2);
+    // Pictures smaller than a window are scored as one window.
+    let (win_w, win_h) = (SSIM_WINDOW.min(width), SSIM_WINDOW.min(height));
+    let step = SSIM_WINDOW / 2;
+    let (mut total, mut windows) = (0.0, 0usize);
+    for top in (0..=height - win_h).step_by(step) {
+        for left in (0..=width - win_w).step_by(step) {
+            let (mut sa, mut sb, mut saa, mut sbb, mut sab) = (0.0, 0.0, 0.0, 0.0, 0.0);
+            for row in samples[top * width..].chunks(width).take(win_h) {
+                for &(a, b) in &row[left..left + win_w] {
+                    let (a, b) = (a as f64, b as f64);
+                    sa += a;
+                    sb += b;
+                    saa += a * a;
+                    sbb += b * b;
+                    sab += a * b;
+                }
+            }
+            let n = (win_w * win_h) as f64;
+            let (ma, mb) = (sa / n, sb / n);
+            let va = saa / n - ma * ma;
+            let vb = sbb / n - mb * mb;
+            let cov = sab / n - ma * mb;
+            total += ((2.0 * ma * mb + c1) * (2.0 * cov + c2))
+                / ((ma * ma + mb * mb + c1) * (va + vb + c2));
+            windows += 1;
+        }
+    }
+    Ok(total / windows as f64)
+}
+
+/// One probe encode of a [`CrfSearch`].
+#[cfg(feature = "encoder")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Probe {
+    pub qp: u32,
+    /// Mean score of the reconstructed frames in the target's metric.
+    pub score: f64,
+    /// Total size of the encoded packets.
+    pub bytes: usize,
+}
+
+/// Outcome of a [`CrfSearch`].
+#[cfg(feature = "encoder")]
+#[derive(Clone, Debug, PartialEq)]
+pub struct CrfResult {
+    /// The smallest probe that met the target.
+    pub best: Probe,
+    /// Every probe, in the order they were encoded.
+    pub probes: Vec<Probe>,
+}
+
+#[cfg(feature = "encoder")]
+impl CrfResult {
+    pub fn qp(&self) -> u32 {
+        self.best.qp
+    }
+}
+
+/// Searches for the cheapest CRF (`qp` under `RcMode::CqpOrCrf`) that
+/// meets a quality [`Target`] on a clip.
+///
+/// Every probe encodes the whole clip with reconstruction enabled and
+/// scores the reconstructed frames against the source. Quality and size are
+/// assumed to fall as the QP rises. The search probes both ends of the QP
+/// range, then interpolates the score between the closest passing and
+/// failing QPs, keeping each guess within the middle half of the interval so
+/// a curved score still converges like a bisection.
+#[cfg(feature = "encoder")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CrfSearch {
+    pub target: Target,
+    pub min_qp: u32,
+    pub max_qp: u32,
+    /// Encodes allowed, including the two at the ends of the range.
+    pub max_probes: usize,
+}
+
+#[cfg(feature = "encoder")]
+impl CrfSearch {
+    /// Searches QP 1 to 63 with up to 8 probes.
+    pub fn new(target: Target) -> Self {
+        Self {
+            target,
+            min_qp: 1,
+            max_qp: 63,
+            max_probes: 8,
+        }
+    }
+
+    pub fn with_qp_range(mut self, min: u32, max: u32) -> Self {
+        self.min_qp = min;
+        self.max_qp = max;
+        self
+    }
+
+    pub fn with_max_probes(mut self, probes: usize) -> Self {
+        self.max_probes = probes;
+        self
+    }
+
+    /// Runs the search on `source` encoded with `config`, whose rate control
+    /// and recon settings are overridden per probe.
+    ///
+    /// Frames must match the configuration's resolution and have distinct
+    /// pts. Fails if even `min_qp` misses the target.
+    pub fn run(&self, source: &[Frame], config: &Configuration) -> Result<CrfResult> {
+        if self.min_qp > self.max_qp || self.max_qp > 63 {
+            return Err(Error::InvalidArgument(format!(
+                "QP range {}..={} is not within 0..=63",
+                self.min_qp, self.max_qp
+            )));
+        }
+        if self.max_probes < 2 {
+            return Err(Error::InvalidArgument(
+                "a CRF search needs at least two probes".into(),
+            ));
+        }
+        let Some(first) = source.first() else {
+            return Err(Error::InvalidArgument("the clip has no frames".into()));
+        };
+        let mut index = HashMap::with_capacity(source.len());
+        for (i, frame) in source.iter().enumerate() {
+            if (frame.width(), frame.height()) != (config.source_width, config.source_height)
+                || (frame.bit_depth(), frame.color_format())
+                    != (first.bit_depth(), first.color_format())
+            {
+                return Err(Error::InvalidArgument(format!(
+                    "frame {i} does not match the configured {}x{} stream",
+                    config.source_width, config.source_height
+                )));
+            }
+            if index.insert(frame.pts, i).is_some() {
+                return Err(Error::InvalidArgument(format!(
+                    "frame {i} repeats pts {}",
+                    frame.pts
+                )));
+            }
+        }
+
+        let mut probes = Vec::new();
+        let mut hi = self.probe(source, &index, config, self.max_qp, &mut probes)?;
+        if !self.target.is_met(hi.score) {
+            let lo = self.probe(source, &index, config, self.min_qp, &mut probes)?;
+            if !self.target.is_met(lo.score) {
+                return Err(Error::InvalidArgument(format!(
+                    "{:?} is out of reach: QP {} scores {:.4}",
+                    self.target, lo.qp, lo.score
+                )));
+            }
+            let mut lo = lo;
+            while hi.qp - lo.qp > 1 && probes.len() < self.max_probes {
+                let qp = self.next_qp(&lo, &hi);
+                let probe = self.probe(source, &index, config, qp, &mut probes)?;
+                if self.target.is_met(probe.score) {
+                    lo = probe;
+                } else {
+                    hi = probe;
+                }
+            }
+        }
+
+        let best = probes
+            .iter()
+            .filter(|p| self.target.is_met(p.score))
+            .min_by_key(|p| (p.bytes, std::cmp::Reverse(p.qp)))
+            .copied()
+            .expect("a passing probe");
+        Ok(CrfResult { best, probes })
+    }
+
+    /// Interpolates the QP where the score crosses the target, between a
+    /// passing `lo` and a failing `hi`.
+    fn next_qp(&self, lo: &Probe, hi: &Probe) -> u32 {
+        let span = hi.qp - lo.qp;
+        let t = (lo.score - self.target.value()) / (lo.score - hi.score);
+        let guess = (lo.qp as f64 + t * span as f64).round() as u32;
+        let margin = (span / 4).max(1);
+        guess.clamp(lo.qp + margin, hi.qp - margin)
+    }
+
+    /// Encodes the clip at `qp` and scores the reconstruction.
+    fn probe(
+        &self,
+        source: &[Frame],
+        index: &HashMap<i64, usize>,
+        config: &Configuration,
+        qp: u32,
+        probes: &mut Vec<Probe>,
+    ) -> Result<Probe> {
+        let mut cfg = *config;
+        cfg.set_rc_mode(RcMode::CqpOrCrf)
+            .set_qp(qp)
+            .enable_recon(true);
+        let encoder = Encoder::builder()?.with_config(cfg).build()?;
+        let (mut sender, mut receiver) = encoder.split();
+        let target = self.target;
+
+        let (bytes, total, scored) = thread::scope(|scope| {
+            let collector = scope.spawn(move || -> Result<(usize, f64, usize)> {
+                let first = &source[0];
+                let mut recon = Frame::new(
+                    first.width(),
+                    first.height(),
+                    first.bit_depth(),
+                    first.color_format(),
+                );
+                let (mut bytes, mut total, mut scored) = (0, 0.0, 0);
+                let mut done = false;
+                while !done {
+                    match receiver.recv()? {
+                        Some(packet) => bytes += packet.data().len(),
+                        None => done = true,
+                    }
+                    while receiver.recv_recon(&mut recon)? {
+                        let Some(&i) = index.get(&recon.pts) else {
+                            return Err(Error::InvalidData(format!(
+                                "reconstructed picture has unknown pts {}",
+                                recon.pts
+                            )));
+                        };
+                        total += target.score(&source[i], &recon)?;
+                        scored += 1;
+                    }
+                }
+                Ok((bytes, total, scored))
+            });
+            let sent = source.iter().try_for_each(|frame| sender.send_frame(frame));
+            // Finish even after a failed send so the collector sees end of stream.
+            let finished = sender.finish();
+            let collected = collector.join().expect("recon collector panicked");
+            sent.and(finished).and(collected)
+        })?;
+        if scored != source.len() {
+            return Err(Error::InvalidData(format!(
+                "encoder returned {scored} of {} reconstructed pictures",
+                source.len()
+            )));
+        }
+        let probe = Probe {
+            qp,
+            score: total / scored as f64,
+            bytes,
+        };
+        probes.push(probe);
+        Ok(probe)
+    }
+}
+
+/// Finds the cheapest CRF meeting `target` on `source` with the default
+/// [`CrfSearch`].
+#[cfg(feature = "encoder")]
+pub fn find_crf(source: &[Frame], config: &Configuration, target: Target) -> Result<CrfResult> {
+    CrfSearch::new(target).run(source, config)
+}
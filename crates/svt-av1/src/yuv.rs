@@ -0,0 +1,437 @@
+use std::io::{ErrorKind, Read};
+
+use crate::config::{BitDepth, ColorFormat};
+use crate::frame::Frame;
+use crate::{Error, Result};
+
+/// Memory layout of a raw YUV picture as produced by capture devices and files.
+///
+/// Planar layouts store Y, U and V one after another. Semi-planar layouts store
+/// Y followed by a single plane of interleaved U/V pairs. The `P*` layouts use
+/// 16-bit little-endian samples with the value held in the most significant bits.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PixelLayout {
+    I420,
+    I422,
+    I444,
+    Yuv400,
+    Nv12,
+    Nv16,
+    Nv24,
+    P010,
+    P210,
+    P410,
+}
+
+impl PixelLayout {
+    /// Fully planar layout for the given chroma format.
+    pub fn planar(fmt: ColorFormat) -> Self {
+        match fmt {
+            ColorFormat::Yuv400 => PixelLayout::Yuv400,
+            ColorFormat::Yuv420 => PixelLayout::I420,
+            ColorFormat::Yuv422 => PixelLayout::I422,
+            ColorFormat::Yuv444 => PixelLayout::I444,
+        }
+    }
+
+    /// Semi-planar layout (NV12/NV16/NV24) for the given chroma format.
+    /// Monochrome has no chroma to interleave and maps to `Yuv400`.
+    pub fn semi_planar(fmt: ColorFormat) -> Self {
+        match fmt {
+            ColorFormat::Yuv400 => PixelLayout::Yuv400,
+            ColorFormat::Yuv420 => PixelLayout::Nv12,
+            ColorFormat::Yuv422 => PixelLayout::Nv16,
+            ColorFormat::Yuv444 => PixelLayout::Nv24,
+        }
+    }
+
+    /// MSB-aligned 16-bit semi-planar layout (P010/P210/P410) for the given chroma format.
+    /// Monochrome maps to `Yuv400`.
+    pub fn semi_planar_msb(fmt: ColorFormat) -> Self {
+        match fmt {
+            ColorFormat::Yuv400 => PixelLayout::Yuv400,
+            ColorFormat::Yuv420 => PixelLayout::P010,
+            ColorFormat::Yuv422 => PixelLayout::P210,
+            ColorFormat::Yuv444 => PixelLayout::P410,
+        }
+    }
+
+    pub fn color_format(self) -> ColorFormat {
+        match self {
+            PixelLayout::Yuv400 => ColorFormat::Yuv400,
+            PixelLayout::I420 | PixelLayout::Nv12 | PixelLayout::P010 => ColorFormat::Yuv420,
+            PixelLayout::I422 | PixelLayout::Nv16 | PixelLayout::P210 => ColorFormat::Yuv422,
+            PixelLayout::I444 | PixelLayout::Nv24 | PixelLayout::P410 => ColorFormat::Yuv444,
+        }
+    }
+
+    pub fn is_semi_planar(self) -> bool {
+        matches!(
+            self,
+            PixelLayout::Nv12
+                | PixelLayout::Nv16
+                | PixelLayout::Nv24
+                | PixelLayout::P010
+                | PixelLayout::P210
+                | PixelLayout::P410
+        )
+    }
+
+    /// True for layouts whose samples are 16-bit with the value in the high bits.
+    pub fn is_msb_aligned(self) -> bool {
+        matches!(
+            self,
+            PixelLayout::P010 | PixelLayout::P210 | PixelLayout::P410
+        )
+    }
+}
+
+/// Geometry of a raw YUV stream.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct RawYuvFormat {
+    pub width: u32,
+    pub height: u32,
+    pub layout: PixelLayout,
+    /// Bit depth of the samples. Above 8 bits, planar and NV layouts use 16-bit
+    /// little-endian LSB-aligned samples; `P*` layouts are always 16-bit MSB-aligned.
+    pub bit_depth: BitDepth,
+    /// Bytes per luma row including padding. `None` means tightly packed.
+    pub luma_stride: Option<usize>,
+    /// Bytes per chroma row including padding (the interleaved row for
+    /// semi-planar layouts). `None` means tightly packed.
+    pub chroma_stride: Option<usize>,
+}
+
+impl RawYuvFormat {
+    /// Tightly packed format with no row padding.
+    pub fn new(width: u32, height: u32, layout: PixelLayout, bit_depth: BitDepth) -> Self {
+        Self {
+            width,
+            height,
+            layout,
+            bit_depth,
+            luma_stride: None,
+            chroma_stride: None,
+        }
+    }
+
+    /// Sets the luma and chroma row strides, in bytes.
+    pub fn with_strides(mut self, luma: usize, chroma: usize) -> Self {
+        self.luma_stride = Some(luma);
+        self.chroma_stride = Some(chroma);
+        self
+    }
+
+    /// Bytes per input sample.
+    pub fn bytes_per_sample(&self) -> usize {
+        if self.layout.is_msb_aligned() || self.bit_depth != BitDepth::Eight {
+            2
+        } else {
+            1
+        }
+    }
+
+    fn chroma_dims(&self) -> (usize, usize) {
+        let (ss_x, ss_y) = self.layout.color_format().subsampling();
+        (
+            ((self.width + ss_x) >> ss_x) as usize,
+            ((self.height + ss_y) >> ss_y) as usize,
+        )
+    }
+
+    fn luma_row_bytes(&self) -> usize {
+        self.width as usize * self.bytes_per_sample()
+    }
+
+    fn chroma_row_bytes(&self) -> usize {
+        if self.layout == PixelLayout::Yuv400 {
+            return 0;
+        }
+        let (cw, _) = self.chroma_dims();
+        let per_row = cw * self.bytes_per_sample();
+        if self.layout.is_semi_planar() {
+            per_row * 2
+        } else {
+            per_row
+        }
+    }
+
+    pub fn luma_stride(&self) -> usize {
+        self.luma_stride.unwrap_or_else(|| self.luma_row_bytes())
+    }
+
+    pub fn chroma_stride(&self) -> usize {
+        self.chroma_stride
+            .unwrap_or_else(|| self.chroma_row_bytes())
+    }
+
+    /// Number of bytes occupied by one picture.
+    pub fn frame_size(&self) -> usize {
+        let luma = self.luma_stride() * self.height as usize;
+        if self.layout == PixelLayout::Yuv400 {
+            return luma;
+        }
+        let (_, ch) = self.chroma_dims();
+        let chroma_planes = if self.layout.is_semi_planar() { 1 } else { 2 };
+        luma + self.chroma_stride() * ch * chroma_planes
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidArgument(format!(
+                "raw YUV dimensions must be non-zero, got {}x{}",
+                self.width, self.height
+            )));
+        }
+        if self.luma_stride() < self.luma_row_bytes() {
+            return Err(Error::InvalidArgument(format!(
+                "luma stride {} is smaller than a row ({} bytes)",
+                self.luma_stride(),
+                self.luma_row_bytes()
+            )));
+        }
+        if self.chroma_stride() < self.chroma_row_bytes() {
+            return Err(Error::InvalidArgument(format!(
+                "chroma stride {} is smaller than a row ({} bytes)",
+                self.chroma_stride(),
+                self.chroma_row_bytes()
+            )));
+        }
+        Ok(())
+    }
+
+    fn check_frame(&self, frame: &Frame) -> Result<()> {
+        if frame.width() != self.width
+            || frame.height() != self.height
+            || frame.bit_depth() != self.bit_depth
+            || frame.color_format() != self.layout.color_format()
+        {
+            return Err(Error::InvalidArgument(
+                "frame geometry does not match the raw YUV format".into(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Whether pictures in this format are byte-identical to `Frame` planes
+    /// laid end to end: planar, LSB-aligned and without row padding.
+    fn matches_frame_layout(&self) -> bool {
+        !self.layout.is_semi_planar()
+            && !self.layout.is_msb_aligned()
+            && self.luma_stride() == self.luma_row_bytes()
+            && self.chroma_stride() == self.chroma_row_bytes()
+    }
+
+    /// Allocates a frame matching this format.
+    pub fn new_frame(&self) -> Frame {
+        Frame::new(
+            self.width,
+            self.height,
+            self.bit_depth,
+            self.layout.color_format(),
+        )
+    }
+
+    /// Converts one picture in this layout into `frame`, deinterleaving chroma
+    /// and normalising samples to LSB-aligned values.
+    ///
+    /// `data` must hold at least `frame_size()` bytes and `frame` must have been
+    /// created for this format (see `new_frame`).
+    pub fn unpack(&self, data: &[u8], frame: &mut Frame) -> Result<()> {
+        self.validate()?;
+        if data.len() < self.frame_size() {
+            return Err(Error::InvalidArgument(format!(
+                "raw YUV buffer holds {} bytes, expected {}",
+                data.len(),
+                self.frame_size()
+            )));
+        }
+        self.check_frame(frame)?;
+
+        let in_bps = self.bytes_per_sample();
+        let out_bps = frame.bytes_per_sample();
+        let shift = if self.layout.is_msb_aligned() {
+            16 - self.bit_depth.bits()
+        } else {
+            0
+        };
+        let luma_stride = self.luma_stride();
+        let chroma_stride = self.chroma_stride();
+
+        for y in 0..self.height as usize {
+            let src = &data[y * luma_stride..][..self.luma_row_bytes()];
+            copy_samples(src, in_bps, shift, 1, frame.row_mut(0, y), out_bps);
+        }
+        if self.layout == PixelLayout::Yuv400 {
+            return Ok(());
+        }
+
+        let (_, ch) = self.chroma_dims();
+        let chroma_base = luma_stride * self.height as usize;
+        let row_bytes = self.chroma_row_bytes();
+        if self.layout.is_semi_planar() {
+            for y in 0..ch {
+                let src = &data[chroma_base + y * chroma_stride..][..row_bytes];
+                copy_samples(src, in_bps, shift, 2, frame.row_mut(1, y), out_bps);
+                copy_samples(
+                    &src[in_bps..],
+                    in_bps,
+                    shift,
+                    2,
+                    frame.row_mut(2, y),
+                    out_bps,
+                );
+            }
+        } else {
+            for p in 1..3 {
+                let plane_base = chroma_base + (p - 1) * chroma_stride * ch;
+                for y in 0..ch {
+                    let src = &data[plane_base + y * chroma_stride..][..row_bytes];
+                    copy_samples(src, in_bps, shift, 1, frame.row_mut(p, y), out_bps);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Copies every `step`-th sample of `src` into the packed row `dst`, shifting
+/// 16-bit input right by `shift` and storing `out_bps`-byte little-endian samples.
+fn copy_samples(
+    src: &[u8],
+    in_bps: usize,
+    shift: u32,
+    step: usize,
+    dst: &mut [u8],
+    out_bps: usize,
+) {
+    if in_bps == out_bps && step == 1 && shift == 0 {
+        dst.copy_from_slice(&src[..dst.len()]);
+        return;
+    }
+    let stride = in_bps * step;
+    for (out, at) in dst.chunks_exact_mut(out_bps).zip((0..).step_by(stride)) {
+        let v = if in_bps == 1 {
+            src[at] as u16
+        } else {
+            u16::from_le_bytes([src[at], src[at + 1]]) >> shift
+        };
+        if out_bps == 1 {
+            out[0] = v as u8;
+        } else {
+            out.copy_from_slice(&v.to_le_bytes());
+        }
+    }
+}
+
+/// Reads consecutive raw YUV pictures from a byte stream into [`Frame`]s.
+///
+/// Pictures are assigned increasing `pts` values starting at zero.
+pub struct RawYuvReader<R> {
+    inner: R,
+    format: RawYuvFormat,
+    buf: Vec<u8>,
+    next_pts: i64,
+}
+
+impl<R: Read> RawYuvReader<R> {
+    pub fn new(inner: R, format: RawYuvFormat) -> Result<Self> {
+        format.validate()?;
+        let buf = if format.matches_frame_layout() {
+            Vec::new()
+        } else {
+            vec![0u8; format.frame_size()]
+        };
+        Ok(Self {
+            inner,
+            buf,
+            format,
+            next_pts: 0,
+        })
+    }
+
+    pub fn format(&self) -> &RawYuvFormat {
+        &self.format
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Reads the next picture into `frame`, reusing its allocation.
+    ///
+    /// Planar, unpadded input is read straight into the frame's planes; other
+    /// layouts go through an intermediate buffer and [`RawYuvFormat::unpack`].
+    ///
+    /// Returns `Ok(false)` on a clean end of stream; a truncated trailing picture
+    /// is reported as an `UnexpectedEof` I/O error.
+    pub fn read_into(&mut self, frame: &mut Frame) -> Result<bool> {
+        let expected = self.format.frame_size();
+        if self.format.matches_frame_layout() {
+            self.format.check_frame(frame)?;
+            let mut filled = 0;
+            for p in 0..frame.num_planes() {
+                let plane = frame.plane_mut(p);
+                let n = fill(&mut self.inner, plane)?;
+                filled += n;
+                if n < plane.len() {
+                    break;
+                }
+            }
+            if !check_filled(filled, expected)? {
+                return Ok(false);
+            }
+        } else {
+            let filled = fill(&mut self.inner, &mut self.buf)?;
+            if !check_filled(filled, expected)? {
+                return Ok(false);
+            }
+            self.format.unpack(&self.buf, frame)?;
+        }
+        frame.pts = self.next_pts;
+        self.next_pts += 1;
+        Ok(true)
+    }
+
+    /// Reads the next picture into a newly allocated frame.
+    pub fn read_frame(&mut self) -> Result<Option<Frame>> {
+        let mut frame = self.format.new_frame();
+        Ok(self.read_into(&mut frame)?.then_some(frame))
+    }
+}
+
+/// Reads until `buf` is full or the stream ends, returning the bytes read.
+fn fill(inner: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match inner.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+/// `Ok(false)` at a clean end of stream, an error for a truncated picture.
+fn check_filled(filled: usize, expected: usize) -> Result<bool> {
+    if filled == 0 {
+        return Ok(false);
+    }
+    if filled < expected {
+        return Err(Error::Io(std::io::Error::new(
+            ErrorKind::UnexpectedEof,
+            format!("truncated raw YUV picture: {filled} of {expected} bytes"),
+        )));
+    }
+    Ok(true)
+}
+
+impl<R: Read> Iterator for RawYuvReader<R> {
+    type Item = Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_frame().transpose()
+    }
+}